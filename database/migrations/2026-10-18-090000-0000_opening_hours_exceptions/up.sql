@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE opening_hours_exceptions (
+    id SERIAL NOT NULL PRIMARY KEY,
+    farm_id INTEGER NOT NULL,
+    start_date DATE NOT NULL,
+    end_date DATE NOT NULL,
+    open TIME,
+    close TIME,
+    description TEXT,
+    FOREIGN KEY (farm_id) REFERENCES farms(id) ON DELETE CASCADE,
+    CHECK (start_date <= end_date),
+    CHECK ((open IS NULL) = (close IS NULL))
+);
@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE contact DROP COLUMN address_public;
+ALTER TABLE contact DROP COLUMN phone_public;
+ALTER TABLE contact DROP COLUMN email_public;
+ALTER TABLE contact DROP CONSTRAINT contact_farm_id_key;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+DO $$
+BEGIN
+    IF EXISTS (
+        SELECT farm_id FROM contact GROUP BY farm_id
+        HAVING count(DISTINCT email) > 1 OR count(DISTINCT phone) > 1 OR count(DISTINCT address) > 1
+    ) THEN
+        RAISE EXCEPTION 'Farms with conflicting contacts have to be merged by hand';
+    END IF;
+END $$;
+UPDATE contact SET email = merged.email, phone = merged.phone, address = merged.address
+FROM (
+    SELECT max(id) AS id, max(email) AS email, max(phone) AS phone, max(address) AS address
+    FROM contact GROUP BY farm_id HAVING count(*) > 1
+) merged
+WHERE contact.id = merged.id;
+DELETE FROM contact a USING contact b WHERE a.farm_id = b.farm_id AND a.id < b.id;
+ALTER TABLE contact ADD CONSTRAINT contact_farm_id_key UNIQUE (farm_id);
+ALTER TABLE contact ADD COLUMN email_public BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE contact ADD COLUMN phone_public BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE contact ADD COLUMN address_public BOOLEAN NOT NULL DEFAULT TRUE;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS shop_type_translations;
+ALTER TABLE shop_types DROP COLUMN retired;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE shop_types ADD COLUMN retired BOOLEAN NOT NULL DEFAULT FALSE;
+SELECT setval(pg_get_serial_sequence('shop_types', 'id'), (SELECT MAX(id) FROM shop_types));
+
+CREATE TABLE shop_type_translations (
+    id SERIAL NOT NULL PRIMARY KEY,
+    shop_type_id INTEGER NOT NULL,
+    language TEXT NOT NULL,
+    name TEXT NOT NULL,
+    FOREIGN KEY (shop_type_id) REFERENCES shop_types(id) ON DELETE CASCADE,
+    UNIQUE (shop_type_id, language)
+);
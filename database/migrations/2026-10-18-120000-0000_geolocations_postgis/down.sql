@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS geolocations_position_idx;
+ALTER TABLE geolocations DROP COLUMN position;
+ALTER TABLE geolocations ALTER COLUMN lat TYPE REAL;
+ALTER TABLE geolocations ALTER COLUMN lon TYPE REAL;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS postgis;
+
+ALTER TABLE geolocations ALTER COLUMN lat TYPE DOUBLE PRECISION;
+ALTER TABLE geolocations ALTER COLUMN lon TYPE DOUBLE PRECISION;
+ALTER TABLE geolocations ADD COLUMN position geography(Point, 4326)
+    GENERATED ALWAYS AS (ST_SetSRID(ST_MakePoint(lon, lat), 4326)::geography) STORED;
+
+CREATE INDEX geolocations_position_idx ON geolocations USING GIST (position);
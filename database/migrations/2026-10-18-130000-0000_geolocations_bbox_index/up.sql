@@ -0,0 +1,2 @@
+-- Your SQL goes here
+CREATE INDEX geolocations_position_geometry_idx ON geolocations USING GIST ((position::geometry));
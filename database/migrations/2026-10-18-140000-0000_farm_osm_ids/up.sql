@@ -0,0 +1,2 @@
+-- Your SQL goes here
+ALTER TABLE farms ADD COLUMN osm_id TEXT UNIQUE;
@@ -0,0 +1,17 @@
+-- This file should undo anything in `up.sql`
+DELETE FROM opening_hours WHERE farm_location_id IS NOT NULL;
+ALTER TABLE opening_hours DROP COLUMN farm_location_id;
+
+DELETE FROM farm_shop_types WHERE farm_location_id IS NOT NULL;
+DROP INDEX farm_shop_types_location_unique;
+DROP INDEX farm_shop_types_farm_unique;
+ALTER TABLE farm_shop_types DROP COLUMN farm_location_id;
+ALTER TABLE farm_shop_types ADD CONSTRAINT farm_shop_types_farm_id_shop_type_id_key UNIQUE (farm_id, shop_type_id);
+
+DELETE FROM geolocations WHERE id IN (
+    SELECT location_id FROM farm_locations a
+    WHERE EXISTS (SELECT 1 FROM farm_locations b WHERE b.farm_id = a.farm_id AND b.id < a.id)
+);
+DROP INDEX farm_locations_farm_id_idx;
+ALTER TABLE farm_locations DROP COLUMN label;
+ALTER TABLE farm_locations ADD CONSTRAINT farm_locations_farm_id_key UNIQUE (farm_id);
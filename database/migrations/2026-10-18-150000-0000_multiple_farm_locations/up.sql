@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE farm_locations DROP CONSTRAINT farm_locations_farm_id_key;
+ALTER TABLE farm_locations ADD COLUMN label TEXT;
+CREATE INDEX farm_locations_farm_id_idx ON farm_locations (farm_id);
+
+-- Shop types and opening hours with a location only apply to that location, all others to the farm
+ALTER TABLE farm_shop_types ADD COLUMN farm_location_id INTEGER REFERENCES farm_locations(id) ON DELETE CASCADE;
+ALTER TABLE farm_shop_types DROP CONSTRAINT farm_shop_types_farm_id_shop_type_id_key;
+CREATE UNIQUE INDEX farm_shop_types_farm_unique ON farm_shop_types (farm_id, shop_type_id) WHERE farm_location_id IS NULL;
+CREATE UNIQUE INDEX farm_shop_types_location_unique ON farm_shop_types (farm_location_id, shop_type_id) WHERE farm_location_id IS NOT NULL;
+
+ALTER TABLE opening_hours ADD COLUMN farm_location_id INTEGER REFERENCES farm_locations(id) ON DELETE CASCADE;
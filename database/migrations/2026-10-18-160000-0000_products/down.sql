@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS products;
+DROP TABLE IF EXISTS product_categories;
@@ -0,0 +1,23 @@
+-- Your SQL goes here
+CREATE TABLE product_categories (
+    id SERIAL NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE
+);
+
+INSERT INTO product_categories(name) VALUES
+    ('vegetables'), ('fruit'), ('dairy'), ('eggs'), ('meat'), ('bakery'), ('beverages'), ('other');
+
+CREATE TABLE products (
+    id SERIAL NOT NULL PRIMARY KEY,
+    farm_id INTEGER NOT NULL,
+    category_id INTEGER,
+    name TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    -- in the minor unit of the currency, like cents
+    price INTEGER CHECK (price >= 0),
+    currency TEXT NOT NULL DEFAULT 'EUR',
+    organic BOOLEAN NOT NULL DEFAULT FALSE,
+    FOREIGN KEY (farm_id) REFERENCES farms(id) ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES product_categories(id) ON DELETE SET NULL,
+    UNIQUE (farm_id, name)
+);
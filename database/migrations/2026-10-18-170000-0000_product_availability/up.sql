@@ -0,0 +1,12 @@
+-- Your SQL goes here
+CREATE TABLE product_availability (
+    id SERIAL NOT NULL PRIMARY KEY,
+    product_id INTEGER NOT NULL,
+    start_month INTEGER NOT NULL CHECK (start_month BETWEEN 1 AND 12),
+    start_day INTEGER NOT NULL CHECK (start_day BETWEEN 1 AND 31),
+    end_month INTEGER NOT NULL CHECK (end_month BETWEEN 1 AND 12),
+    end_day INTEGER NOT NULL CHECK (end_day BETWEEN 1 AND 31),
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+);
+
+CREATE INDEX product_availability_product_id_idx ON product_availability (product_id);
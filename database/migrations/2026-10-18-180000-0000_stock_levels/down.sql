@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS farm_api_keys;
+ALTER TABLE products DROP COLUMN stock_updated;
+ALTER TABLE products DROP COLUMN stock;
@@ -0,0 +1,12 @@
+-- Your SQL goes here
+ALTER TABLE products ADD COLUMN stock INTEGER CHECK (stock >= 0);
+ALTER TABLE products ADD COLUMN stock_updated TIMESTAMPTZ;
+
+CREATE TABLE farm_api_keys (
+    id SERIAL NOT NULL PRIMARY KEY,
+    farm_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    key_digest BYTEA NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (farm_id) REFERENCES farms(id) ON DELETE CASCADE
+);
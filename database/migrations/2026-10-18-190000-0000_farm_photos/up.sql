@@ -0,0 +1,13 @@
+-- Your SQL goes here
+CREATE TABLE farm_photos (
+    id SERIAL NOT NULL PRIMARY KEY,
+    farm_id INTEGER NOT NULL,
+    storage_id UUID NOT NULL UNIQUE,
+    format TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (farm_id) REFERENCES farms(id) ON DELETE CASCADE
+);
+
+CREATE INDEX farm_photos_farm_id ON farm_photos (farm_id);
@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+DROP FUNCTION IF EXISTS farm_distance;
+DROP TRIGGER IF EXISTS farm_search_contact ON contact;
+DROP TRIGGER IF EXISTS farm_search_products ON products;
+DROP TRIGGER IF EXISTS farm_search_farms ON farms;
+DROP FUNCTION IF EXISTS farm_search_trigger;
+DROP FUNCTION IF EXISTS refresh_farm_search;
+DROP TABLE IF EXISTS farm_search;
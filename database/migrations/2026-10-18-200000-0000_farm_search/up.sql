@@ -0,0 +1,68 @@
+-- Your SQL goes here
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Searchable text of every farm, kept up to date by the triggers below. Addresses are only
+-- included while they are public.
+CREATE TABLE farm_search (
+    farm_id INTEGER NOT NULL PRIMARY KEY,
+    name TEXT NOT NULL,
+    products TEXT NOT NULL,
+    address TEXT NOT NULL,
+    document TSVECTOR NOT NULL GENERATED ALWAYS AS (
+        setweight(to_tsvector('simple', name), 'A') ||
+        setweight(to_tsvector('simple', products), 'B') ||
+        setweight(to_tsvector('simple', address), 'C')
+    ) STORED,
+    FOREIGN KEY (farm_id) REFERENCES farms(id) ON DELETE CASCADE
+);
+
+CREATE INDEX farm_search_document_idx ON farm_search USING GIN (document);
+CREATE INDEX farm_search_name_trgm_idx ON farm_search USING GIN (name gin_trgm_ops);
+CREATE INDEX farm_search_products_trgm_idx ON farm_search USING GIN (products gin_trgm_ops);
+CREATE INDEX farm_search_address_trgm_idx ON farm_search USING GIN (address gin_trgm_ops);
+
+CREATE FUNCTION refresh_farm_search(target INTEGER) RETURNS VOID AS $$
+    INSERT INTO farm_search (farm_id, name, products, address)
+    SELECT f.id,
+        f.name,
+        COALESCE((SELECT string_agg(p.name, ' ' ORDER BY p.name) FROM products p WHERE p.farm_id = f.id), ''),
+        COALESCE((SELECT c.address FROM contact c WHERE c.farm_id = f.id AND c.address_public), '')
+    FROM farms f
+    WHERE f.id = target
+    ON CONFLICT (farm_id) DO UPDATE
+        SET name = EXCLUDED.name, products = EXCLUDED.products, address = EXCLUDED.address;
+$$ LANGUAGE SQL;
+
+CREATE FUNCTION farm_search_trigger() RETURNS TRIGGER AS $$
+BEGIN
+    IF TG_TABLE_NAME = 'farms' THEN
+        PERFORM refresh_farm_search(NEW.id);
+    ELSIF TG_OP = 'DELETE' THEN
+        PERFORM refresh_farm_search(OLD.farm_id);
+    ELSE
+        PERFORM refresh_farm_search(NEW.farm_id);
+        IF TG_OP = 'UPDATE' AND OLD.farm_id <> NEW.farm_id THEN
+            PERFORM refresh_farm_search(OLD.farm_id);
+        END IF;
+    END IF;
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER farm_search_farms AFTER INSERT OR UPDATE OF name ON farms
+    FOR EACH ROW EXECUTE FUNCTION farm_search_trigger();
+CREATE TRIGGER farm_search_products AFTER INSERT OR UPDATE OF name, farm_id OR DELETE ON products
+    FOR EACH ROW EXECUTE FUNCTION farm_search_trigger();
+CREATE TRIGGER farm_search_contact AFTER INSERT OR UPDATE OR DELETE ON contact
+    FOR EACH ROW EXECUTE FUNCTION farm_search_trigger();
+
+SELECT refresh_farm_search(id) FROM farms;
+
+-- Distance in metres from the position to the closest location of the farm, NULL without position.
+CREATE FUNCTION farm_distance(target INTEGER, from_lat DOUBLE PRECISION, from_lon DOUBLE PRECISION)
+RETURNS DOUBLE PRECISION AS $$
+    SELECT min(ST_Distance(g.position, ST_SetSRID(ST_MakePoint(from_lon, from_lat), 4326)::geography, false))
+    FROM farm_locations fl
+    JOIN geolocations g ON g.id = fl.location_id
+    WHERE fl.farm_id = target AND from_lat IS NOT NULL AND from_lon IS NOT NULL;
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX IF EXISTS products_name_prefix_idx;
+DROP INDEX IF EXISTS farms_name_prefix_idx;
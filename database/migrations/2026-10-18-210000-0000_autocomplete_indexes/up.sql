@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- Compared byte-wise, the lower-cased names serve both prefix matches and ordering by name.
+CREATE INDEX farms_name_prefix_idx ON farms ((lower(name) COLLATE "C"));
+CREATE INDEX products_name_prefix_idx ON products ((lower(name) COLLATE "C"));
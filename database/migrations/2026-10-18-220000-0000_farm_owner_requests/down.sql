@@ -0,0 +1,11 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE IF EXISTS farm_owner_requests;
+
+-- enum values cannot be dropped, so the type is created again without 'REJECTED'
+UPDATE users SET farmowner = 'NO' WHERE farmowner = 'REJECTED';
+ALTER TYPE farm_admin_status RENAME TO farm_admin_status_old;
+CREATE TYPE farm_admin_status AS ENUM('NO', 'YES', 'REQUESTED');
+ALTER TABLE users ALTER COLUMN farmowner DROP DEFAULT;
+ALTER TABLE users ALTER COLUMN farmowner TYPE farm_admin_status USING farmowner::text::farm_admin_status;
+ALTER TABLE users ALTER COLUMN farmowner SET DEFAULT 'NO';
+DROP TYPE farm_admin_status_old;
@@ -0,0 +1,19 @@
+-- Your SQL goes here
+ALTER TYPE farm_admin_status ADD VALUE 'REJECTED';
+
+CREATE TABLE farm_owner_requests (
+    id SERIAL NOT NULL PRIMARY KEY,
+    user_id INTEGER NOT NULL,
+    motivation TEXT NOT NULL,
+    evidence TEXT,
+    status farm_admin_status NOT NULL DEFAULT 'REQUESTED',
+    created TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    decided TIMESTAMPTZ,
+    decided_by INTEGER,
+    reason TEXT,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (decided_by) REFERENCES users(id) ON DELETE SET NULL
+);
+
+-- a user has at most one request waiting for a decision
+CREATE UNIQUE INDEX farm_owner_requests_pending_idx ON farm_owner_requests (user_id) WHERE status = 'REQUESTED';
@@ -0,0 +1,101 @@
+//! API keys letting devices like vending machines act for a farm without a user login. A key is
+//! given out once as `<id>.<secret>`, only the SHA-256 digest of the secret is stored. Secrets are
+//! random, so unlike passwords they need no slow hash to resist guessing.
+
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::SaltString;
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use crate::{DbResult, FarmDB};
+use crate::farm::farm_admin_exists;
+use crate::schema::farm_api_keys;
+
+#[derive(Identifiable, Queryable, Selectable)]
+pub struct FarmApiKey {
+    pub id: i32,
+    pub farm_id: i32,
+    pub name: String,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farm_api_keys)]
+struct NewFarmApiKey {
+    farm_id: i32,
+    name: String,
+    key_digest: Vec<u8>,
+}
+
+pub async fn list_api_keys(db: &FarmDB, farm_id: i32) -> DbResult<Vec<FarmApiKey>> {
+    let keys = db.run(move |conn| {
+        farm_api_keys::table
+            .filter(farm_api_keys::farm_id.eq(farm_id))
+            .select(FarmApiKey::as_select())
+            .order(farm_api_keys::id)
+            .load(conn)
+    }).await?;
+    Ok(keys)
+}
+
+/// Creates a key together with its only plain text copy. Returns `None` if the user is no admin of
+/// the farm.
+pub async fn create_api_key(
+    db: &FarmDB,
+    user_id: i32,
+    farm_id: i32,
+    name: String,
+) -> DbResult<Option<(FarmApiKey, String)>> {
+    let secret = SaltString::generate(&mut OsRng).to_string();
+    let key_digest = digest(&secret);
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, farm_id)? {
+            return Ok(None);
+        }
+        let key = diesel::insert_into(farm_api_keys::table)
+            .values(NewFarmApiKey { farm_id, name, key_digest })
+            .returning(FarmApiKey::as_returning())
+            .get_result(conn)?;
+        let plain = format!("{}.{secret}", key.id);
+        Ok(Some((key, plain)))
+    }).await
+}
+
+/// Id of the farm the key belongs to, `None` if the key is unknown or malformed.
+pub async fn farm_of_api_key(db: &FarmDB, key: String) -> DbResult<Option<i32>> {
+    let parsed = key
+        .split_once('.')
+        .and_then(|(id, secret)| Some((id.parse::<i32>().ok()?, secret.to_string())));
+    let Some((id, secret)) = parsed else {
+        return Ok(None);
+    };
+    let stored: Option<(i32, Vec<u8>)> = db.run(move |conn| {
+        farm_api_keys::table
+            .find(id)
+            .select((farm_api_keys::farm_id, farm_api_keys::key_digest))
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(stored
+        .filter(|(_, stored)| bool::from(stored.ct_eq(&digest(&secret))))
+        .map(|(farm_id, _)| farm_id))
+}
+
+fn digest(secret: &str) -> Vec<u8> {
+    Sha256::digest(secret.as_bytes()).to_vec()
+}
+
+/// Returns `false` if the user is no admin of the farm or the farm has no such key.
+pub async fn delete_api_key(db: &FarmDB, user_id: i32, farm_id: i32, key_id: i32) -> DbResult<bool> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, farm_id)? {
+            return Ok(false);
+        }
+        let deleted = diesel::delete(farm_api_keys::table)
+            .filter(farm_api_keys::id.eq(key_id))
+            .filter(farm_api_keys::farm_id.eq(farm_id))
+            .execute(conn)?;
+        Ok(deleted > 0)
+    }).await
+}
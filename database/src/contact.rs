@@ -0,0 +1,47 @@
+//! Normalisation of contact details, shared by the API and the OSM import.
+
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// Pattern of valid email addresses, matched against lowercase addresses.
+pub const EMAIL_REGEX: &str = include_str!("email_regex.txt");
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| Regex::new(EMAIL_REGEX).expect("Cannot parse email regex"));
+
+pub fn is_valid_email(value: &str) -> bool {
+    EMAIL.is_match(value)
+}
+
+/// Normalizes an international phone number to E.164, e.g. `+41 (0)79 123 45 67` to
+/// `+41791234567`. Numbers have to start with a country code, either as `+` or `00`.
+pub fn normalize_phone_number(value: &str) -> Result<String, Vec<String>> {
+    let compact: String = value
+        .replace("(0)", "")
+        .chars()
+        .filter(|c| !" -./()".contains(*c))
+        .collect();
+    let digits = if let Some(digits) = compact.strip_prefix('+') {
+        digits
+    } else if let Some(digits) = compact.strip_prefix("00") {
+        digits
+    } else {
+        return Err(vec![format!("`{value}` has to start with a country code like `+41`")]);
+    };
+    let mut messages = Vec::new();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        messages.push(format!("`{value}` contains invalid characters"));
+    } else if digits.starts_with('0') {
+        messages.push(format!("`{value}` has an invalid country code"));
+    }
+    if !(7..=15).contains(&digits.len()) {
+        messages.push(format!(
+            "Expected between 7 and 15 digits but got {}",
+            digits.len()
+        ));
+    }
+    if messages.is_empty() {
+        Ok(format!("+{digits}"))
+    } else {
+        Err(messages)
+    }
+}
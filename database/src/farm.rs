@@ -1,7 +1,22 @@
-use crate::{DbResult, FarmDB};
-use crate::schema::{farm_admins, farm_locations, farm_shop_types, farms, geolocations, opening_hours, shop_types};
+use crate::{DatabaseError, DbResult, FarmDB};
+use crate::schema::{
+    contact, farm_admins, farm_locations, farm_shop_types, farms, geolocations, opening_hours, opening_hours_exceptions,
+    shop_types,
+};
+use chrono::{Datelike, Days, NaiveDate, NaiveDateTime};
+use diesel::dsl::{self, not};
+use diesel::sql_types::Integer;
+use diesel::pg::Pg;
 use diesel::prelude::*;
+use std::collections::HashMap;
 use uuid::Uuid;
+use crate::location::{
+    BoundingBox, FullLocation, MAX_WEB_MERCATOR_LATITUDE, NewGeoLocation, WEB_MERCATOR_SRID, geography_point,
+    geometry, insert_farm_location, locations_of_farms, st_distance, st_dwithin, st_snaptogrid, st_transform,
+};
+use crate::page::Page;
+use crate::photo::{FarmPhoto, photos_of_farms};
+use crate::product::{DateRange, FullProduct, farms_selling, products_of_farms};
 use crate::user::{FarmAdmin, User};
 
 #[derive(Identifiable, Queryable, Selectable)]
@@ -20,8 +35,8 @@ pub struct NewFarm {
 #[derive(Identifiable, Queryable, Selectable)]
 pub struct Geolocation {
     pub id: i32,
-    pub lat: f32,
-    pub lon: f32,
+    pub lat: f64,
+    pub lon: f64,
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -32,12 +47,14 @@ pub struct FarmLocation {
     pub id: i32,
     pub farm_id: i32,
     pub location_id: i32,
+    pub label: Option<String>,
 }
 
-#[derive(Queryable, Selectable)]
+#[derive(Identifiable, Queryable, Selectable)]
 pub struct ShopType {
     pub id: i32,
     pub name: String,
+    pub retired: bool,
 }
 
 #[derive(Identifiable, Queryable, Associations)]
@@ -50,21 +67,52 @@ pub struct FarmShopTypes {
     pub farm_id: i32,
 }
 
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(Farm))]
+#[diesel(table_name = contact)]
 pub struct Contact {
     pub id: i32,
+    pub farm_id: i32,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub email_public: bool,
+    pub phone_public: bool,
+    pub address_public: bool,
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = contact)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewContact {
+    pub farm_id: i32,
     pub email: Option<String>,
     pub phone: Option<String>,
     pub address: Option<String>,
+    pub email_public: bool,
+    pub phone_public: bool,
+    pub address_public: bool,
 }
 
 pub struct FullFarm {
     pub id: i32,
     pub name: String,
-    pub lat: f32,
-    pub lon: f32,
     pub ext_id: Uuid,
+    /// All locations, the main location first.
+    pub locations: Vec<FullLocation>,
     pub shop_types: Vec<ShopType>,
-    pub opening_hours: Vec<OpeningHours>
+    pub opening_hours: Vec<OpeningHours>,
+    pub opening_hours_exceptions: Vec<OpeningHoursException>,
+    pub contact: Option<Contact>,
+    pub products: Vec<FullProduct>,
+    pub photos: Vec<FarmPhoto>,
+}
+
+impl FullFarm {
+    /// Location changed by farm updates and shown where a farm needs a single position.
+    pub fn main_location(&self) -> Option<&FullLocation> {
+        self.locations.first()
+    }
 }
 
 #[derive(Identifiable, Queryable, Selectable, Associations)]
@@ -79,6 +127,129 @@ pub struct OpeningHours {
     pub close: chrono::NaiveTime,
 }
 
+/// Deviation from the weekly opening hours for every day from `start_date` to `end_date`. Without
+/// `open` and `close` the farm is closed on those days.
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(check_for_backend(diesel::pg::Pg))]
+#[diesel(belongs_to(Farm))]
+#[diesel(table_name = opening_hours_exceptions)]
+pub struct OpeningHoursException {
+    pub id: i32,
+    pub farm_id: i32,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub open: Option<chrono::NaiveTime>,
+    pub close: Option<chrono::NaiveTime>,
+    pub description: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = opening_hours_exceptions)]
+pub struct NewOpeningHoursException {
+    pub farm_id: i32,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: chrono::NaiveDate,
+    pub open: Option<chrono::NaiveTime>,
+    pub close: Option<chrono::NaiveTime>,
+    pub description: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = opening_hours)]
+pub struct NewOpeningHours {
+    pub farm_id: i32,
+    pub weekday: i32,
+    pub open: chrono::NaiveTime,
+    pub close: chrono::NaiveTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farm_shop_types)]
+struct NewFarmShopType {
+    farm_id: i32,
+    shop_type_id: i32,
+}
+
+/// Farm found by a radius search with its great-circle distance in kilometres.
+pub struct NearbyFarm {
+    pub farm: Farm,
+    pub distance: f64,
+}
+
+/// Farm with the position of one of its locations.
+pub struct LocatedFarm {
+    pub farm: Farm,
+    pub location_id: i32,
+    pub label: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+/// Farm locations aggregated into one grid cell.
+pub struct FarmCluster {
+    /// Centroid of the locations in the cell.
+    pub lat: f64,
+    pub lon: f64,
+    pub count: i64,
+}
+
+/// Optional criteria farms in list queries have to match.
+#[derive(Clone, Default)]
+pub struct FarmFilter {
+    pub shop_type: Option<String>,
+    /// Part of the name of a product the farm sells, ignoring case.
+    pub product: Option<String>,
+    /// Dates the farm has to sell the product on, or any product if none is given.
+    pub available: Option<DateRange>,
+    /// Local time the farm has to be open at, in the time zone its opening hours are read in.
+    pub open_at: Option<NaiveDateTime>,
+}
+
+/// Order of pages of farms. Each variant holds the sort key of the last farm of the previous page.
+pub enum FarmOrder {
+    /// By name, farms of the same name by id.
+    Name(Option<(String, i32)>),
+    /// Most recently added first.
+    Newest(Option<i32>),
+}
+
+/// Order of pages of farms found by a radius search, holding the sort key of the last farm of the
+/// previous page.
+pub enum NearbyOrder {
+    /// Nearest first by the distance in kilometres, farms at the same distance by id.
+    Distance(Option<(f64, i32)>),
+    /// By name, farms of the same name by id.
+    Name(Option<(String, i32)>),
+}
+
+/// Partial change of a farm. Every field that is `None` stays untouched.
+#[derive(Default)]
+pub struct FarmUpdate {
+    pub name: Option<String>,
+    pub location: Option<NewGeoLocation>,
+    pub shop_types: Option<Vec<i32>>,
+    pub opening_hours: Option<Vec<NewOpeningHours>>,
+    pub opening_hours_exceptions: Option<Vec<NewOpeningHoursException>>,
+    pub contact: Option<NewContact>,
+}
+
+impl FarmUpdate {
+    /// Assigns the rows to the farm, for updates built before the farm was created.
+    pub fn for_farm(mut self, farm_id: i32) -> Self {
+        self.opening_hours.iter_mut().flatten().for_each(|h| h.farm_id = farm_id);
+        self.opening_hours_exceptions.iter_mut().flatten().for_each(|e| e.farm_id = farm_id);
+        self.contact.iter_mut().for_each(|c| c.farm_id = farm_id);
+        self
+    }
+}
+
+/// Farm created by an import together with the details set right after.
+pub struct NewFarmEntry {
+    pub farm: NewFarm,
+    pub location: NewGeoLocation,
+    pub details: FarmUpdate,
+}
+
 #[derive(Insertable)]
 #[diesel(table_name = farm_admins)]
 pub struct NewFarmAdmin {
@@ -86,34 +257,263 @@ pub struct NewFarmAdmin {
     pub farm_id: i32,
 }
 
-pub async fn list_farms(db: &FarmDB) -> DbResult<Vec<Farm>> {
+pub async fn list_farms(db: &FarmDB, filter: FarmFilter) -> DbResult<Vec<Farm>> {
     let farms = db.run(move |conn| {
-        farms::table.select(Farm::as_select()).load(conn)
+        filtered_farms(filter).select(Farm::as_select()).load(conn)
     }).await?;
     Ok(farms)
 }
 
-pub async fn get_farms_near(db: &FarmDB, lat: f32, lon: f32, radius: f32) -> DbResult<Vec<Farm>> {
+/// Page of [list_farms] with up to `limit` farms.
+pub async fn list_farms_page(db: &FarmDB, filter: FarmFilter, order: FarmOrder, limit: i64) -> DbResult<Page<Farm>> {
     db.run(move |conn| {
-        let f = farms::table
+        let total = filtered_farms(filter.clone()).count().get_result(conn)?;
+        let rows = ordered_farms(filtered_farms(filter), order)
+            .select(Farm::as_select())
+            .limit(limit)
+            .load(conn)?;
+        Ok(Page { rows, total })
+    }).await
+}
+
+fn filtered_farms(filter: FarmFilter) -> farms::BoxedQuery<'static, Pg> {
+    let mut query = farms::table.into_boxed();
+    if let Some(shop_type) = filter.shop_type {
+        query = query.filter(farms::id.eq_any(farm_shop_types::table
+            .inner_join(shop_types::table)
+            .filter(shop_types::name.eq(shop_type))
+            .select(farm_shop_types::farm_id)));
+    }
+    if let Some(selling) = farms_selling(filter.product, filter.available) {
+        query = query.filter(farms::id.eq_any(selling));
+    }
+    if let Some(at) = filter.open_at {
+        query = query.filter(farms::id.eq_any(farms_open_at(at)));
+    }
+    query
+}
+
+/// Ids of the farms open at the local time `at`, that is with any location open. Locations with
+/// hours of their own use those, all others the hours of the farm. Ranges closing before they open
+/// continue on the following day. Days covered by an exception of the farm ignore the weekly hours
+/// of all locations and are only open during the hours of their exceptions.
+fn farms_open_at(at: NaiveDateTime) -> farms::BoxedQuery<'static, Pg, Integer> {
+    let day = at.date();
+    let previous_day = day - Days::new(1);
+    let time = at.time();
+    let weekday = day.weekday().num_days_from_monday() as i32;
+    let excepted = |day: NaiveDate| opening_hours_exceptions::table
+        .filter(opening_hours_exceptions::start_date.le(day))
+        .filter(opening_hours_exceptions::end_date.ge(day))
+        .select(opening_hours_exceptions::farm_id);
+    let location_hours = diesel::alias!(opening_hours as location_hours);
+    let own_hours = location_hours
+        .filter(location_hours.field(opening_hours::farm_location_id).is_not_null())
+        .select(location_hours.field(opening_hours::farm_location_id));
+    let with_farm_hours = farm_locations::table
+        .filter(not(farm_locations::id.nullable().eq_any(own_hours)))
+        .select(farm_locations::farm_id);
+    let weekly = opening_hours::table
+        .filter(opening_hours::farm_location_id.is_not_null().or(opening_hours::farm_id.eq_any(with_farm_hours)))
+        .filter(
+            opening_hours::weekday.eq(weekday)
+                .and(not(opening_hours::farm_id.eq_any(excepted(day))))
+                .and(opening_hours::open.le(time))
+                .and(opening_hours::close.le(opening_hours::open).or(opening_hours::close.gt(time)))
+                .or(opening_hours::weekday.eq((weekday + 6) % 7)
+                    .and(not(opening_hours::farm_id.eq_any(excepted(previous_day))))
+                    .and(opening_hours::close.le(opening_hours::open))
+                    .and(opening_hours::close.gt(time))),
+        )
+        .select(opening_hours::farm_id);
+    let exceptional = opening_hours_exceptions::table
+        .filter(
+            opening_hours_exceptions::start_date.le(day)
+                .and(opening_hours_exceptions::end_date.ge(day))
+                .and(opening_hours_exceptions::open.le(time))
+                .and(opening_hours_exceptions::close.le(opening_hours_exceptions::open)
+                    .or(opening_hours_exceptions::close.gt(time)))
+                .or(opening_hours_exceptions::start_date.le(previous_day)
+                    .and(opening_hours_exceptions::end_date.ge(previous_day))
+                    .and(opening_hours_exceptions::close.le(opening_hours_exceptions::open))
+                    .and(opening_hours_exceptions::close.gt(time))),
+        )
+        .select(opening_hours_exceptions::farm_id);
+    farms::table
+        .filter(farms::id.eq_any(weekly).or(farms::id.eq_any(exceptional)))
+        .select(farms::id)
+        .into_boxed()
+}
+
+/// Sorts the farms and skips those up to the sort key of the previous page.
+fn ordered_farms(query: farms::BoxedQuery<'static, Pg>, order: FarmOrder) -> farms::BoxedQuery<'static, Pg> {
+    match order {
+        FarmOrder::Name(after) => {
+            let query = query.order((farms::name, farms::id));
+            match after {
+                Some((name, id)) => query.filter(
+                    farms::name.gt(name.clone()).or(farms::name.eq(name).and(farms::id.gt(id))),
+                ),
+                None => query,
+            }
+        }
+        FarmOrder::Newest(after) => {
+            let query = query.order(farms::id.desc());
+            match after {
+                Some(id) => query.filter(farms::id.lt(id)),
+                None => query,
+            }
+        }
+    }
+}
+
+/// Shop types that may be assigned to the farm: every active one plus the retired ones the farm
+/// already has.
+pub async fn assignable_shop_types(db: &FarmDB, farm_id: i32) -> DbResult<Vec<ShopType>> {
+    let shop_types = db.run(move |conn| {
+        shop_types::table
+            .select(ShopType::as_select())
+            .filter(shop_types::retired.eq(false).or(shop_types::id.eq_any(farm_shop_types::table
+                .filter(farm_shop_types::farm_id.eq(farm_id))
+                .select(farm_shop_types::shop_type_id))))
+            .load(conn)
+    }).await?;
+    Ok(shop_types)
+}
+
+/// Farms with any location within `radius` kilometres of the given position, up to `limit` of
+/// them. The distance is the one of the nearest location.
+pub async fn get_farms_near(
+    db: &FarmDB,
+    lat: f64,
+    lon: f64,
+    radius: f64,
+    filter: FarmFilter,
+    order: NearbyOrder,
+    limit: i64,
+) -> DbResult<Page<NearbyFarm>> {
+    db.run(move |conn| {
+        let within = || st_dwithin(geolocations::position, geography_point(lat, lon), radius * 1000.0, false);
+        let total = filtered_farms(filter.clone())
+            .filter(farms::id.eq_any(farm_locations::table
+                .inner_join(geolocations::table.on(farm_locations::location_id.eq(geolocations::id)))
+                .filter(within())
+                .select(farm_locations::farm_id)))
+            .count()
+            .get_result(conn)?;
+        let distance = diesel::dsl::min(st_distance(geolocations::position, geography_point(lat, lon), false));
+        let distance_km = distance / 1000.0;
+        let mut query = farms::table
             .inner_join(farm_locations::table)
             .inner_join(geolocations::table.on(farm_locations::location_id.eq(geolocations::id)))
-            .filter(geolocations::lat.between(lat-radius, lat+radius))
-            .filter(geolocations::lon.between(lon-radius, lon+radius))
-            .select(Farm::as_select())
-            .load::<Farm>(conn)?;
-        Ok(f)
+            .filter(within())
+            .filter(farms::id.eq_any(filtered_farms(filter).select(farms::id)))
+            .group_by(farms::id)
+            .select((Farm::as_select(), distance_km))
+            .limit(limit)
+            .into_boxed();
+        query = match order {
+            NearbyOrder::Distance(after) => {
+                let query = query.order((distance_km, farms::id));
+                match after {
+                    Some((km, id)) => query.having(
+                        distance_km.gt(km).or(distance_km.eq(km).and(farms::id.gt(id))),
+                    ),
+                    None => query,
+                }
+            }
+            NearbyOrder::Name(after) => {
+                let query = query.order((farms::name, farms::id));
+                match after {
+                    Some((name, id)) => query.filter(
+                        farms::name.gt(name.clone()).or(farms::name.eq(name).and(farms::id.gt(id))),
+                    ),
+                    None => query,
+                }
+            }
+        };
+        let rows = query
+            .load::<(Farm, Option<f64>)>(conn)?
+            .into_iter()
+            .map(|(farm, distance)| NearbyFarm {
+                farm,
+                distance: distance.unwrap_or_default(),
+            })
+            .collect();
+        Ok(Page { rows, total })
+    }).await
+}
+
+/// Farm locations inside the bounding box, at most `limit` of them. Farms with several locations
+/// inside appear once per location.
+pub async fn get_farms_in_bbox(db: &FarmDB, bbox: BoundingBox, limit: i64) -> DbResult<Vec<LocatedFarm>> {
+    db.run(move |conn| {
+        let query = farms::table
+            .inner_join(farm_locations::table)
+            .inner_join(geolocations::table.on(farm_locations::location_id.eq(geolocations::id)))
+            .select((
+                Farm::as_select(),
+                farm_locations::id,
+                farm_locations::label,
+                geolocations::lat,
+                geolocations::lon,
+            ))
+            .filter(bbox.contains_position())
+            .order((farms::id, farm_locations::id))
+            .limit(limit);
+        let farms = query
+            .load::<(Farm, i32, Option<String>, f64, f64)>(conn)?
+            .into_iter()
+            .map(|(farm, location_id, label, lat, lon)| LocatedFarm { farm, location_id, label, lat, lon })
+            .collect();
+        Ok(farms)
+    }).await
+}
+
+/// Farm locations inside the bounding box grouped into square Web Mercator grid cells with edges
+/// of `cell_size` metres, largest cells first. Positions beyond the latitudes covered by Web
+/// Mercator are left out.
+pub async fn cluster_farms_in_bbox(db: &FarmDB, bbox: BoundingBox, cell_size: f64) -> DbResult<Vec<FarmCluster>> {
+    let bbox = BoundingBox {
+        min_lat: bbox.min_lat.max(-MAX_WEB_MERCATOR_LATITUDE),
+        max_lat: bbox.max_lat.min(MAX_WEB_MERCATOR_LATITUDE),
+        ..bbox
+    };
+    db.run(move |conn| {
+        let cell = st_snaptogrid(st_transform(geometry(geolocations::position), WEB_MERCATOR_SRID), cell_size);
+        let clusters = farm_locations::table
+            .inner_join(geolocations::table)
+            .filter(bbox.contains_position())
+            .group_by(cell)
+            .select((dsl::avg(geolocations::lat), dsl::avg(geolocations::lon), dsl::count_star()))
+            .order((dsl::count_star().desc(), dsl::avg(geolocations::lat), dsl::avg(geolocations::lon)))
+            .load::<(Option<f64>, Option<f64>, i64)>(conn)?
+            .into_iter()
+            .map(|(lat, lon, count)| FarmCluster {
+                lat: lat.unwrap_or_default(),
+                lon: lon.unwrap_or_default(),
+                count,
+            })
+            .collect();
+        Ok(clusters)
     }).await
 }
 
-pub async fn get_farms_owned_by(db: &FarmDB, user: &User) -> DbResult<Vec<Farm>> {
+/// Page of the farms the user is an admin of, with up to `limit` farms.
+pub async fn get_farms_owned_by(db: &FarmDB, user: &User, order: FarmOrder, limit: i64) -> DbResult<Page<Farm>> {
     let user_id = user.id;
     db.run(move |conn| {
-        let f = farms::table
-            .inner_join(farm_admins::table.on(farm_admins::user_id.eq(user_id)))
+        let owned = || farms::table
+            .filter(farms::id.eq_any(farm_admins::table
+                .filter(farm_admins::user_id.eq(user_id))
+                .select(farm_admins::farm_id)))
+            .into_boxed();
+        let total = owned().count().get_result(conn)?;
+        let rows = ordered_farms(owned(), order)
             .select(Farm::as_select())
-            .load::<Farm>(conn)?;
-        Ok(f)
+            .limit(limit)
+            .load(conn)?;
+        Ok(Page { rows, total })
     }).await
 }
 
@@ -129,37 +529,117 @@ pub async fn id_from_ext_id(db: &FarmDB, ext_id: Uuid) -> DbResult<Option<i32>>
 }
 
 pub async fn load_full_farm(db: &FarmDB, farm_id: i32) -> DbResult<Option<FullFarm>> {
+    Ok(load_full_farms(db, vec![farm_id]).await?.pop())
+}
+
+/// Farms with all their details, ordered by id. Loads each kind of detail for all farms at once.
+pub async fn load_full_farms(db: &FarmDB, farm_ids: Vec<i32>) -> DbResult<Vec<FullFarm>> {
     db.run(move |conn| {
-        farms::table
+        let farms: Vec<Farm> = farms::table
+            .filter(farms::id.eq_any(&farm_ids))
             .select(Farm::as_select())
-            .find(farm_id)
-            .first(conn)
-            .optional()?.map(|farm| {
-            let location = FarmLocation::belonging_to(&farm)
-                .inner_join(geolocations::table)
-                .select(Geolocation::as_select())
-                .get_result(conn)?;
-            let shop_types = FarmShopTypes::belonging_to(&farm)
-                .inner_join(shop_types::table)
-                .select(ShopType::as_select())
-                .load(conn)?;
-            let hours: Vec<OpeningHours> = OpeningHours::belonging_to(&farm)
-                .select(OpeningHours::as_select())
-                .load(conn)?;
-
-            Ok(Some(FullFarm {
-                id: farm_id,
+            .order(farms::id)
+            .load(conn)?;
+        let farm_ids: Vec<i32> = farms.iter().map(|farm| farm.id).collect();
+        let mut locations = locations_of_farms(conn, farm_ids.clone())?;
+        let mut shop_types = group_by_farm(farm_shop_types::table
+            .inner_join(shop_types::table)
+            .filter(farm_shop_types::farm_id.eq_any(&farm_ids))
+            .filter(farm_shop_types::farm_location_id.is_null())
+            .select((farm_shop_types::farm_id, ShopType::as_select()))
+            .load::<(i32, ShopType)>(conn)?);
+        let mut hours = group_by_farm(opening_hours::table
+            .filter(opening_hours::farm_id.eq_any(&farm_ids))
+            .filter(opening_hours::farm_location_id.is_null())
+            .select(OpeningHours::as_select())
+            .load::<OpeningHours>(conn)?
+            .into_iter()
+            .map(|hours| (hours.farm_id, hours)));
+        let mut exceptions = group_by_farm(opening_hours_exceptions::table
+            .filter(opening_hours_exceptions::farm_id.eq_any(&farm_ids))
+            .select(OpeningHoursException::as_select())
+            .order(opening_hours_exceptions::start_date)
+            .load::<OpeningHoursException>(conn)?
+            .into_iter()
+            .map(|exception| (exception.farm_id, exception)));
+        let mut contacts: HashMap<i32, Contact> = contact::table
+            .filter(contact::farm_id.eq_any(&farm_ids))
+            .select(Contact::as_select())
+            .load::<Contact>(conn)?
+            .into_iter()
+            .map(|contact| (contact.farm_id, contact))
+            .collect();
+        let mut products = group_by_farm(products_of_farms(conn, farm_ids.clone())?
+            .into_iter()
+            .map(|product| (product.product.farm_id, product)));
+        let mut photos = group_by_farm(photos_of_farms(conn, farm_ids)?
+            .into_iter()
+            .map(|photo| (photo.farm_id, photo)));
+
+        Ok(farms
+            .into_iter()
+            .map(|farm| FullFarm {
+                id: farm.id,
                 name: farm.name,
                 ext_id: farm.ext_id,
-                lat: location.lat,
-                lon: location.lon,
-                shop_types,
-                opening_hours: hours,
-            }))
-        }).unwrap_or(Ok(None))
+                locations: locations.remove(&farm.id).unwrap_or_default(),
+                shop_types: shop_types.remove(&farm.id).unwrap_or_default(),
+                opening_hours: hours.remove(&farm.id).unwrap_or_default(),
+                opening_hours_exceptions: exceptions.remove(&farm.id).unwrap_or_default(),
+                contact: contacts.remove(&farm.id),
+                products: products.remove(&farm.id).unwrap_or_default(),
+                photos: photos.remove(&farm.id).unwrap_or_default(),
+            })
+            .collect())
     }).await
 }
 
+fn group_by_farm<T>(rows: impl IntoIterator<Item = (i32, T)>) -> HashMap<i32, Vec<T>> {
+    let mut groups: HashMap<i32, Vec<T>> = HashMap::new();
+    for (farm_id, row) in rows {
+        groups.entry(farm_id).or_default().push(row);
+    }
+    groups
+}
+
+/// Opening hours of the farms, without those of single locations.
+pub async fn load_opening_hours(db: &FarmDB, farm_ids: Vec<i32>) -> DbResult<Vec<OpeningHours>> {
+    let hours = db.run(move |conn| {
+        opening_hours::table
+            .filter(opening_hours::farm_id.eq_any(farm_ids))
+            .filter(opening_hours::farm_location_id.is_null())
+            .select(OpeningHours::as_select())
+            .load(conn)
+    }).await?;
+    Ok(hours)
+}
+
+/// Names of the shop types of each farm as `(farm_id, name)` pairs, without those of single
+/// locations.
+pub async fn load_shop_type_names(db: &FarmDB, farm_ids: Vec<i32>) -> DbResult<Vec<(i32, String)>> {
+    let names = db.run(move |conn| {
+        farm_shop_types::table
+            .inner_join(shop_types::table)
+            .filter(farm_shop_types::farm_id.eq_any(farm_ids))
+            .filter(farm_shop_types::farm_location_id.is_null())
+            .select((farm_shop_types::farm_id, shop_types::name))
+            .order(shop_types::name)
+            .load(conn)
+    }).await?;
+    Ok(names)
+}
+
+pub async fn load_opening_hours_exceptions(db: &FarmDB, farm_ids: Vec<i32>) -> DbResult<Vec<OpeningHoursException>> {
+    let exceptions = db.run(move |conn| {
+        opening_hours_exceptions::table
+            .filter(opening_hours_exceptions::farm_id.eq_any(farm_ids))
+            .select(OpeningHoursException::as_select())
+            .order(opening_hours_exceptions::start_date)
+            .load(conn)
+    }).await?;
+    Ok(exceptions)
+}
+
 pub async fn create_farm(db: &FarmDB, owner: &User, new_farm: NewFarm) -> DbResult<Farm> {
     let owner_id = owner.id;
     db.run(move |conn| {
@@ -184,6 +664,41 @@ pub async fn create_farm(db: &FarmDB, owner: &User, new_farm: NewFarm) -> DbResu
     }).await
 }
 
+/// Creates all farms administrated by `owner` in a single transaction. Either all farms are created
+/// or none.
+pub async fn create_farms(db: &FarmDB, owner: &User, entries: Vec<NewFarmEntry>) -> DbResult<Vec<Farm>> {
+    let owner_id = owner.id;
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            entries.into_iter()
+                .map(|entry| insert_farm_entry(conn, owner_id, entry))
+                .collect()
+        })
+    }).await
+}
+
+/// Creates a farm administrated by `owner` together with its location and details in a single
+/// transaction.
+pub async fn create_farm_entry(db: &FarmDB, owner: &User, entry: NewFarmEntry) -> DbResult<Farm> {
+    let owner_id = owner.id;
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| insert_farm_entry(conn, owner_id, entry))
+    }).await
+}
+
+fn insert_farm_entry(conn: &mut PgConnection, owner_id: i32, entry: NewFarmEntry) -> Result<Farm, DatabaseError> {
+    let farm: Farm = diesel::insert_into(farms::table)
+        .values(entry.farm)
+        .returning(Farm::as_returning())
+        .get_result(conn)?;
+    diesel::insert_into(farm_admins::table)
+        .values(NewFarmAdmin { user_id: owner_id, farm_id: farm.id })
+        .execute(conn)?;
+    insert_farm_location(conn, entry.location, farm.id, None)?;
+    apply_farm_update(conn, farm.id, entry.details.for_farm(farm.id))?;
+    Ok(farm)
+}
+
 pub async fn delete_farm(db: &FarmDB, user_id: i32, farm_id: i32) -> DbResult<bool> {
     db.run(move |conn| {
         let owner = farm_admins::table.select(FarmAdmin::as_select())
@@ -203,4 +718,136 @@ pub async fn delete_farm(db: &FarmDB, user_id: i32, farm_id: i32) -> DbResult<bo
             Ok(false)
         }
     }).await
+}
+
+/// Deletes the farm without checking who administrates it. Only meant for sysadmins. Returns
+/// `false` if there is no such farm.
+pub async fn delete_any_farm(db: &FarmDB, farm_id: i32) -> DbResult<bool> {
+    db.run(move |conn| {
+        let deleted = diesel::delete(farms::table)
+            .filter(farms::id.eq(farm_id))
+            .execute(conn)?;
+        Ok(deleted > 0)
+    }).await
+}
+
+pub async fn update_farm(db: &FarmDB, user_id: i32, farm_id: i32, update: FarmUpdate) -> DbResult<bool> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            if !farm_admin_exists(conn, user_id, farm_id)? {
+                return Ok(false);
+            }
+            apply_farm_update(conn, farm_id, update)?;
+            Ok(true)
+        })
+    }).await
+}
+
+/// Applies the update without checking who administrates the farm. Only meant for sysadmins.
+pub async fn update_any_farm(db: &FarmDB, farm_id: i32, update: FarmUpdate) -> DbResult<()> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| apply_farm_update(conn, farm_id, update))
+    }).await
+}
+
+pub(crate) fn apply_farm_update(conn: &mut PgConnection, farm_id: i32, update: FarmUpdate) -> Result<(), DatabaseError> {
+    if let Some(name) = update.name {
+        diesel::update(farms::table)
+            .filter(farms::id.eq(farm_id))
+            .set(farms::name.eq(name))
+            .execute(conn)?;
+    }
+    if let Some(location) = update.location {
+        let location_ids = farm_locations::table
+            .filter(farm_locations::farm_id.eq(farm_id))
+            .select(farm_locations::location_id)
+            .order(farm_locations::id)
+            .limit(1);
+        diesel::update(geolocations::table)
+            .filter(geolocations::id.eq_any(location_ids))
+            .set((geolocations::lat.eq(location.lat), geolocations::lon.eq(location.lon)))
+            .execute(conn)?;
+    }
+    if let Some(shop_type_ids) = update.shop_types {
+        diesel::delete(farm_shop_types::table)
+            .filter(farm_shop_types::farm_id.eq(farm_id))
+            .filter(farm_shop_types::farm_location_id.is_null())
+            .execute(conn)?;
+        let new_shop_types: Vec<NewFarmShopType> = shop_type_ids.into_iter()
+            .map(|shop_type_id| NewFarmShopType { farm_id, shop_type_id })
+            .collect();
+        diesel::insert_into(farm_shop_types::table)
+            .values(&new_shop_types)
+            .execute(conn)?;
+    }
+    if let Some(hours) = update.opening_hours {
+        diesel::delete(opening_hours::table)
+            .filter(opening_hours::farm_id.eq(farm_id))
+            .filter(opening_hours::farm_location_id.is_null())
+            .execute(conn)?;
+        diesel::insert_into(opening_hours::table)
+            .values(&hours)
+            .execute(conn)?;
+    }
+    if let Some(exceptions) = update.opening_hours_exceptions {
+        diesel::delete(opening_hours_exceptions::table)
+            .filter(opening_hours_exceptions::farm_id.eq(farm_id))
+            .execute(conn)?;
+        diesel::insert_into(opening_hours_exceptions::table)
+            .values(&exceptions)
+            .execute(conn)?;
+    }
+    if let Some(new_contact) = update.contact {
+        diesel::insert_into(contact::table)
+            .values(&new_contact)
+            .on_conflict(contact::farm_id)
+            .do_update()
+            .set(&new_contact)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+pub async fn is_farm_admin(db: &FarmDB, user_id: i32, farm_id: i32) -> DbResult<bool> {
+    db.run(move |conn| Ok(farm_admin_exists(conn, user_id, farm_id)?)).await
+}
+
+/// Adds an exception to the opening hours of a farm. Returns `None` if the user is no admin of the farm.
+pub async fn add_opening_hours_exception(
+    db: &FarmDB,
+    user_id: i32,
+    exception: NewOpeningHoursException,
+) -> DbResult<Option<OpeningHoursException>> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, exception.farm_id)? {
+            return Ok(None);
+        }
+        let exception = diesel::insert_into(opening_hours_exceptions::table)
+            .values(exception)
+            .returning(OpeningHoursException::as_returning())
+            .get_result(conn)?;
+        Ok(Some(exception))
+    }).await
+}
+
+pub async fn delete_opening_hours_exception(db: &FarmDB, user_id: i32, farm_id: i32, exception_id: i32) -> DbResult<bool> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, farm_id)? {
+            return Ok(false);
+        }
+        let deleted = diesel::delete(opening_hours_exceptions::table)
+            .filter(opening_hours_exceptions::id.eq(exception_id))
+            .filter(opening_hours_exceptions::farm_id.eq(farm_id))
+            .execute(conn)?;
+        Ok(deleted > 0)
+    }).await
+}
+
+pub(crate) fn farm_admin_exists(conn: &mut PgConnection, user_id: i32, farm_id: i32) -> QueryResult<bool> {
+    let admin = farm_admins::table.select(FarmAdmin::as_select())
+        .filter(farm_admins::user_id.eq(user_id))
+        .filter(farm_admins::farm_id.eq(farm_id))
+        .first::<FarmAdmin>(conn)
+        .optional()?;
+    Ok(admin.is_some())
 }
\ No newline at end of file
@@ -0,0 +1,159 @@
+//! Requests of users to become farm owners, which sysadmins approve or reject. While a request waits
+//! for a decision, the user has the farm owner status `REQUESTED`, afterwards the status of the
+//! decision.
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use crate::{DatabaseError, DbResult, FarmDB};
+use crate::page::Page;
+use crate::schema::{farm_owner_requests, users};
+use crate::user::{set_farmowner_status, FarmOwnerStatus, User};
+
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(User))]
+pub struct FarmOwnerRequest {
+    pub id: i32,
+    pub user_id: i32,
+    pub motivation: String,
+    pub evidence: Option<String>,
+    /// `REQUESTED` until decided, then `YES` or `REJECTED`.
+    pub status: FarmOwnerStatus,
+    pub created: DateTime<Utc>,
+    pub decided: Option<DateTime<Utc>>,
+    /// Sysadmin who decided, `None` if undecided or the sysadmin was deleted.
+    pub decided_by: Option<i32>,
+    /// Reason given with the decision.
+    pub reason: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farm_owner_requests)]
+struct NewFarmOwnerRequest {
+    user_id: i32,
+    motivation: String,
+    evidence: Option<String>,
+}
+
+/// Queues a request and marks the user as `REQUESTED`. Returns `None` if the user already is a farm
+/// owner or has a request waiting for a decision.
+pub async fn submit(
+    db: &FarmDB,
+    user_id: i32,
+    motivation: String,
+    evidence: Option<String>,
+) -> DbResult<Option<FarmOwnerRequest>> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            let status: FarmOwnerStatus = users::table
+                .find(user_id)
+                .select(users::farmowner)
+                .for_update()
+                .first(conn)?;
+            // sysadmins may have changed the status of a user with a pending request
+            let pending = diesel::select(diesel::dsl::exists(
+                farm_owner_requests::table
+                    .filter(farm_owner_requests::user_id.eq(user_id))
+                    .filter(farm_owner_requests::status.eq(FarmOwnerStatus::REQUESTED)),
+            )).get_result::<bool>(conn)?;
+            if pending || matches!(status, FarmOwnerStatus::YES | FarmOwnerStatus::REQUESTED) {
+                return Ok(None);
+            }
+            let request = diesel::insert_into(farm_owner_requests::table)
+                .values(NewFarmOwnerRequest { user_id, motivation, evidence })
+                .returning(FarmOwnerRequest::as_returning())
+                .get_result(conn)?;
+            set_farmowner_status(conn, user_id, FarmOwnerStatus::REQUESTED)?;
+            Ok(Some(request))
+        })
+    }).await
+}
+
+/// Most recent request of the user, decided or not.
+pub async fn latest_of_user(db: &FarmDB, user_id: i32) -> DbResult<Option<FarmOwnerRequest>> {
+    let request = db.run(move |conn| {
+        farm_owner_requests::table
+            .filter(farm_owner_requests::user_id.eq(user_id))
+            .select(FarmOwnerRequest::as_select())
+            .order(farm_owner_requests::id.desc())
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(request)
+}
+
+/// Requests with the given status together with the requesting user, oldest first and starting
+/// after the request with id `after`.
+pub async fn list(
+    db: &FarmDB,
+    status: FarmOwnerStatus,
+    after: Option<i32>,
+    limit: i64,
+) -> DbResult<Page<(FarmOwnerRequest, User)>> {
+    db.run(move |conn| {
+        let total = farm_owner_requests::table
+            .filter(farm_owner_requests::status.eq(status.clone()))
+            .count()
+            .get_result(conn)?;
+        let rows = farm_owner_requests::table
+            .inner_join(users::table)
+            .filter(farm_owner_requests::status.eq(status))
+            .filter(farm_owner_requests::id.gt(after.unwrap_or(0)))
+            .select((FarmOwnerRequest::as_select(), User::as_select()))
+            .order(farm_owner_requests::id)
+            .limit(limit)
+            .load(conn)?;
+        Ok(Page { rows, total })
+    }).await
+}
+
+/// Approves or rejects a request waiting for a decision and gives its user the status of the
+/// decision. Returns `None` if there is no such request.
+pub async fn decide(
+    db: &FarmDB,
+    request_id: i32,
+    sysadmin_id: i32,
+    approve: bool,
+    reason: Option<String>,
+) -> DbResult<Option<FarmOwnerRequest>> {
+    let status = if approve { FarmOwnerStatus::YES } else { FarmOwnerStatus::REJECTED };
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            let request = diesel::update(farm_owner_requests::table)
+                .filter(farm_owner_requests::id.eq(request_id))
+                .filter(farm_owner_requests::status.eq(FarmOwnerStatus::REQUESTED))
+                .set((
+                    farm_owner_requests::status.eq(status.clone()),
+                    farm_owner_requests::decided.eq(Utc::now()),
+                    farm_owner_requests::decided_by.eq(sysadmin_id),
+                    farm_owner_requests::reason.eq(reason),
+                ))
+                .returning(FarmOwnerRequest::as_returning())
+                .get_result(conn)
+                .optional()?;
+            if let Some(request) = &request {
+                set_farmowner_status(conn, request.user_id, status)?;
+            }
+            Ok(request)
+        })
+    }).await
+}
+
+/// Approves or rejects the request of the user waiting for a decision, if there is one, without
+/// changing the status of the user.
+pub(crate) fn decide_pending(
+    conn: &mut PgConnection,
+    user_id: i32,
+    sysadmin_id: i32,
+    approve: bool,
+) -> QueryResult<usize> {
+    let status = if approve { FarmOwnerStatus::YES } else { FarmOwnerStatus::REJECTED };
+    diesel::update(farm_owner_requests::table)
+        .filter(farm_owner_requests::user_id.eq(user_id))
+        .filter(farm_owner_requests::status.eq(FarmOwnerStatus::REQUESTED))
+        .set((
+            farm_owner_requests::status.eq(status),
+            farm_owner_requests::decided.eq(Utc::now()),
+            farm_owner_requests::decided_by.eq(sysadmin_id),
+        ))
+        .execute(conn)
+}
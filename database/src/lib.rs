@@ -7,6 +7,17 @@ pub mod schema;
 pub mod user;
 pub mod location;
 pub mod farm;
+pub mod shop_type;
+pub mod osm;
+pub mod osm_farm;
+pub mod product;
+pub mod api_key;
+pub mod photo;
+pub mod search;
+pub mod page;
+pub mod farm_owner_request;
+pub mod stats;
+pub mod contact;
 
 #[derive(Debug)]
 pub struct DatabaseError(pub String);
@@ -1,20 +1,132 @@
+use diesel::pg::Pg;
 use diesel::prelude::*;
-use crate::{DbResult, FarmDB};
-use crate::schema::{geolocations, farm_locations};
+use diesel::sql_types::{Bool, Double, Integer, Nullable};
+use crate::{DatabaseError, DbResult, FarmDB};
+use crate::farm::{NewOpeningHours, OpeningHours, ShopType, farm_admin_exists};
+use crate::schema::{geolocations, farm_locations, farm_shop_types, farms, opening_hours, shop_types};
+use std::collections::HashMap;
+use crate::schema::sql_types::Geography;
+
+/// Spatial reference id of WGS 84, the coordinate system of all stored positions.
+pub const WGS84_SRID: i32 = 4326;
+
+/// Spatial reference id of Web Mercator, the projection of web maps, in metres.
+pub const WEB_MERCATOR_SRID: i32 = 3857;
+
+/// Latitude up to which Web Mercator is defined.
+pub const MAX_WEB_MERCATOR_LATITUDE: f64 = 85.05112878;
+
+/// PostGIS `geometry`. Only used as intermediate value of geography points and grids.
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "geometry"))]
+pub struct Geometry;
+
+define_sql_function! {
+    #[sql_name = "ST_MakePoint"]
+    fn st_makepoint(x: Double, y: Double) -> Geometry;
+}
+
+define_sql_function! {
+    #[sql_name = "ST_SetSRID"]
+    fn st_setsrid(geometry: Geometry, srid: Integer) -> Geometry;
+}
+
+define_sql_function! {
+    fn geography(geometry: Geometry) -> Geography;
+}
+
+define_sql_function! {
+    /// Whether both positions are at most `distance` metres apart. Uses the spatial index on
+    /// `geolocations.position`.
+    #[sql_name = "ST_DWithin"]
+    fn st_dwithin(a: Nullable<Geography>, b: Geography, distance: Double, use_spheroid: Bool) -> Nullable<Bool>;
+}
+
+define_sql_function! {
+    /// Distance in metres.
+    #[sql_name = "ST_Distance"]
+    fn st_distance(a: Nullable<Geography>, b: Geography, use_spheroid: Bool) -> Nullable<Double>;
+}
+
+define_sql_function! {
+    #[sql_name = "ST_MakeEnvelope"]
+    fn st_makeenvelope(xmin: Double, ymin: Double, xmax: Double, ymax: Double, srid: Integer) -> Geometry;
+}
+
+define_sql_function! {
+    /// Planar geometry of a geography. Indexed for `geolocations.position` to allow bounding box
+    /// queries in plain longitude and latitude.
+    fn geometry(geography: Nullable<Geography>) -> Nullable<Geometry>;
+}
+
+define_sql_function! {
+    #[sql_name = "ST_Intersects"]
+    fn st_intersects(a: Nullable<Geometry>, b: Geometry) -> Nullable<Bool>;
+}
+
+define_sql_function! {
+    #[sql_name = "ST_Transform"]
+    fn st_transform(geometry: Nullable<Geometry>, srid: Integer) -> Nullable<Geometry>;
+}
+
+define_sql_function! {
+    /// Rounds the coordinates to multiples of `size`.
+    #[sql_name = "ST_SnapToGrid"]
+    fn st_snaptogrid(geometry: Nullable<Geometry>, size: Double) -> Nullable<Geometry>;
+}
+
+/// Rectangle in WGS 84 degrees. A `min_lon` greater than `max_lon` describes a rectangle crossing
+/// the antimeridian.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingBox {
+    pub min_lat: f64,
+    pub min_lon: f64,
+    pub max_lat: f64,
+    pub max_lon: f64,
+}
+
+impl BoundingBox {
+    pub fn crosses_antimeridian(&self) -> bool {
+        self.min_lon > self.max_lon
+    }
+
+    /// Whether `geolocations.position` lies inside the rectangle. Uses the index on the geometry of
+    /// the position.
+    pub(crate) fn contains_position<QS>(self) -> Box<dyn BoxableExpression<QS, Pg, SqlType = Nullable<Bool>>>
+    where
+        QS: 'static,
+        geolocations::position: SelectableExpression<QS>,
+    {
+        let position = geometry(geolocations::position);
+        if self.crosses_antimeridian() {
+            let east = st_makeenvelope(self.min_lon, self.min_lat, 180.0, self.max_lat, WGS84_SRID);
+            let west = st_makeenvelope(-180.0, self.min_lat, self.max_lon, self.max_lat, WGS84_SRID);
+            Box::new(st_intersects(position, east).or(st_intersects(position, west)))
+        } else {
+            let envelope = st_makeenvelope(self.min_lon, self.min_lat, self.max_lon, self.max_lat, WGS84_SRID);
+            Box::new(st_intersects(position, envelope))
+        }
+    }
+}
+
+/// Geography point of a WGS 84 position, comparable to `geolocations.position`.
+pub fn geography_point(lat: f64, lon: f64) -> geography<st_setsrid<st_makepoint<f64, f64>, i32>> {
+    geography(st_setsrid(st_makepoint(lon, lat), WGS84_SRID))
+}
 
 #[derive(Selectable)]
 #[diesel(table_name = geolocations)]
 pub struct GeoLocation {
     pub id: i32,
-    pub lat: f32,
-    pub lon: f32,
+    pub lat: f64,
+    pub lon: f64,
 }
 
 #[derive(Insertable)]
 #[diesel(table_name = geolocations)]
 pub struct NewGeoLocation {
-    pub lat: f32,
-    pub lon: f32,
+    pub lat: f64,
+    pub lon: f64,
 }
 
 #[derive(Selectable)]
@@ -29,17 +141,304 @@ pub struct FarmLocation {
 struct NewFarmLocation {
     farm_id: i32,
     location_id: i32,
+    label: Option<String>,
+}
+
+/// Location of a farm with its own shop types and opening hours. A location without own shop types
+/// or opening hours has those of the farm. Opening hours exceptions always are those of the farm.
+pub struct FullLocation {
+    /// Id of the `farm_locations` row.
+    pub id: i32,
+    pub label: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    pub shop_types: Vec<ShopType>,
+    pub opening_hours: Vec<OpeningHours>,
+}
+
+/// Partial change of a farm location. Every field that is `None` stays untouched.
+#[derive(Default)]
+pub struct LocationUpdate {
+    pub label: Option<Option<String>>,
+    pub position: Option<NewGeoLocation>,
+    pub shop_types: Option<Vec<i32>>,
+    pub opening_hours: Option<Vec<NewOpeningHours>>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farm_shop_types)]
+struct NewLocationShopType {
+    farm_id: i32,
+    farm_location_id: i32,
+    shop_type_id: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = opening_hours)]
+struct NewLocationOpeningHours {
+    farm_id: i32,
+    farm_location_id: i32,
+    weekday: i32,
+    open: chrono::NaiveTime,
+    close: chrono::NaiveTime,
 }
 
 pub async fn add_new_location_to_farm(db: &FarmDB, location: NewGeoLocation, farm_id: i32) -> DbResult<()> {
     db.run(move |conn| {
-        let new_id: i32 = diesel::insert_into(geolocations::table)
-            .values(location)
-            .returning(geolocations::id)
-            .get_result(conn)?;
-        diesel::insert_into(farm_locations::table)
-            .values(&NewFarmLocation { farm_id, location_id: new_id })
-            .execute(conn)?;
+        insert_farm_location(conn, location, farm_id, None)?;
         Ok(())
     }).await
-}
\ No newline at end of file
+}
+
+/// Inserts the position and links it to the farm. Returns the id of the farm location.
+pub(crate) fn insert_farm_location(
+    conn: &mut PgConnection,
+    location: NewGeoLocation,
+    farm_id: i32,
+    label: Option<String>,
+) -> QueryResult<i32> {
+    let new_id: i32 = diesel::insert_into(geolocations::table)
+        .values(location)
+        .returning(geolocations::id)
+        .get_result(conn)?;
+    diesel::insert_into(farm_locations::table)
+        .values(&NewFarmLocation { farm_id, location_id: new_id, label })
+        .returning(farm_locations::id)
+        .get_result(conn)
+}
+
+/// All locations of the farm, the main location first.
+pub(crate) fn locations_of_farm(conn: &mut PgConnection, farm_id: i32) -> QueryResult<Vec<FullLocation>> {
+    Ok(locations_of_farms(conn, vec![farm_id])?.remove(&farm_id).unwrap_or_default())
+}
+
+/// All locations of each farm by farm id, the main location first.
+pub(crate) fn locations_of_farms(
+    conn: &mut PgConnection,
+    farm_ids: Vec<i32>,
+) -> QueryResult<HashMap<i32, Vec<FullLocation>>> {
+    let locations: Vec<(i32, i32, Option<String>, f64, f64)> = farm_locations::table
+        .inner_join(geolocations::table)
+        .filter(farm_locations::farm_id.eq_any(farm_ids))
+        .select((
+            farm_locations::farm_id,
+            farm_locations::id,
+            farm_locations::label,
+            geolocations::lat,
+            geolocations::lon,
+        ))
+        .order(farm_locations::id)
+        .load(conn)?;
+    let location_ids: Vec<i32> = locations.iter().map(|(_, id, ..)| *id).collect();
+    let shop_types = farm_shop_types::table
+        .inner_join(shop_types::table)
+        .filter(farm_shop_types::farm_location_id.eq_any(&location_ids))
+        .select((farm_shop_types::farm_location_id.assume_not_null(), ShopType::as_select()))
+        .order(shop_types::name)
+        .load::<(i32, ShopType)>(conn)?;
+    let mut shop_types = group_by_location(shop_types);
+    let hours = opening_hours::table
+        .filter(opening_hours::farm_location_id.eq_any(&location_ids))
+        .select((opening_hours::farm_location_id.assume_not_null(), OpeningHours::as_select()))
+        .order((opening_hours::weekday, opening_hours::open))
+        .load::<(i32, OpeningHours)>(conn)?;
+    let mut hours = group_by_location(hours);
+    let mut farms: HashMap<i32, Vec<FullLocation>> = HashMap::new();
+    for (farm_id, id, label, lat, lon) in locations {
+        farms.entry(farm_id).or_default().push(FullLocation {
+            id,
+            label,
+            lat,
+            lon,
+            shop_types: shop_types.remove(&id).unwrap_or_default(),
+            opening_hours: hours.remove(&id).unwrap_or_default(),
+        });
+    }
+    Ok(farms)
+}
+
+fn group_by_location<T>(rows: Vec<(i32, T)>) -> HashMap<i32, Vec<T>> {
+    let mut groups: HashMap<i32, Vec<T>> = HashMap::new();
+    for (location_id, row) in rows {
+        groups.entry(location_id).or_default().push(row);
+    }
+    groups
+}
+
+pub async fn get_locations(db: &FarmDB, farm_id: i32) -> DbResult<Vec<FullLocation>> {
+    Ok(db.run(move |conn| locations_of_farm(conn, farm_id)).await?)
+}
+
+/// Adds a location to the farm. Returns `None` if the user is no admin of the farm.
+pub async fn add_location(
+    db: &FarmDB,
+    user_id: i32,
+    farm_id: i32,
+    position: NewGeoLocation,
+    update: LocationUpdate,
+) -> DbResult<Option<i32>> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            if !farm_admin_exists(conn, user_id, farm_id)? {
+                return Ok(None);
+            }
+            let location_id = insert_farm_location(conn, position, farm_id, None)?;
+            apply_location_update(conn, farm_id, location_id, update)?;
+            Ok(Some(location_id))
+        })
+    }).await
+}
+
+/// Returns `false` if the user is no admin of the farm or the location belongs to another farm.
+pub async fn update_location(
+    db: &FarmDB,
+    user_id: i32,
+    farm_id: i32,
+    location_id: i32,
+    update: LocationUpdate,
+) -> DbResult<bool> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            if !farm_admin_exists(conn, user_id, farm_id)? || !location_of_farm(conn, farm_id, location_id)? {
+                return Ok(false);
+            }
+            apply_location_update(conn, farm_id, location_id, update)?;
+            Ok(true)
+        })
+    }).await
+}
+
+/// Outcome of [delete_location].
+#[derive(Debug, PartialEq)]
+pub enum LocationDeletion {
+    Deleted,
+    /// The user is no admin of the farm.
+    NotAdmin,
+    /// The farm has no such location.
+    NotFound,
+    /// The location is the last of the farm, which can't be deleted.
+    LastLocation,
+}
+
+/// Deletes the location with its shop types and opening hours, unless it is the last location of
+/// the farm.
+pub async fn delete_location(
+    db: &FarmDB,
+    user_id: i32,
+    farm_id: i32,
+    location_id: i32,
+) -> DbResult<LocationDeletion> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            if !farm_admin_exists(conn, user_id, farm_id)? {
+                return Ok(LocationDeletion::NotAdmin);
+            }
+            // locks the farm so concurrent deletions can't remove all of its locations
+            farms::table
+                .find(farm_id)
+                .select(farms::id)
+                .for_update()
+                .first::<i32>(conn)?;
+            if !location_of_farm(conn, farm_id, location_id)? {
+                return Ok(LocationDeletion::NotFound);
+            }
+            let count: i64 = farm_locations::table
+                .filter(farm_locations::farm_id.eq(farm_id))
+                .count()
+                .get_result(conn)?;
+            if count <= 1 {
+                return Ok(LocationDeletion::LastLocation);
+            }
+            let position_id = farm_locations::table
+                .find(location_id)
+                .select(farm_locations::location_id);
+            // removes the farm location, its shop types and its opening hours as well
+            diesel::delete(geolocations::table.filter(geolocations::id.eq_any(position_id)))
+                .execute(conn)?;
+            Ok(LocationDeletion::Deleted)
+        })
+    }).await
+}
+
+fn location_of_farm(conn: &mut PgConnection, farm_id: i32, location_id: i32) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        farm_locations::table
+            .filter(farm_locations::id.eq(location_id))
+            .filter(farm_locations::farm_id.eq(farm_id)),
+    ))
+    .get_result(conn)
+}
+
+fn apply_location_update(
+    conn: &mut PgConnection,
+    farm_id: i32,
+    location_id: i32,
+    update: LocationUpdate,
+) -> Result<(), DatabaseError> {
+    if let Some(label) = update.label {
+        diesel::update(farm_locations::table.find(location_id))
+            .set(farm_locations::label.eq(label))
+            .execute(conn)?;
+    }
+    if let Some(position) = update.position {
+        let position_id = farm_locations::table
+            .find(location_id)
+            .select(farm_locations::location_id);
+        diesel::update(geolocations::table.filter(geolocations::id.eq_any(position_id)))
+            .set((geolocations::lat.eq(position.lat), geolocations::lon.eq(position.lon)))
+            .execute(conn)?;
+    }
+    if let Some(shop_type_ids) = update.shop_types {
+        diesel::delete(farm_shop_types::table.filter(farm_shop_types::farm_location_id.eq(location_id)))
+            .execute(conn)?;
+        let new_shop_types: Vec<NewLocationShopType> = shop_type_ids
+            .into_iter()
+            .map(|shop_type_id| NewLocationShopType { farm_id, farm_location_id: location_id, shop_type_id })
+            .collect();
+        diesel::insert_into(farm_shop_types::table)
+            .values(&new_shop_types)
+            .execute(conn)?;
+    }
+    if let Some(hours) = update.opening_hours {
+        diesel::delete(opening_hours::table.filter(opening_hours::farm_location_id.eq(location_id)))
+            .execute(conn)?;
+        let new_hours: Vec<NewLocationOpeningHours> = hours
+            .into_iter()
+            .map(|h| NewLocationOpeningHours {
+                farm_id,
+                farm_location_id: location_id,
+                weekday: h.weekday,
+                open: h.open,
+                close: h.close,
+            })
+            .collect();
+        diesel::insert_into(opening_hours::table)
+            .values(&new_hours)
+            .execute(conn)?;
+    }
+    Ok(())
+}
+
+/// Names of the shop types of each location as `(location_id, name)` pairs.
+pub async fn load_location_shop_type_names(db: &FarmDB, location_ids: Vec<i32>) -> DbResult<Vec<(i32, String)>> {
+    let names = db.run(move |conn| {
+        farm_shop_types::table
+            .inner_join(shop_types::table)
+            .filter(farm_shop_types::farm_location_id.eq_any(location_ids))
+            .select((farm_shop_types::farm_location_id.assume_not_null(), shop_types::name))
+            .order(shop_types::name)
+            .load(conn)
+    }).await?;
+    Ok(names)
+}
+
+/// Opening hours of each location as `(location_id, hours)` pairs.
+pub async fn load_location_opening_hours(db: &FarmDB, location_ids: Vec<i32>) -> DbResult<Vec<(i32, OpeningHours)>> {
+    let hours = db.run(move |conn| {
+        opening_hours::table
+            .filter(opening_hours::farm_location_id.eq_any(location_ids))
+            .select((opening_hours::farm_location_id.assume_not_null(), OpeningHours::as_select()))
+            .load(conn)
+    }).await?;
+    Ok(hours)
+}
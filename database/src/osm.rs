@@ -0,0 +1,587 @@
+//! Conversion between the OpenStreetMap `opening_hours` syntax and opening hours rows.
+//!
+//! Only a subset of the [specification](https://wiki.openstreetmap.org/wiki/Key:opening_hours/specification)
+//! is supported:
+//!
+//! * `24/7`
+//! * weekday selectors like `Mo`, `Mo-Fr` or `Mo-We,Sa` followed by time ranges like
+//!   `08:00-12:00,13:30-18:00` or by `off`. Ranges past midnight are written as `20:00-02:00`,
+//!   midnight at the end of a day as `24:00`.
+//! * date selectors like `2025 Dec 24`, `Dec 24-26` or `2025 Dec 30-2026 Jan 02` followed by time
+//!   ranges or `off`. Dates without a year are resolved against a given reference year.
+//!
+//! Rules are separated by `;` and later weekday rules replace earlier ones for the days they
+//! select. Rules for public and school holidays (`PH`, `SH`) cannot be mapped to dates and are
+//! reported as ignored.
+
+use crate::farm::{NewOpeningHours, NewOpeningHoursException, OpeningHours, OpeningHoursException};
+use chrono::{Datelike, NaiveDate, NaiveTime, Timelike};
+use std::fmt::{Display, Formatter};
+
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+pub type TimeRange = (NaiveTime, NaiveTime);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WeeklyRange {
+    pub weekday: i32,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+/// Hours for every day from `start_date` to `end_date`. Closed if `hours` is empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DateException {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub hours: Vec<TimeRange>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct OsmSchedule {
+    pub weekly: Vec<WeeklyRange>,
+    pub exceptions: Vec<DateException>,
+    /// Rules that are valid but cannot be represented, e.g. `PH off`.
+    pub ignored_rules: Vec<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct OsmParseError {
+    pub rule: String,
+    pub message: String,
+}
+
+impl Display for OsmParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} in rule `{}`", self.message, self.rule)
+    }
+}
+
+impl OsmSchedule {
+    pub fn from_rows(hours: &[OpeningHours], exceptions: &[OpeningHoursException]) -> Self {
+        let weekly = hours
+            .iter()
+            .map(|h| WeeklyRange {
+                weekday: h.weekday,
+                open: h.open,
+                close: h.close,
+            })
+            .collect();
+        let mut date_exceptions: Vec<DateException> = Vec::new();
+        for exception in exceptions {
+            let existing = date_exceptions
+                .iter_mut()
+                .find(|e| e.start_date == exception.start_date && e.end_date == exception.end_date);
+            let date_exception = match existing {
+                Some(existing) => existing,
+                None => {
+                    date_exceptions.push(DateException {
+                        start_date: exception.start_date,
+                        end_date: exception.end_date,
+                        hours: Vec::new(),
+                    });
+                    date_exceptions.last_mut().unwrap()
+                }
+            };
+            if let Some(range) = exception.open.zip(exception.close) {
+                date_exception.hours.push(range);
+            }
+        }
+        let mut schedule = Self {
+            weekly,
+            exceptions: date_exceptions,
+            ignored_rules: Vec::new(),
+        };
+        schedule.normalize();
+        schedule
+    }
+
+    pub fn opening_hours(&self, farm_id: i32) -> Vec<NewOpeningHours> {
+        self.weekly
+            .iter()
+            .map(|r| NewOpeningHours {
+                farm_id,
+                weekday: r.weekday,
+                open: r.open,
+                close: r.close,
+            })
+            .collect()
+    }
+
+    pub fn opening_hours_exceptions(&self, farm_id: i32) -> Vec<NewOpeningHoursException> {
+        self.exceptions
+            .iter()
+            .flat_map(|e| {
+                let new_exception = move |range: Option<TimeRange>| NewOpeningHoursException {
+                    farm_id,
+                    start_date: e.start_date,
+                    end_date: e.end_date,
+                    open: range.map(|(open, _)| open),
+                    close: range.map(|(_, close)| close),
+                    description: None,
+                };
+                if e.hours.is_empty() {
+                    vec![new_exception(None)]
+                } else {
+                    e.hours.iter().map(|r| new_exception(Some(*r))).collect()
+                }
+            })
+            .collect()
+    }
+
+    fn normalize(&mut self) {
+        self.weekly.sort_by_key(|r| (r.weekday, r.open, r.close));
+        for exception in self.exceptions.iter_mut() {
+            exception.hours.sort();
+        }
+        self.exceptions.sort_by_key(|e| (e.start_date, e.end_date));
+    }
+
+    fn day_hours(&self, weekday: i32) -> Vec<TimeRange> {
+        self.weekly
+            .iter()
+            .filter(|r| r.weekday == weekday)
+            .map(|r| (r.open, r.close))
+            .collect()
+    }
+}
+
+/// Parses an OSM `opening_hours` value. Dates without a year are resolved against `year`.
+pub fn parse(input: &str, year: i32) -> Result<OsmSchedule, OsmParseError> {
+    let mut days: [Vec<TimeRange>; 7] = Default::default();
+    let mut schedule = OsmSchedule::default();
+    for rule in input.split(';').map(str::trim).filter(|r| !r.is_empty()) {
+        let error = |message: &str| OsmParseError {
+            rule: rule.to_string(),
+            message: message.to_string(),
+        };
+        if rule == "24/7" {
+            days = std::array::from_fn(|_| vec![(NaiveTime::MIN, NaiveTime::MIN)]);
+            continue;
+        }
+        if rule.starts_with("PH") || rule.starts_with("SH") {
+            schedule.ignored_rules.push(rule.to_string());
+            continue;
+        }
+        let mut cursor = Cursor::new(rule);
+        if cursor.starts_with_date() {
+            let (start_date, end_date) = cursor.date_range(year).map_err(|m| error(&m))?;
+            let hours = cursor.hours().map_err(|m| error(&m))?;
+            schedule.exceptions.push(DateException {
+                start_date,
+                end_date,
+                hours,
+            });
+        } else {
+            let weekdays = if cursor.starts_with_weekday() {
+                cursor.weekdays().map_err(|m| error(&m))?
+            } else {
+                (0..7).collect()
+            };
+            let hours = cursor.hours().map_err(|m| error(&m))?;
+            for weekday in weekdays {
+                days[weekday as usize] = hours.clone();
+            }
+        }
+    }
+    schedule.weekly = days
+        .into_iter()
+        .enumerate()
+        .flat_map(|(weekday, hours)| {
+            hours.into_iter().map(move |(open, close)| WeeklyRange {
+                weekday: weekday as i32,
+                open,
+                close,
+            })
+        })
+        .collect();
+    schedule.normalize();
+    Ok(schedule)
+}
+
+/// Formats a schedule as OSM `opening_hours` value. Weekdays with equal hours are combined into
+/// one rule, exceptions are appended as date rules with explicit years.
+pub fn to_osm(schedule: &OsmSchedule) -> String {
+    let mut schedule = schedule.clone();
+    schedule.normalize();
+    let days: Vec<Vec<TimeRange>> = (0..7).map(|d| schedule.day_hours(d)).collect();
+    let mut rules = Vec::new();
+    if days.iter().all(|h| h == &vec![(NaiveTime::MIN, NaiveTime::MIN)]) {
+        rules.push("24/7".to_string());
+    } else {
+        let mut handled = [false; 7];
+        for weekday in 0..7 {
+            if handled[weekday] || days[weekday].is_empty() {
+                continue;
+            }
+            let same: Vec<usize> = (weekday..7).filter(|d| days[*d] == days[weekday]).collect();
+            same.iter().for_each(|d| handled[*d] = true);
+            rules.push(format!("{} {}", format_weekdays(&same), format_hours(&days[weekday])));
+        }
+    }
+    for exception in &schedule.exceptions {
+        let hours = if exception.hours.is_empty() {
+            "off".to_string()
+        } else {
+            format_hours(&exception.hours)
+        };
+        rules.push(format!(
+            "{} {}",
+            format_date_range(exception.start_date, exception.end_date),
+            hours
+        ));
+    }
+    rules.join("; ")
+}
+
+fn format_weekdays(days: &[usize]) -> String {
+    let mut runs: Vec<(usize, usize)> = Vec::new();
+    for day in days {
+        match runs.last_mut() {
+            Some((_, end)) if *end + 1 == *day => *end = *day,
+            _ => runs.push((*day, *day)),
+        }
+    }
+    runs.into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                WEEKDAYS[start].to_string()
+            } else {
+                format!("{}-{}", WEEKDAYS[start], WEEKDAYS[end])
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn format_hours(hours: &[TimeRange]) -> String {
+    hours
+        .iter()
+        .map(|(open, close)| {
+            let close = if *close == NaiveTime::MIN {
+                "24:00".to_string()
+            } else {
+                close.format("%H:%M").to_string()
+            };
+            format!("{}-{}", open.format("%H:%M"), close)
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
+fn format_date(date: NaiveDate) -> String {
+    format!("{} {} {:02}", date.year(), MONTHS[date.month0() as usize], date.day())
+}
+
+fn format_date_range(start: NaiveDate, end: NaiveDate) -> String {
+    if start == end {
+        format_date(start)
+    } else if start.year() == end.year() && start.month() == end.month() {
+        format!("{}-{:02}", format_date(start), end.day())
+    } else if start.year() == end.year() {
+        format!("{}-{} {:02}", format_date(start), MONTHS[end.month0() as usize], end.day())
+    } else {
+        format!("{}-{}", format_date(start), format_date(end))
+    }
+}
+
+/// Minimal scanner over a single rule.
+struct Cursor<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(rule: &'a str) -> Self {
+        Self { rest: rule.trim() }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if let Some(rest) = self.rest.strip_prefix(token) {
+            self.rest = rest;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn starts_with_weekday(&self) -> bool {
+        WEEKDAYS.iter().any(|d| self.rest.starts_with(d))
+    }
+
+    fn starts_with_month(&self) -> bool {
+        MONTHS.iter().any(|m| self.rest.starts_with(m))
+    }
+
+    fn starts_with_date(&self) -> bool {
+        let bytes = self.rest.as_bytes();
+        self.starts_with_month()
+            || (bytes.len() > 4 && bytes[..4].iter().all(u8::is_ascii_digit) && bytes[4] != b':')
+    }
+
+    fn number(&mut self, max_digits: usize) -> Result<u32, String> {
+        let digits = self
+            .rest
+            .chars()
+            .take(max_digits)
+            .take_while(char::is_ascii_digit)
+            .count();
+        if digits == 0 {
+            return Err(format!("Expected a number at `{}`", self.rest));
+        }
+        let (number, rest) = self.rest.split_at(digits);
+        self.rest = rest;
+        Ok(number.parse().expect("only digits"))
+    }
+
+    fn weekday(&mut self) -> Result<usize, String> {
+        let weekday = WEEKDAYS
+            .iter()
+            .position(|d| self.rest.starts_with(d))
+            .ok_or_else(|| format!("Expected a weekday at `{}`", self.rest))?;
+        self.rest = &self.rest[2..];
+        Ok(weekday)
+    }
+
+    fn weekdays(&mut self) -> Result<Vec<i32>, String> {
+        let mut weekdays = Vec::new();
+        loop {
+            let start = self.weekday()?;
+            let end = if self.eat("-") { self.weekday()? } else { start };
+            let mut day = start;
+            loop {
+                weekdays.push(day as i32);
+                if day == end {
+                    break;
+                }
+                day = (day + 1) % 7;
+            }
+            if !self.eat(",") {
+                break;
+            }
+        }
+        Ok(weekdays)
+    }
+
+    fn month(&mut self) -> Option<u32> {
+        let month = MONTHS.iter().position(|m| self.rest.starts_with(m))?;
+        self.rest = &self.rest[3..];
+        Some(month as u32 + 1)
+    }
+
+    /// Parses `[year] month day`. Missing parts are taken from the given defaults.
+    fn date(&mut self, default_year: i32, default_month: Option<u32>) -> Result<(i32, u32, u32), String> {
+        let year = if self.starts_with_month() || default_month.is_some() && !self.starts_with_date() {
+            default_year
+        } else {
+            let year = self.number(4)? as i32;
+            self.skip_whitespace();
+            year
+        };
+        let month = match self.month() {
+            Some(month) => {
+                self.skip_whitespace();
+                month
+            }
+            None => default_month.ok_or_else(|| format!("Expected a month at `{}`", self.rest))?,
+        };
+        let day = self.number(2)?;
+        Ok((year, month, day))
+    }
+
+    fn date_range(&mut self, year: i32) -> Result<(NaiveDate, NaiveDate), String> {
+        let invalid = |(y, m, d): (i32, u32, u32)| format!("Invalid date {y}-{m:02}-{d:02}");
+        let start = self.date(year, None)?;
+        let start_date = NaiveDate::from_ymd_opt(start.0, start.1, start.2).ok_or_else(|| invalid(start))?;
+        if !self.eat("-") {
+            return Ok((start_date, start_date));
+        }
+        let explicit_year = self.starts_with_date() && !self.starts_with_month();
+        let mut end = if self.starts_with_month() || explicit_year {
+            self.date(start.0, None)?
+        } else {
+            self.date(start.0, Some(start.1))?
+        };
+        if !explicit_year && (end.1, end.2) < (start.1, start.2) {
+            end.0 += 1;
+        }
+        let end_date = NaiveDate::from_ymd_opt(end.0, end.1, end.2).ok_or_else(|| invalid(end))?;
+        if end_date < start_date {
+            return Err(format!("Date range ends before it starts at {end_date}"));
+        }
+        Ok((start_date, end_date))
+    }
+
+    fn time(&mut self) -> Result<NaiveTime, String> {
+        let hour = self.number(2)?;
+        if !self.eat(":") {
+            return Err(format!("Expected `:` at `{}`", self.rest));
+        }
+        let minute = self.number(2)?;
+        if hour == 24 && minute == 0 {
+            return Ok(NaiveTime::MIN);
+        }
+        NaiveTime::from_hms_opt(hour, minute, 0).ok_or_else(|| format!("Invalid time {hour:02}:{minute:02}"))
+    }
+
+    /// Parses the remainder of a rule, either `off` or a list of time ranges.
+    fn hours(&mut self) -> Result<Vec<TimeRange>, String> {
+        self.skip_whitespace();
+        if self.rest == "off" || self.rest == "closed" {
+            return Ok(Vec::new());
+        }
+        let mut hours = Vec::new();
+        loop {
+            let open = self.time()?;
+            if !self.eat("-") {
+                return Err(format!("Expected `-` at `{}`", self.rest));
+            }
+            let close = self.time()?;
+            if open.second() != 0 || close.second() != 0 {
+                return Err("Seconds are not supported".to_string());
+            }
+            hours.push((open, close));
+            if !self.eat(",") {
+                break;
+            }
+        }
+        if !self.rest.trim().is_empty() {
+            return Err(format!("Unsupported syntax `{}`", self.rest.trim()));
+        }
+        Ok(hours)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::osm::{DateException, OsmSchedule, WeeklyRange, parse, to_osm};
+    use chrono::{NaiveDate, NaiveTime};
+    use proptest::prelude::*;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn parse_weekday_rules() {
+        let schedule = parse("Mo-Fr 08:00-18:00; Sa 09:00-12:00; PH off", 2025).unwrap();
+        assert_eq!(schedule.weekly.len(), 6);
+        assert_eq!(
+            schedule.weekly[5],
+            WeeklyRange {
+                weekday: 5,
+                open: time(9, 0),
+                close: time(12, 0)
+            }
+        );
+        assert_eq!(schedule.ignored_rules, vec!["PH off".to_string()]);
+
+        let schedule = parse("Mo-Sa 08:00-12:00,13:00-18:30; Sa 08:00-24:00; Fr-Mo 22:00-02:00; We off", 2025).unwrap();
+        assert_eq!(
+            to_osm(&schedule),
+            "Mo,Fr-Su 22:00-02:00; Tu,Th 08:00-12:00,13:00-18:30"
+        );
+
+        let schedule = parse("24/7", 2025).unwrap();
+        assert_eq!(schedule.weekly.len(), 7);
+        assert_eq!(to_osm(&schedule), "24/7");
+    }
+
+    #[test]
+    fn parse_date_rules() {
+        let schedule = parse("Mo-Fr 08:00-18:00; Dec 24-26 off; Dec 31 09:00-12:00; 2025 Dec 30-2026 Jan 02 off; Dec 28-Jan 03 off", 2025).unwrap();
+        assert_eq!(
+            schedule.exceptions,
+            vec![
+                DateException {
+                    start_date: date(2025, 12, 24),
+                    end_date: date(2025, 12, 26),
+                    hours: vec![],
+                },
+                DateException {
+                    start_date: date(2025, 12, 28),
+                    end_date: date(2026, 1, 3),
+                    hours: vec![],
+                },
+                DateException {
+                    start_date: date(2025, 12, 30),
+                    end_date: date(2026, 1, 2),
+                    hours: vec![],
+                },
+                DateException {
+                    start_date: date(2025, 12, 31),
+                    end_date: date(2025, 12, 31),
+                    hours: vec![(time(9, 0), time(12, 0))],
+                },
+            ]
+        );
+        assert_eq!(
+            to_osm(&schedule),
+            "Mo-Fr 08:00-18:00; 2025 Dec 24-26 off; 2025 Dec 28-2026 Jan 03 off; 2025 Dec 30-2026 Jan 02 off; 2025 Dec 31 09:00-12:00"
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("Mo-Fr 8-18", 2025).is_err());
+        assert!(parse("Mo-Fr 08:00-25:00", 2025).is_err());
+        assert!(parse("Xy 08:00-12:00", 2025).is_err());
+        assert!(parse("Mo 08:00-12:00 \"by appointment\"", 2025).is_err());
+        assert!(parse("Feb 30 off", 2025).is_err());
+        let error = parse("Mo 08:00-12:00; Tu 08:00", 2025).unwrap_err();
+        assert_eq!(error.rule, "Tu 08:00");
+    }
+
+    fn time_strategy() -> impl Strategy<Value = NaiveTime> {
+        (0u32..24, 0u32..60).prop_map(|(h, m)| time(h, m))
+    }
+
+    fn schedule_strategy() -> impl Strategy<Value = OsmSchedule> {
+        let weekly = prop::collection::vec(
+            (0i32..7, time_strategy(), time_strategy())
+                .prop_map(|(weekday, open, close)| WeeklyRange { weekday, open, close }),
+            0..10,
+        );
+        let exception = (2000i32..2100, 1u32..=365, 0u32..60, prop::collection::vec((time_strategy(), time_strategy()), 0..3))
+            .prop_map(|(year, day, length, hours)| {
+                let start_date = NaiveDate::from_yo_opt(year, day).unwrap();
+                DateException {
+                    start_date,
+                    end_date: start_date + chrono::Days::new(length as u64),
+                    hours,
+                }
+            });
+        let exceptions = prop::collection::vec(exception, 0..5);
+        (weekly, exceptions).prop_map(|(weekly, mut exceptions)| {
+            exceptions.sort_by_key(|e| (e.start_date, e.end_date));
+            exceptions.dedup_by_key(|e| (e.start_date, e.end_date));
+            let mut schedule = OsmSchedule {
+                weekly,
+                exceptions,
+                ignored_rules: Vec::new(),
+            };
+            schedule.normalize();
+            schedule
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip(schedule in schedule_strategy()) {
+            let osm = to_osm(&schedule);
+            let parsed = parse(&osm, 1999).unwrap();
+            prop_assert_eq!(&parsed, &schedule);
+            prop_assert_eq!(to_osm(&parsed), osm);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//! Farms imported from OpenStreetMap. The id of the OSM element, like `node/123` or `way/456`, is
+//! stored with the farm so a repeated import updates it instead of creating a duplicate.
+
+use crate::farm::{FarmUpdate, NewContact, apply_farm_update};
+use crate::location::{NewGeoLocation, insert_farm_location};
+use crate::osm::OsmSchedule;
+use crate::schema::{farms, shop_types};
+use crate::{DatabaseError, DbResult};
+use diesel::prelude::*;
+
+/// Farm mapped from the tags of an OSM element.
+pub struct OsmFarm {
+    pub osm_id: String,
+    pub name: String,
+    pub location: NewGeoLocation,
+    /// Names of shop types. Names missing from the catalogue are ignored.
+    pub shop_types: Vec<String>,
+    /// Replaces the opening hours if present.
+    pub schedule: Option<OsmSchedule>,
+    /// Lowercase address accepted by [`crate::contact::is_valid_email`].
+    pub email: Option<String>,
+    /// Phone number in E.164, see [`crate::contact::normalize_phone_number`].
+    pub phone: Option<String>,
+    pub address: Option<String>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum OsmImport {
+    Created,
+    Updated,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farms)]
+struct NewOsmFarm {
+    name: String,
+    osm_id: String,
+}
+
+/// Creates the farm or updates the farm previously imported from the same OSM element. The contact
+/// is only replaced if the element has any contact tags, so details added by farmers survive.
+pub fn import_farm(conn: &mut PgConnection, farm: OsmFarm) -> DbResult<OsmImport> {
+    conn.transaction::<_, DatabaseError, _>(|conn| {
+        let existing: Option<i32> = farms::table
+            .filter(farms::osm_id.eq(&farm.osm_id))
+            .select(farms::id)
+            .first(conn)
+            .optional()?;
+        let shop_type_ids: Vec<i32> = shop_types::table
+            .filter(shop_types::name.eq_any(&farm.shop_types))
+            .select(shop_types::id)
+            .load(conn)?;
+        let (farm_id, outcome, location) = match existing {
+            Some(farm_id) => (farm_id, OsmImport::Updated, Some(farm.location)),
+            None => {
+                let farm_id = diesel::insert_into(farms::table)
+                    .values(NewOsmFarm {
+                        name: farm.name.clone(),
+                        osm_id: farm.osm_id,
+                    })
+                    .returning(farms::id)
+                    .get_result(conn)?;
+                insert_farm_location(conn, farm.location, farm_id, None)?;
+                (farm_id, OsmImport::Created, None)
+            }
+        };
+        let has_contact = farm.email.is_some() || farm.phone.is_some() || farm.address.is_some();
+        let update = FarmUpdate {
+            name: Some(farm.name),
+            location,
+            shop_types: Some(shop_type_ids),
+            opening_hours: farm.schedule.as_ref().map(|s| s.opening_hours(farm_id)),
+            opening_hours_exceptions: farm.schedule.as_ref().map(|s| s.opening_hours_exceptions(farm_id)),
+            contact: has_contact.then_some(NewContact {
+                farm_id,
+                email: farm.email,
+                phone: farm.phone,
+                address: farm.address,
+                email_public: true,
+                phone_public: true,
+                address_public: true,
+            }),
+        };
+        apply_farm_update(conn, farm_id, update)?;
+        Ok(outcome)
+    })
+}
@@ -0,0 +1,10 @@
+//! Pages of list queries. Pages start after the sort key of the last row of the previous page
+//! rather than at an offset, so rows added or removed meanwhile don't shift later pages.
+
+/// Rows of one page together with the number of rows matching the query on all pages.
+pub struct Page<T> {
+    /// Up to the requested number of rows. Callers ask for one row more than they return to learn
+    /// whether there is a next page.
+    pub rows: Vec<T>,
+    pub total: i64,
+}
@@ -0,0 +1,77 @@
+//! Photos of farms. The image files themselves live in the photo storage of the server, a row only
+//! records where to find them.
+
+use chrono::{DateTime, Utc};
+use diesel::prelude::*;
+use uuid::Uuid;
+use crate::{DbResult, FarmDB};
+use crate::farm::{Farm, farm_admin_exists};
+use crate::schema::farm_photos;
+
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(Farm))]
+pub struct FarmPhoto {
+    pub id: i32,
+    pub farm_id: i32,
+    /// Id of the photo and its thumbnails in the photo storage.
+    pub storage_id: Uuid,
+    /// File extension of the stored files, like `jpg`.
+    pub format: String,
+    pub width: i32,
+    pub height: i32,
+    pub created: DateTime<Utc>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = farm_photos)]
+pub struct NewFarmPhoto {
+    pub farm_id: i32,
+    pub storage_id: Uuid,
+    pub format: String,
+    pub width: i32,
+    pub height: i32,
+}
+
+/// Photos of the farm in the order they were uploaded.
+pub async fn list_photos(db: &FarmDB, farm_id: i32) -> DbResult<Vec<FarmPhoto>> {
+    Ok(db.run(move |conn| photos_of_farms(conn, vec![farm_id])).await?)
+}
+
+pub(crate) fn photos_of_farms(conn: &mut PgConnection, farm_ids: Vec<i32>) -> QueryResult<Vec<FarmPhoto>> {
+    farm_photos::table
+        .filter(farm_photos::farm_id.eq_any(farm_ids))
+        .select(FarmPhoto::as_select())
+        .order(farm_photos::id)
+        .load(conn)
+}
+
+/// Returns `None` if the user is no admin of the farm.
+pub async fn create_photo(db: &FarmDB, user_id: i32, photo: NewFarmPhoto) -> DbResult<Option<FarmPhoto>> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, photo.farm_id)? {
+            return Ok(None);
+        }
+        let photo = diesel::insert_into(farm_photos::table)
+            .values(photo)
+            .returning(FarmPhoto::as_returning())
+            .get_result(conn)?;
+        Ok(Some(photo))
+    }).await
+}
+
+/// Returns the deleted photo, so its files can be removed from the storage. `None` if the user is
+/// no admin of the farm or the farm has no such photo.
+pub async fn delete_photo(db: &FarmDB, user_id: i32, farm_id: i32, photo_id: i32) -> DbResult<Option<FarmPhoto>> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, farm_id)? {
+            return Ok(None);
+        }
+        let photo = diesel::delete(farm_photos::table)
+            .filter(farm_photos::id.eq(photo_id))
+            .filter(farm_photos::farm_id.eq(farm_id))
+            .returning(FarmPhoto::as_returning())
+            .get_result(conn)
+            .optional()?;
+        Ok(photo)
+    }).await
+}
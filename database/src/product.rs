@@ -0,0 +1,327 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Bool, Integer};
+use crate::{DatabaseError, DbResult, FarmDB};
+use crate::farm::{Farm, farm_admin_exists};
+use crate::schema::{product_availability, product_categories, products};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Identifiable, Queryable, Selectable)]
+#[diesel(table_name = product_categories)]
+pub struct ProductCategory {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(Farm))]
+pub struct Product {
+    pub id: i32,
+    pub farm_id: i32,
+    pub category_id: Option<i32>,
+    pub name: String,
+    pub unit: String,
+    /// In the minor unit of the currency, like cents.
+    pub price: Option<i32>,
+    pub currency: String,
+    pub organic: bool,
+    /// Quantity in stock, `None` if not tracked.
+    pub stock: Option<i32>,
+    pub stock_updated: Option<DateTime<Utc>>,
+}
+
+/// New stock level of a product. `None` stops tracking the stock.
+pub struct StockLevel {
+    pub product_id: i32,
+    pub stock: Option<i32>,
+}
+
+/// Yearly recurring period a product is available in, from the start day to the end day, both
+/// inclusive. A period ending before its start spans the turn of the year.
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(Product))]
+#[diesel(table_name = product_availability)]
+pub struct Availability {
+    pub id: i32,
+    pub product_id: i32,
+    pub start_month: i32,
+    pub start_day: i32,
+    pub end_month: i32,
+    pub end_day: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = product_availability)]
+pub struct NewAvailability {
+    pub product_id: i32,
+    pub start_month: i32,
+    pub start_day: i32,
+    pub end_month: i32,
+    pub end_day: i32,
+}
+
+/// Product with the name of its category and its availability. A product without availability
+/// periods is available all year.
+pub struct FullProduct {
+    pub product: Product,
+    pub category: Option<String>,
+    pub availability: Vec<Availability>,
+}
+
+/// Dates a product has to be available on at least one of, both inclusive.
+#[derive(Clone, Copy, Debug)]
+pub struct DateRange {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+}
+
+impl DateRange {
+    /// The range as intervals of `month * 100 + day`, split at the turn of the year. `None` if the
+    /// range covers a whole year.
+    fn month_day_intervals(&self) -> Option<Vec<(i32, i32)>> {
+        if self.to.signed_duration_since(self.from).num_days() >= 365 {
+            return None;
+        }
+        let month_day = |date: NaiveDate| date.month() as i32 * 100 + date.day() as i32;
+        let (from, to) = (month_day(self.from), month_day(self.to));
+        if self.from.year() == self.to.year() {
+            Some(vec![(from, to)])
+        } else {
+            Some(vec![(from, 1231), (101, to)])
+        }
+    }
+}
+
+#[derive(Insertable, AsChangeset)]
+#[diesel(table_name = products)]
+#[diesel(treat_none_as_null = true)]
+pub struct NewProduct {
+    pub farm_id: i32,
+    pub category_id: Option<i32>,
+    pub name: String,
+    pub unit: String,
+    pub price: Option<i32>,
+    pub currency: String,
+    pub organic: bool,
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// `ILIKE` pattern matching any text containing `text`.
+pub(crate) fn contains_pattern(text: &str) -> String {
+    format!("%{}%", escape_like(text))
+}
+
+/// `LIKE` pattern matching any text starting with `text`.
+pub(crate) fn prefix_pattern(text: &str) -> String {
+    format!("{}%", escape_like(text))
+}
+
+pub async fn list_categories(db: &FarmDB) -> DbResult<Vec<ProductCategory>> {
+    let categories = db.run(move |conn| {
+        product_categories::table
+            .select(ProductCategory::as_select())
+            .order(product_categories::name)
+            .load(conn)
+    }).await?;
+    Ok(categories)
+}
+
+/// Products of the farms, ordered by name.
+pub async fn load_products(db: &FarmDB, farm_ids: Vec<i32>) -> DbResult<Vec<FullProduct>> {
+    Ok(db.run(move |conn| products_of_farms(conn, farm_ids)).await?)
+}
+
+pub(crate) fn products_of_farms(conn: &mut PgConnection, farm_ids: Vec<i32>) -> QueryResult<Vec<FullProduct>> {
+    let products = products::table
+        .left_join(product_categories::table)
+        .filter(products::farm_id.eq_any(farm_ids))
+        .select((Product::as_select(), product_categories::name.nullable()))
+        .order(products::name)
+        .load::<(Product, Option<String>)>(conn)?;
+    let product_ids: Vec<i32> = products.iter().map(|(p, _)| p.id).collect();
+    let mut availability: HashMap<i32, Vec<Availability>> = HashMap::new();
+    for period in product_availability::table
+        .filter(product_availability::product_id.eq_any(product_ids))
+        .select(Availability::as_select())
+        .order((product_availability::start_month, product_availability::start_day))
+        .load::<Availability>(conn)?
+    {
+        availability.entry(period.product_id).or_default().push(period);
+    }
+    Ok(products
+        .into_iter()
+        .map(|(product, category)| FullProduct {
+            availability: availability.remove(&product.id).unwrap_or_default(),
+            product,
+            category,
+        })
+        .collect())
+}
+
+/// Ids of the farms selling a product whose name contains `name`, ignoring case, and that is
+/// available during `available`. `None` if neither is given.
+pub(crate) fn farms_selling(
+    name: Option<String>,
+    available: Option<DateRange>,
+) -> Option<products::BoxedQuery<'static, Pg, Integer>> {
+    if name.is_none() && available.is_none() {
+        return None;
+    }
+    let mut query = products::table.select(products::farm_id).into_boxed();
+    if let Some(name) = name {
+        query = query.filter(products::name.ilike(contains_pattern(&name)));
+    }
+    if let Some(intervals) = available.and_then(|range| range.month_day_intervals()) {
+        let always_available = products::id.ne_all(product_availability::table.select(product_availability::product_id));
+        let available_then = products::id.eq_any(
+            product_availability::table
+                .select(product_availability::product_id)
+                .into_boxed()
+                .filter(overlapping_any(&intervals)),
+        );
+        query = query.filter(always_available.or(available_then));
+    }
+    Some(query)
+}
+
+/// Availability periods overlapping any of the `month * 100 + day` intervals, of which there is at
+/// least one.
+fn overlapping_any(intervals: &[(i32, i32)]) -> Box<dyn BoxableExpression<product_availability::table, Pg, SqlType = Bool>> {
+    let start = || product_availability::start_month * 100 + product_availability::start_day;
+    let end = || product_availability::end_month * 100 + product_availability::end_day;
+    intervals
+        .iter()
+        .map(|&(from, to)| -> Box<dyn BoxableExpression<product_availability::table, Pg, SqlType = Bool>> {
+            let within_year = start().le(end()).and(start().le(to)).and(end().ge(from));
+            let across_years = start().gt(end()).and(start().le(to).or(end().ge(from)));
+            Box::new(within_year.or(across_years))
+        })
+        .reduce(|a, b| Box::new(a.or(b)))
+        .expect("at least one interval")
+}
+
+pub async fn by_name(db: &FarmDB, farm_id: i32, name: String) -> DbResult<Option<Product>> {
+    let product = db.run(move |conn| {
+        products::table
+            .filter(products::farm_id.eq(farm_id))
+            .filter(products::name.eq(name))
+            .select(Product::as_select())
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(product)
+}
+
+/// Returns `None` if the user is no admin of the farm.
+pub async fn create_product(db: &FarmDB, user_id: i32, product: NewProduct) -> DbResult<Option<Product>> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, product.farm_id)? {
+            return Ok(None);
+        }
+        let product = diesel::insert_into(products::table)
+            .values(product)
+            .returning(Product::as_returning())
+            .get_result(conn)?;
+        Ok(Some(product))
+    }).await
+}
+
+/// Replaces all fields of the product. Returns `None` if the user is no admin of the farm or the
+/// farm has no such product.
+pub async fn update_product(
+    db: &FarmDB,
+    user_id: i32,
+    product_id: i32,
+    product: NewProduct,
+) -> DbResult<Option<Product>> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, product.farm_id)? {
+            return Ok(None);
+        }
+        let product = diesel::update(products::table)
+            .filter(products::id.eq(product_id))
+            .filter(products::farm_id.eq(product.farm_id))
+            .set(&product)
+            .returning(Product::as_returning())
+            .get_result(conn)
+            .optional()?;
+        Ok(product)
+    }).await
+}
+
+/// Replaces the availability periods of the product. Returns `false` if the user is no admin of the
+/// farm or the farm has no such product.
+pub async fn replace_availability(
+    db: &FarmDB,
+    user_id: i32,
+    farm_id: i32,
+    product_id: i32,
+    periods: Vec<NewAvailability>,
+) -> DbResult<bool> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            let of_farm: bool = diesel::select(diesel::dsl::exists(
+                products::table
+                    .filter(products::id.eq(product_id))
+                    .filter(products::farm_id.eq(farm_id)),
+            ))
+            .get_result(conn)?;
+            if !of_farm || !farm_admin_exists(conn, user_id, farm_id)? {
+                return Ok(false);
+            }
+            diesel::delete(product_availability::table.filter(product_availability::product_id.eq(product_id)))
+                .execute(conn)?;
+            diesel::insert_into(product_availability::table)
+                .values(&periods)
+                .execute(conn)?;
+            Ok(true)
+        })
+    }).await
+}
+
+/// Sets the stock levels of products of the farm. Authorization is up to the caller, as devices
+/// update stock levels with an API key instead of a user login. Returns `None` without changing
+/// anything if any product doesn't belong to the farm.
+pub async fn set_stock_levels(db: &FarmDB, farm_id: i32, levels: Vec<StockLevel>) -> DbResult<Option<Vec<Product>>> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            let product_ids: Vec<i32> = levels.iter().map(|l| l.product_id).collect();
+            let known: i64 = products::table
+                .filter(products::farm_id.eq(farm_id))
+                .filter(products::id.eq_any(&product_ids))
+                .count()
+                .get_result(conn)?;
+            if known as usize != product_ids.iter().collect::<HashSet<_>>().len() {
+                return Ok(None);
+            }
+            let now = Utc::now();
+            let mut updated = Vec::with_capacity(levels.len());
+            for level in levels {
+                let product = diesel::update(products::table.find(level.product_id))
+                    .set((products::stock.eq(level.stock), products::stock_updated.eq(now)))
+                    .returning(Product::as_returning())
+                    .get_result(conn)?;
+                updated.push(product);
+            }
+            Ok(Some(updated))
+        })
+    }).await
+}
+
+/// Returns `false` if the user is no admin of the farm or the farm has no such product.
+pub async fn delete_product(db: &FarmDB, user_id: i32, farm_id: i32, product_id: i32) -> DbResult<bool> {
+    db.run(move |conn| {
+        if !farm_admin_exists(conn, user_id, farm_id)? {
+            return Ok(false);
+        }
+        let deleted = diesel::delete(products::table)
+            .filter(products::id.eq(product_id))
+            .filter(products::farm_id.eq(farm_id))
+            .execute(conn)?;
+        Ok(deleted > 0)
+    }).await
+}
@@ -1,9 +1,17 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
-    #[derive(diesel::sql_types::SqlType)]
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "farm_admin_status"))]
     pub struct FarmAdminStatus;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "geography"))]
+    pub struct Geography;
+
+    #[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "tsvector", schema = "pg_catalog"))]
+    pub struct Tsvector;
 }
 
 diesel::table! {
@@ -13,6 +21,9 @@ diesel::table! {
         email -> Nullable<Text>,
         phone -> Nullable<Text>,
         address -> Nullable<Text>,
+        email_public -> Bool,
+        phone_public -> Bool,
+        address_public -> Bool,
     }
 }
 
@@ -24,11 +35,64 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    farm_api_keys (id) {
+        id -> Int4,
+        farm_id -> Int4,
+        name -> Text,
+        key_digest -> Bytea,
+        created -> Timestamptz,
+    }
+}
+
 diesel::table! {
     farm_locations (id) {
         id -> Int4,
         farm_id -> Int4,
         location_id -> Int4,
+        label -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::FarmAdminStatus;
+
+    farm_owner_requests (id) {
+        id -> Int4,
+        user_id -> Int4,
+        motivation -> Text,
+        evidence -> Nullable<Text>,
+        status -> FarmAdminStatus,
+        created -> Timestamptz,
+        decided -> Nullable<Timestamptz>,
+        decided_by -> Nullable<Int4>,
+        reason -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    farm_photos (id) {
+        id -> Int4,
+        farm_id -> Int4,
+        storage_id -> Uuid,
+        format -> Text,
+        width -> Int4,
+        height -> Int4,
+        created -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Tsvector;
+
+    farm_search (farm_id) {
+        farm_id -> Int4,
+        name -> Text,
+        products -> Text,
+        address -> Text,
+        document -> Tsvector,
     }
 }
 
@@ -37,6 +101,7 @@ diesel::table! {
         id -> Int4,
         farm_id -> Int4,
         shop_type_id -> Int4,
+        farm_location_id -> Nullable<Int4>,
     }
 }
 
@@ -46,14 +111,19 @@ diesel::table! {
         name -> Text,
         created -> Timestamp,
         ext_id -> Uuid,
+        osm_id -> Nullable<Text>,
     }
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::Geography;
+
     geolocations (id) {
         id -> Int4,
-        lat -> Float4,
-        lon -> Float4,
+        lat -> Float8,
+        lon -> Float8,
+        position -> Nullable<Geography>,
     }
 }
 
@@ -64,6 +134,61 @@ diesel::table! {
         weekday -> Int4,
         open -> Time,
         close -> Time,
+        farm_location_id -> Nullable<Int4>,
+    }
+}
+
+diesel::table! {
+    opening_hours_exceptions (id) {
+        id -> Int4,
+        farm_id -> Int4,
+        start_date -> Date,
+        end_date -> Date,
+        open -> Nullable<Time>,
+        close -> Nullable<Time>,
+        description -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    product_availability (id) {
+        id -> Int4,
+        product_id -> Int4,
+        start_month -> Int4,
+        start_day -> Int4,
+        end_month -> Int4,
+        end_day -> Int4,
+    }
+}
+
+diesel::table! {
+    product_categories (id) {
+        id -> Int4,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    products (id) {
+        id -> Int4,
+        farm_id -> Int4,
+        category_id -> Nullable<Int4>,
+        name -> Text,
+        unit -> Text,
+        price -> Nullable<Int4>,
+        currency -> Text,
+        organic -> Bool,
+        stock -> Nullable<Int4>,
+        stock_updated -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    shop_type_translations (id) {
+        id -> Int4,
+        shop_type_id -> Int4,
+        language -> Text,
+        name -> Text,
     }
 }
 
@@ -71,6 +196,7 @@ diesel::table! {
     shop_types (id) {
         id -> Int4,
         name -> Text,
+        retired -> Bool,
     }
 }
 
@@ -88,26 +214,45 @@ diesel::table! {
         sysadmin -> Int4,
         farmowner -> FarmAdminStatus,
         ext_id -> Uuid,
+        locked -> Bool,
     }
 }
 
 diesel::joinable!(contact -> farms (farm_id));
 diesel::joinable!(farm_admins -> farms (farm_id));
 diesel::joinable!(farm_admins -> users (user_id));
+diesel::joinable!(farm_api_keys -> farms (farm_id));
 diesel::joinable!(farm_locations -> farms (farm_id));
 diesel::joinable!(farm_locations -> geolocations (location_id));
+diesel::joinable!(farm_owner_requests -> users (user_id));
+diesel::joinable!(farm_photos -> farms (farm_id));
+diesel::joinable!(farm_search -> farms (farm_id));
 diesel::joinable!(farm_shop_types -> farms (farm_id));
 diesel::joinable!(farm_shop_types -> shop_types (shop_type_id));
 diesel::joinable!(opening_hours -> farms (farm_id));
+diesel::joinable!(opening_hours_exceptions -> farms (farm_id));
+diesel::joinable!(product_availability -> products (product_id));
+diesel::joinable!(products -> farms (farm_id));
+diesel::joinable!(products -> product_categories (category_id));
+diesel::joinable!(shop_type_translations -> shop_types (shop_type_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
     contact,
     farm_admins,
+    farm_api_keys,
     farm_locations,
+    farm_owner_requests,
+    farm_photos,
+    farm_search,
     farm_shop_types,
     farms,
     geolocations,
     opening_hours,
+    opening_hours_exceptions,
+    product_availability,
+    product_categories,
+    products,
+    shop_type_translations,
     shop_types,
     users,
 );
@@ -0,0 +1,243 @@
+//! Full-text and fuzzy search of farms by their name, the names of their products and their public
+//! address, based on the `farm_search` table kept up to date by triggers.
+
+use diesel::dsl::sql;
+use diesel::expression::SqlLiteral;
+use diesel::infix_operator;
+use diesel::pg::Pg;
+use diesel::prelude::*;
+use diesel::sql_types::{Double, Integer, Nullable, Text};
+use crate::{DbResult, FarmDB};
+use crate::farm::{Farm, ShopType};
+use crate::page::Page;
+use crate::product::prefix_pattern;
+use crate::schema::{farm_search, farms, products, shop_type_translations, shop_types};
+use crate::schema::sql_types::Tsvector;
+
+/// Marks the start of a match in highlighted text.
+pub const HIGHLIGHT_START: char = '\u{2}';
+/// Marks the end of a match in highlighted text.
+pub const HIGHLIGHT_END: char = '\u{3}';
+
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "tsquery", schema = "pg_catalog"))]
+pub struct Tsquery;
+
+/// Text search configuration, only used as `'simple'` as farms are named in many languages.
+#[derive(diesel::query_builder::QueryId, Clone, diesel::sql_types::SqlType)]
+#[diesel(postgres_type(name = "regconfig", schema = "pg_catalog"))]
+pub struct Regconfig;
+
+define_sql_function! {
+    fn to_tsquery(config: Regconfig, query: Text) -> Tsquery;
+}
+
+define_sql_function! {
+    fn ts_rank(document: Tsvector, query: Tsquery) -> Float;
+}
+
+define_sql_function! {
+    fn ts_headline(config: Regconfig, document: Text, query: Tsquery, options: Text) -> Text;
+}
+
+define_sql_function! {
+    /// Greatest trigram similarity of `a` to any part of `b`, from 0 to 1.
+    fn word_similarity(a: Text, b: Text) -> Float;
+}
+
+define_sql_function! {
+    fn lower(text: Text) -> Text;
+}
+
+define_sql_function! {
+    /// Distance in metres to the closest location of the farm.
+    fn farm_distance(farm_id: Integer, lat: Nullable<Double>, lon: Nullable<Double>) -> Nullable<Double>;
+}
+
+infix_operator!(Matches, " @@ ", backend: Pg);
+// Uses the trigram indexes, unlike comparing `word_similarity` with a threshold.
+infix_operator!(WordSimilar, " <% ", backend: Pg);
+
+fn simple() -> SqlLiteral<Regconfig> {
+    sql::<Regconfig>("'simple'")
+}
+
+/// Lower-cased name of rows in `table`, compared byte-wise like in the prefix indexes.
+fn name_key(table: &str) -> SqlLiteral<Text> {
+    sql(&format!(r#"lower({table}.name) COLLATE "C""#))
+}
+
+/// `to_tsquery` syntax for documents containing words starting with every word of `text`. `None`
+/// if `text` has no words.
+pub fn prefix_query(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("{}:*", word.to_lowercase()))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" & "))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SearchOrder {
+    Relevance,
+    /// Closest first, which needs a position to search from.
+    Distance,
+}
+
+pub struct FarmSearch {
+    pub text: String,
+    /// Position as `(lat, lon)` to measure distances from.
+    pub near: Option<(f64, f64)>,
+    pub order: SearchOrder,
+    pub limit: i64,
+    /// Hits of previous pages to skip. Ranked hits have no stable sort key to continue after.
+    pub offset: i64,
+}
+
+/// Matched farm with its searchable texts, in which [HIGHLIGHT_START] and [HIGHLIGHT_END] enclose
+/// the matching words. Products and address are shortened to the parts around matches.
+pub struct SearchHit {
+    pub farm: Farm,
+    pub relevance: f32,
+    /// Kilometres to the closest location, if searched near a position.
+    pub distance: Option<f64>,
+    pub name: String,
+    pub products: String,
+    pub address: String,
+}
+
+/// Farms matching all words of the search text as word prefixes or resembling it, the most
+/// relevant or closest first.
+pub async fn search_farms(db: &FarmDB, search: FarmSearch) -> DbResult<Page<SearchHit>> {
+    let Some(terms) = prefix_query(&search.text) else {
+        return Ok(Page { rows: Vec::new(), total: 0 });
+    };
+    db.run(move |conn| {
+        let query = || to_tsquery(simple(), terms.clone());
+        let text = || search.text.clone().into_sql::<Text>();
+        let relevance = ts_rank(farm_search::document, query())
+            + word_similarity(text(), farm_search::name)
+            + word_similarity(text(), farm_search::products) * 0.5f32
+            + word_similarity(text(), farm_search::address) * 0.25f32;
+        let (lat, lon) = search.near.unzip();
+        let distance = farm_distance(farm_search::farm_id, lat, lon);
+        let marks = format!("StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_END}");
+        let whole = format!("{marks}, HighlightAll=true");
+        let fragments = format!("{marks}, MaxFragments=2, MaxWords=8, MinWords=3");
+        let matching = || Matches::new(farm_search::document, query())
+            .or(WordSimilar::new(text(), farm_search::name))
+            .or(WordSimilar::new(text(), farm_search::products))
+            .or(WordSimilar::new(text(), farm_search::address));
+        let total = farm_search::table.filter(matching()).count().get_result(conn)?;
+        let mut select = farm_search::table
+            .inner_join(farms::table)
+            .filter(matching())
+            .select((
+                Farm::as_select(),
+                relevance.clone(),
+                distance,
+                ts_headline(simple(), farm_search::name, query(), whole.clone()),
+                ts_headline(simple(), farm_search::products, query(), fragments),
+                ts_headline(simple(), farm_search::address, query(), whole),
+            ))
+            .limit(search.limit)
+            .offset(search.offset)
+            .into_boxed();
+        select = match search.order {
+            SearchOrder::Relevance => select.order((relevance.desc(), farms::id)),
+            SearchOrder::Distance => select.order((distance.asc().nulls_last(), relevance.desc(), farms::id)),
+        };
+        let rows = select
+            .load::<(Farm, f32, Option<f64>, String, String, String)>(conn)?
+            .into_iter()
+            .map(|(farm, relevance, distance, name, products, address)| SearchHit {
+                farm,
+                relevance,
+                distance: distance.map(|d| d / 1000.0),
+                name,
+                products,
+                address,
+            })
+            .collect();
+        Ok(Page { rows, total })
+    }).await
+}
+
+/// Completions of a typed prefix, each list ordered by name.
+pub struct Suggestions {
+    pub shop_types: Vec<ShopTypeSuggestion>,
+    pub farms: Vec<Farm>,
+    /// Products with the farm selling them.
+    pub products: Vec<(String, Farm)>,
+}
+
+pub struct ShopTypeSuggestion {
+    pub shop_type: ShopType,
+    /// Translation of the name into the requested language, if there is one.
+    pub translation: Option<String>,
+}
+
+/// Up to `limit` shop types, farms and products each whose name starts with `prefix`, ignoring
+/// case. Shop types also match by their translation into `language`. Farm and product names are
+/// looked up and ordered using the prefix indexes on their lower-cased names.
+pub async fn autocomplete(db: &FarmDB, prefix: String, language: Option<String>, limit: i64) -> DbResult<Suggestions> {
+    let pattern = prefix_pattern(&prefix);
+    db.run(move |conn| {
+        let translation_name = shop_type_translations::name.nullable();
+        let shop_types = shop_types::table
+            .left_join(
+                shop_type_translations::table.on(shop_type_translations::shop_type_id
+                    .eq(shop_types::id)
+                    .and(shop_type_translations::language.eq(language.unwrap_or_default()))),
+            )
+            .filter(shop_types::retired.eq(false))
+            .filter(
+                lower(shop_types::name)
+                    .like(lower(pattern.clone()))
+                    .or(lower(shop_type_translations::name).like(lower(pattern.clone())).nullable()),
+            )
+            .select((ShopType::as_select(), translation_name))
+            .order(shop_types::name)
+            .limit(limit)
+            .load::<(ShopType, Option<String>)>(conn)?
+            .into_iter()
+            .map(|(shop_type, translation)| ShopTypeSuggestion { shop_type, translation })
+            .collect();
+        let farms = farms::table
+            .filter(name_key("farms").like(lower(pattern.clone())))
+            .select(Farm::as_select())
+            .order((name_key("farms"), farms::id))
+            .limit(limit)
+            .load(conn)?;
+        let products = products::table
+            .inner_join(farms::table)
+            .filter(name_key("products").like(lower(pattern)))
+            .select((products::name, Farm::as_select()))
+            .order((name_key("products"), products::id))
+            .limit(limit)
+            .load(conn)?;
+        Ok(Suggestions { shop_types, farms, products })
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::product::prefix_pattern;
+    use crate::search::prefix_query;
+
+    #[test]
+    fn prefix_queries() {
+        assert_eq!(Some("hof:* & m:*".to_string()), prefix_query("Hof M."));
+        assert_eq!(Some("bio:* & eier:*".to_string()), prefix_query("  bio-Eier "));
+        assert_eq!(Some("o:* & malley:*".to_string()), prefix_query("O'Malley"));
+        assert_eq!(None, prefix_query(" &|!:* "));
+    }
+
+    #[test]
+    fn prefix_patterns() {
+        assert_eq!("hof%", prefix_pattern("hof"));
+        assert_eq!("100\\%%", prefix_pattern("100%"));
+        assert_eq!("a\\_b\\\\%", prefix_pattern("a_b\\"));
+    }
+}
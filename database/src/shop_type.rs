@@ -0,0 +1,178 @@
+use diesel::prelude::*;
+use crate::{DbResult, FarmDB};
+use crate::farm::ShopType;
+use crate::page::Page;
+use crate::schema::{shop_type_translations, shop_types};
+
+#[derive(Identifiable, Queryable, Selectable, Associations)]
+#[diesel(belongs_to(ShopType))]
+pub struct ShopTypeTranslation {
+    pub id: i32,
+    pub shop_type_id: i32,
+    pub language: String,
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = shop_type_translations)]
+pub struct NewShopTypeTranslation {
+    pub shop_type_id: i32,
+    pub language: String,
+    pub name: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = shop_types)]
+pub struct NewShopType {
+    pub name: String,
+}
+
+/// Partial change of a shop type. Retired shop types stay assigned to their farms but can not be
+/// assigned to further farms.
+#[derive(AsChangeset, Default)]
+#[diesel(table_name = shop_types)]
+pub struct ShopTypeChange {
+    pub name: Option<String>,
+    pub retired: Option<bool>,
+}
+
+pub async fn list_shop_types(db: &FarmDB, include_retired: bool) -> DbResult<Vec<ShopType>> {
+    let shop_types = db.run(move |conn| {
+        let mut query = shop_types::table
+            .select(ShopType::as_select())
+            .order(shop_types::name)
+            .into_boxed();
+        if !include_retired {
+            query = query.filter(shop_types::retired.eq(false));
+        }
+        query.load(conn)
+    }).await?;
+    Ok(shop_types)
+}
+
+/// Page of [list_shop_types] with up to `limit` shop types named after `after`.
+pub async fn list_shop_types_page(
+    db: &FarmDB,
+    include_retired: bool,
+    after: Option<String>,
+    limit: i64,
+) -> DbResult<Page<ShopType>> {
+    db.run(move |conn| {
+        let listed = || {
+            let mut query = shop_types::table.into_boxed();
+            if !include_retired {
+                query = query.filter(shop_types::retired.eq(false));
+            }
+            query
+        };
+        let total = listed().count().get_result(conn)?;
+        let mut query = listed()
+            .select(ShopType::as_select())
+            .order(shop_types::name)
+            .limit(limit);
+        if let Some(name) = after {
+            query = query.filter(shop_types::name.gt(name));
+        }
+        Ok(Page { rows: query.load(conn)?, total })
+    }).await
+}
+
+pub async fn list_translations(db: &FarmDB) -> DbResult<Vec<ShopTypeTranslation>> {
+    let translations = db.run(move |conn| {
+        shop_type_translations::table
+            .select(ShopTypeTranslation::as_select())
+            .order(shop_type_translations::language)
+            .load(conn)
+    }).await?;
+    Ok(translations)
+}
+
+pub async fn translations_of(db: &FarmDB, shop_type_id: i32) -> DbResult<Vec<ShopTypeTranslation>> {
+    let translations = db.run(move |conn| {
+        shop_type_translations::table
+            .filter(shop_type_translations::shop_type_id.eq(shop_type_id))
+            .select(ShopTypeTranslation::as_select())
+            .order(shop_type_translations::language)
+            .load(conn)
+    }).await?;
+    Ok(translations)
+}
+
+pub async fn by_id(db: &FarmDB, shop_type_id: i32) -> DbResult<Option<ShopType>> {
+    let shop_type = db.run(move |conn| {
+        shop_types::table
+            .find(shop_type_id)
+            .select(ShopType::as_select())
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(shop_type)
+}
+
+pub async fn by_name(db: &FarmDB, name: String) -> DbResult<Option<ShopType>> {
+    let shop_type = db.run(move |conn| {
+        shop_types::table
+            .filter(shop_types::name.eq(name))
+            .select(ShopType::as_select())
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(shop_type)
+}
+
+pub async fn create_shop_type(db: &FarmDB, shop_type: NewShopType) -> DbResult<ShopType> {
+    let shop_type = db.run(move |conn| {
+        diesel::insert_into(shop_types::table)
+            .values(shop_type)
+            .returning(ShopType::as_returning())
+            .get_result(conn)
+    }).await?;
+    Ok(shop_type)
+}
+
+/// Returns `None` if there is no shop type with the given id.
+pub async fn update_shop_type(db: &FarmDB, shop_type_id: i32, change: ShopTypeChange) -> DbResult<Option<ShopType>> {
+    if change.name.is_none() && change.retired.is_none() {
+        return by_id(db, shop_type_id).await;
+    }
+    let shop_type = db.run(move |conn| {
+        diesel::update(shop_types::table.find(shop_type_id))
+            .set(change)
+            .returning(ShopType::as_returning())
+            .get_result(conn)
+            .optional()
+    }).await?;
+    Ok(shop_type)
+}
+
+pub async fn delete_shop_type(db: &FarmDB, shop_type_id: i32) -> DbResult<()> {
+    db.run(move |conn| {
+        diesel::delete(shop_types::table.find(shop_type_id)).execute(conn)
+    }).await?;
+    Ok(())
+}
+
+/// Adds the translation or replaces the existing one for the same language.
+pub async fn set_translation(db: &FarmDB, translation: NewShopTypeTranslation) -> DbResult<ShopTypeTranslation> {
+    let translation = db.run(move |conn| {
+        diesel::insert_into(shop_type_translations::table)
+            .values(&translation)
+            .on_conflict((shop_type_translations::shop_type_id, shop_type_translations::language))
+            .do_update()
+            .set(shop_type_translations::name.eq(&translation.name))
+            .returning(ShopTypeTranslation::as_returning())
+            .get_result(conn)
+    }).await?;
+    Ok(translation)
+}
+
+/// Returns `false` if there was no translation to delete.
+pub async fn delete_translation(db: &FarmDB, shop_type_id: i32, language: String) -> DbResult<bool> {
+    let deleted = db.run(move |conn| {
+        diesel::delete(shop_type_translations::table)
+            .filter(shop_type_translations::shop_type_id.eq(shop_type_id))
+            .filter(shop_type_translations::language.eq(language))
+            .execute(conn)
+    }).await?;
+    Ok(deleted > 0)
+}
@@ -0,0 +1,39 @@
+//! Figures about the whole system for sysadmins.
+
+use diesel::prelude::*;
+use crate::{DbResult, FarmDB};
+use crate::schema::{farm_owner_requests, farm_photos, farms, products, users};
+use crate::user::FarmOwnerStatus;
+
+pub struct SystemStats {
+    pub users: i64,
+    pub sysadmins: i64,
+    pub farm_owners: i64,
+    pub locked_users: i64,
+    /// Requests to become a farm owner waiting for a decision.
+    pub pending_farm_owner_requests: i64,
+    pub farms: i64,
+    pub products: i64,
+    pub photos: i64,
+}
+
+pub async fn system_stats(db: &FarmDB) -> DbResult<SystemStats> {
+    db.run(|conn| {
+        Ok(SystemStats {
+            users: users::table.count().get_result(conn)?,
+            sysadmins: users::table.filter(users::sysadmin.ne(0)).count().get_result(conn)?,
+            farm_owners: users::table
+                .filter(users::farmowner.eq(FarmOwnerStatus::YES))
+                .count()
+                .get_result(conn)?,
+            locked_users: users::table.filter(users::locked).count().get_result(conn)?,
+            pending_farm_owner_requests: farm_owner_requests::table
+                .filter(farm_owner_requests::status.eq(FarmOwnerStatus::REQUESTED))
+                .count()
+                .get_result(conn)?,
+            farms: farms::table.count().get_result(conn)?,
+            products: products::table.count().get_result(conn)?,
+            photos: farm_photos::table.count().get_result(conn)?,
+        })
+    }).await
+}
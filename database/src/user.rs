@@ -1,6 +1,9 @@
 use std::io::Write;
 use crate::farm::Farm;
-use crate::{DbResult, FarmDB};
+use crate::{DatabaseError, DbResult, FarmDB};
+use crate::farm_owner_request::decide_pending;
+use crate::page::Page;
+use crate::product::contains_pattern;
 use crate::schema::{farm_admins, users};
 use argon2::password_hash::rand_core::OsRng;
 use argon2::password_hash::SaltString;
@@ -19,6 +22,7 @@ pub enum FarmOwnerStatus {
     NO,
     YES,
     REQUESTED,
+    REJECTED,
 }
 
 impl ToSql<schema::sql_types::FarmAdminStatus, Pg> for FarmOwnerStatus {
@@ -27,6 +31,7 @@ impl ToSql<schema::sql_types::FarmAdminStatus, Pg> for FarmOwnerStatus {
             FarmOwnerStatus::NO => out.write_all(b"NO")?,
             FarmOwnerStatus::YES => out.write_all(b"YES")?,
             FarmOwnerStatus::REQUESTED => out.write_all(b"REQUESTED")?,
+            FarmOwnerStatus::REJECTED => out.write_all(b"REJECTED")?,
         }
         Ok(IsNull::No)
     }
@@ -38,6 +43,7 @@ impl FromSql<schema::sql_types::FarmAdminStatus, Pg> for FarmOwnerStatus {
             b"NO" => Ok(FarmOwnerStatus::NO),
             b"YES" => Ok(FarmOwnerStatus::YES),
             b"REQUESTED" => Ok(FarmOwnerStatus::REQUESTED),
+            b"REJECTED" => Ok(FarmOwnerStatus::REJECTED),
             _ => Err("Unrecognized enum variant".into()),
         }
     }
@@ -54,6 +60,8 @@ pub struct User {
     pub sysadmin: i32,
     pub farmowner: FarmOwnerStatus,
     pub ext_id: Uuid,
+    /// Locked users cannot log in.
+    pub locked: bool,
 }
 
 pub struct NewUser {
@@ -185,22 +193,102 @@ pub async fn by_username(db: &FarmDB, username: String) -> DbResult<Option<User>
     Ok(user)
 }
 
-async fn set_farmowner_status(db: &FarmDB, user_id: i32, status: FarmOwnerStatus) -> DbResult<()> {
+pub async fn by_ext_id(db: &FarmDB, ext_id: Uuid) -> DbResult<Option<User>> {
+    let user = db.run(move |conn| {
+        users::table
+            .select(User::as_select())
+            .filter(users::ext_id.eq(ext_id))
+            .first(conn)
+            .optional()
+    }).await?;
+    Ok(user)
+}
+
+/// Users whose username, name or email contains `text`, ignoring case, ordered by username and
+/// starting after the username `after`.
+pub async fn search_users(db: &FarmDB, text: Option<String>, after: Option<String>, limit: i64) -> DbResult<Page<User>> {
+    db.run(move |conn| {
+        let matching = || {
+            let mut query = users::table.into_boxed();
+            if let Some(text) = &text {
+                let pattern = contains_pattern(text);
+                query = query.filter(
+                    users::username.ilike(pattern.clone())
+                        .or(users::firstname.ilike(pattern.clone()))
+                        .or(users::lastname.ilike(pattern.clone()))
+                        .or(users::email.ilike(pattern)),
+                );
+            }
+            query
+        };
+        let total = matching().count().get_result(conn)?;
+        let mut query = matching().select(User::as_select()).order(users::username).limit(limit);
+        if let Some(after) = after {
+            query = query.filter(users::username.gt(after));
+        }
+        let rows = query.load(conn)?;
+        Ok(Page { rows, total })
+    }).await
+}
+
+pub(crate) fn set_farmowner_status(conn: &mut PgConnection, user_id: i32, status: FarmOwnerStatus) -> QueryResult<usize> {
+    diesel::update(users::table)
+        .filter(users::id.eq(user_id))
+        .set(users::farmowner.eq(status))
+        .execute(conn)
+}
+
+pub async fn make_farmowner(db: &FarmDB, user_id: i32) -> DbResult<()> {
+    db.run(move |conn| set_farmowner_status(conn, user_id, FarmOwnerStatus::YES)).await?;
+    Ok(())
+}
+
+/// Changes the roles given as `Some` in a single transaction. Setting the farm owner status
+/// directly decides a pending farm owner request of the user as well, approving it for `YES` and
+/// rejecting it otherwise.
+pub async fn set_roles(
+    db: &FarmDB,
+    user_id: i32,
+    sysadmin_id: i32,
+    sysadmin: Option<bool>,
+    farmowner: Option<FarmOwnerStatus>,
+) -> DbResult<()> {
+    db.run(move |conn| {
+        conn.transaction::<_, DatabaseError, _>(|conn| {
+            if let Some(sysadmin) = sysadmin {
+                diesel::update(users::table)
+                    .filter(users::id.eq(user_id))
+                    .set(users::sysadmin.eq(sysadmin as i32))
+                    .execute(conn)?;
+            }
+            if let Some(status) = farmowner {
+                let approve = status == FarmOwnerStatus::YES;
+                decide_pending(conn, user_id, sysadmin_id, approve)?;
+                set_farmowner_status(conn, user_id, status)?;
+            }
+            Ok(())
+        })
+    }).await
+}
+
+pub async fn set_sysadmin(db: &FarmDB, user_id: i32, sysadmin: bool) -> DbResult<()> {
     db.run(move |conn| {
         diesel::update(users::table)
             .filter(users::id.eq(user_id))
-            .set(users::farmowner.eq(status))
+            .set(users::sysadmin.eq(sysadmin as i32))
             .execute(conn)
     }).await?;
     Ok(())
 }
 
-pub async fn make_farmowner(db: &FarmDB, user_id: i32) -> DbResult<()> {
-    set_farmowner_status(db, user_id, FarmOwnerStatus::YES).await
-}
-
-pub async fn request_farm_admin_status(db: &FarmDB, user_id: i32) -> DbResult<()> {
-    set_farmowner_status(db, user_id, FarmOwnerStatus::REQUESTED).await
+pub async fn set_locked(db: &FarmDB, user_id: i32, locked: bool) -> DbResult<()> {
+    db.run(move |conn| {
+        diesel::update(users::table)
+            .filter(users::id.eq(user_id))
+            .set(users::locked.eq(locked))
+            .execute(conn)
+    }).await?;
+    Ok(())
 }
 
 pub async fn delete(db: &FarmDB, user_id: i32) -> DbResult<()> {
@@ -0,0 +1,109 @@
+//! Reader for the JSON output of the [Overpass API](https://wiki.openstreetmap.org/wiki/Overpass_API).
+//! Ways and relations are positioned at their `center` when queried with `out center`, otherwise
+//! ways at the mean of their nodes contained in the same response.
+
+use crate::tags::{OsmElement, is_farm};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::Read;
+
+#[derive(Deserialize)]
+struct Response {
+    elements: Vec<Element>,
+}
+
+#[derive(Deserialize)]
+struct Element {
+    r#type: String,
+    id: i64,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    center: Option<Center>,
+    #[serde(default)]
+    nodes: Vec<i64>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct Center {
+    lat: f64,
+    lon: f64,
+}
+
+/// Reads all elements tagged as farm.
+pub fn read(reader: impl Read) -> serde_json::Result<Vec<OsmElement>> {
+    let response: Response = serde_json::from_reader(reader)?;
+    let nodes: HashMap<i64, (f64, f64)> = response
+        .elements
+        .iter()
+        .filter(|e| e.r#type == "node")
+        .filter_map(|e| Some((e.id, (e.lat?, e.lon?))))
+        .collect();
+    let elements = response
+        .elements
+        .into_iter()
+        .filter(|e| is_farm(&e.tags))
+        .map(|e| {
+            let position = match (e.lat.zip(e.lon), e.center) {
+                (Some(position), _) => Some(position),
+                (None, Some(center)) => Some((center.lat, center.lon)),
+                (None, None) => centroid(&e.nodes, &nodes),
+            };
+            OsmElement {
+                osm_id: format!("{}/{}", e.r#type, e.id),
+                position,
+                tags: e.tags,
+            }
+        })
+        .collect();
+    Ok(elements)
+}
+
+/// Mean position of the nodes of a way. The closing node of an area is only counted once.
+pub fn centroid(refs: &[i64], nodes: &HashMap<i64, (f64, f64)>) -> Option<(f64, f64)> {
+    let refs = match refs {
+        [first, rest @ .., last] if first == last => &refs[..rest.len() + 1],
+        refs => refs,
+    };
+    let positions: Vec<(f64, f64)> = refs.iter().filter_map(|id| nodes.get(id).copied()).collect();
+    if positions.len() < refs.len() || positions.is_empty() {
+        return None;
+    }
+    let (lat, lon) = positions
+        .iter()
+        .fold((0.0, 0.0), |(lat, lon), (node_lat, node_lon)| (lat + node_lat, lon + node_lon));
+    Some((lat / positions.len() as f64, lon / positions.len() as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::overpass::read;
+
+    #[test]
+    fn read_overpass_json() {
+        let json = r#"{
+            "version": 0.6,
+            "elements": [
+                { "type": "node", "id": 1, "lat": 47.0, "lon": 8.0, "tags": { "shop": "farm", "name": "Hof" } },
+                { "type": "node", "id": 2, "lat": 47.0, "lon": 8.0, "tags": { "shop": "bakery" } },
+                { "type": "way", "id": 3, "center": { "lat": 46.5, "lon": 7.5 }, "tags": { "shop": "farm" } },
+                { "type": "way", "id": 4, "nodes": [10, 11, 12, 10], "tags": { "shop": "farm" } },
+                { "type": "way", "id": 5, "nodes": [10, 13], "tags": { "shop": "farm" } },
+                { "type": "node", "id": 10, "lat": 46.0, "lon": 7.0 },
+                { "type": "node", "id": 11, "lat": 46.0, "lon": 7.3 },
+                { "type": "node", "id": 12, "lat": 46.3, "lon": 7.0 }
+            ]
+        }"#;
+        let elements = read(json.as_bytes()).expect("valid Overpass JSON");
+        let ids: Vec<&str> = elements.iter().map(|e| e.osm_id.as_str()).collect();
+        assert_eq!(vec!["node/1", "way/3", "way/4", "way/5"], ids);
+        assert_eq!(Some((47.0, 8.0)), elements[0].position);
+        assert_eq!(Some("Hof"), elements[0].tags.get("name").map(String::as_str));
+        assert_eq!(Some((46.5, 7.5)), elements[1].position);
+        let (lat, lon) = elements[2].position.expect("centroid of the way");
+        assert!((lat - 46.1).abs() < 1e-9 && (lon - 7.1).abs() < 1e-9);
+        // node 13 is missing
+        assert_eq!(None, elements[3].position);
+    }
+}
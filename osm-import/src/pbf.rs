@@ -0,0 +1,356 @@
+//! Reader for the [OSM PBF format](https://wiki.openstreetmap.org/wiki/PBF_Format). Only
+//! uncompressed and zlib compressed blobs are supported, which covers the extracts of the usual
+//! download services.
+//!
+//! Farms mapped as ways are positioned at the mean of their nodes, which takes a second pass over
+//! the file once the ways are known.
+
+use crate::overpass::centroid;
+use crate::tags::{OsmElement, is_farm};
+use flate2::read::ZlibDecoder;
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+use std::io::{self, ErrorKind, Read};
+
+/// Largest blob allowed by the specification.
+const MAX_BLOB_SIZE: usize = 32 * 1024 * 1024;
+
+struct Node {
+    id: i64,
+    lat: f64,
+    lon: f64,
+    tags: HashMap<String, String>,
+}
+
+/// Reads all elements tagged as farm. `open` is called once per pass to read the file from the
+/// start.
+pub fn read<R: Read>(mut open: impl FnMut() -> io::Result<R>) -> io::Result<Vec<OsmElement>> {
+    let mut elements = vec![];
+    let mut ways = vec![];
+    for_each_block(open()?, |block| {
+        for node in nodes(&block) {
+            if is_farm(&node.tags) {
+                elements.push(OsmElement {
+                    osm_id: format!("node/{}", node.id),
+                    position: Some((node.lat, node.lon)),
+                    tags: node.tags,
+                });
+            }
+        }
+        for way in block.primitivegroup.iter().flat_map(|g| &g.ways) {
+            let tags = tags(&block, &way.keys, &way.vals);
+            if is_farm(&tags) {
+                let refs: Vec<i64> = way
+                    .refs
+                    .iter()
+                    .scan(0, |id, delta| {
+                        *id += delta;
+                        Some(*id)
+                    })
+                    .collect();
+                ways.push((way.id, refs, tags));
+            }
+        }
+    })?;
+
+    if !ways.is_empty() {
+        let wanted: HashSet<i64> = ways.iter().flat_map(|(_, refs, _)| refs).copied().collect();
+        let mut positions = HashMap::new();
+        for_each_block(open()?, |block| {
+            for node in nodes(&block) {
+                if wanted.contains(&node.id) {
+                    positions.insert(node.id, (node.lat, node.lon));
+                }
+            }
+        })?;
+        for (id, refs, tags) in ways {
+            elements.push(OsmElement {
+                osm_id: format!("way/{id}"),
+                position: centroid(&refs, &positions),
+                tags,
+            });
+        }
+    }
+    Ok(elements)
+}
+
+fn invalid_data(error: impl ToString) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error.to_string())
+}
+
+fn read_message<M: Message + Default>(reader: &mut impl Read, size: usize) -> io::Result<M> {
+    if size > MAX_BLOB_SIZE {
+        return Err(invalid_data(format!("blob of {size} bytes exceeds the maximum size")));
+    }
+    let mut buffer = vec![0; size];
+    reader.read_exact(&mut buffer)?;
+    M::decode(buffer.as_slice()).map_err(invalid_data)
+}
+
+fn for_each_block(mut reader: impl Read, mut f: impl FnMut(PrimitiveBlock)) -> io::Result<()> {
+    loop {
+        let mut size = [0; 4];
+        match reader.read_exact(&mut size) {
+            Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+            result => result?,
+        }
+        let header: BlobHeader = read_message(&mut reader, u32::from_be_bytes(size) as usize)?;
+        let blob: Blob = read_message(&mut reader, header.datasize as usize)?;
+        if header.r#type != "OSMData" {
+            continue;
+        }
+        let data = match (blob.raw, blob.zlib_data) {
+            (Some(raw), _) => raw,
+            (None, Some(zlib_data)) => {
+                let mut data = Vec::with_capacity(blob.raw_size.unwrap_or_default() as usize);
+                ZlibDecoder::new(zlib_data.as_slice()).read_to_end(&mut data)?;
+                data
+            }
+            (None, None) => return Err(invalid_data("unsupported blob compression")),
+        };
+        f(PrimitiveBlock::decode(data.as_slice()).map_err(invalid_data)?);
+    }
+}
+
+fn string(block: &PrimitiveBlock, index: u32) -> String {
+    block
+        .stringtable
+        .s
+        .get(index as usize)
+        .map(|s| String::from_utf8_lossy(s).into_owned())
+        .unwrap_or_default()
+}
+
+fn tags(block: &PrimitiveBlock, keys: &[u32], vals: &[u32]) -> HashMap<String, String> {
+    keys.iter()
+        .zip(vals)
+        .map(|(k, v)| (string(block, *k), string(block, *v)))
+        .collect()
+}
+
+/// All nodes of the block, plain and dense ones.
+fn nodes(block: &PrimitiveBlock) -> Vec<Node> {
+    let granularity = block.granularity.unwrap_or(100) as f64;
+    let degrees = |offset: Option<i64>, value: i64| 1e-9 * (offset.unwrap_or_default() as f64 + granularity * value as f64);
+    let mut nodes = vec![];
+    for group in &block.primitivegroup {
+        for node in &group.nodes {
+            nodes.push(Node {
+                id: node.id,
+                lat: degrees(block.lat_offset, node.lat),
+                lon: degrees(block.lon_offset, node.lon),
+                tags: tags(block, &node.keys, &node.vals),
+            });
+        }
+        if let Some(dense) = &group.dense {
+            // keys and values of all nodes, every node terminated by a 0
+            let mut keys_vals = dense.keys_vals.iter();
+            let (mut id, mut lat, mut lon) = (0, 0, 0);
+            for ((id_delta, lat_delta), lon_delta) in dense.id.iter().zip(&dense.lat).zip(&dense.lon) {
+                id += id_delta;
+                lat += lat_delta;
+                lon += lon_delta;
+                let mut tags = HashMap::new();
+                while let Some(&key) = keys_vals.next()
+                    && key != 0
+                {
+                    let value = keys_vals.next().copied().unwrap_or_default();
+                    tags.insert(string(block, key as u32), string(block, value as u32));
+                }
+                nodes.push(Node {
+                    id,
+                    lat: degrees(block.lat_offset, lat),
+                    lon: degrees(block.lon_offset, lon),
+                    tags,
+                });
+            }
+        }
+    }
+    nodes
+}
+
+// Messages of the OSM PBF schema, https://github.com/openstreetmap/OSM-binary. Fields not needed
+// for the import are left out.
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BlobHeader {
+    #[prost(string, required, tag = "1")]
+    pub r#type: String,
+    #[prost(int32, required, tag = "3")]
+    pub datasize: i32,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Blob {
+    #[prost(bytes = "vec", optional, tag = "1")]
+    pub raw: Option<Vec<u8>>,
+    #[prost(int32, optional, tag = "2")]
+    pub raw_size: Option<i32>,
+    #[prost(bytes = "vec", optional, tag = "3")]
+    pub zlib_data: Option<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PrimitiveBlock {
+    #[prost(message, required, tag = "1")]
+    pub stringtable: StringTable,
+    #[prost(message, repeated, tag = "2")]
+    pub primitivegroup: Vec<PrimitiveGroup>,
+    #[prost(int32, optional, tag = "17")]
+    pub granularity: Option<i32>,
+    #[prost(int64, optional, tag = "19")]
+    pub lat_offset: Option<i64>,
+    #[prost(int64, optional, tag = "20")]
+    pub lon_offset: Option<i64>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct StringTable {
+    #[prost(bytes = "vec", repeated, tag = "1")]
+    pub s: Vec<Vec<u8>>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PrimitiveGroup {
+    #[prost(message, repeated, tag = "1")]
+    pub nodes: Vec<PbfNode>,
+    #[prost(message, optional, tag = "2")]
+    pub dense: Option<DenseNodes>,
+    #[prost(message, repeated, tag = "3")]
+    pub ways: Vec<Way>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct PbfNode {
+    #[prost(sint64, required, tag = "1")]
+    pub id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    pub vals: Vec<u32>,
+    #[prost(sint64, required, tag = "8")]
+    pub lat: i64,
+    #[prost(sint64, required, tag = "9")]
+    pub lon: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DenseNodes {
+    #[prost(sint64, repeated, packed = "true", tag = "1")]
+    pub id: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    pub lat: Vec<i64>,
+    #[prost(sint64, repeated, packed = "true", tag = "9")]
+    pub lon: Vec<i64>,
+    #[prost(int32, repeated, packed = "true", tag = "10")]
+    pub keys_vals: Vec<i32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Way {
+    #[prost(int64, required, tag = "1")]
+    pub id: i64,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub keys: Vec<u32>,
+    #[prost(uint32, repeated, packed = "true", tag = "3")]
+    pub vals: Vec<u32>,
+    #[prost(sint64, repeated, packed = "true", tag = "8")]
+    pub refs: Vec<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pbf::{Blob, BlobHeader, DenseNodes, PbfNode, PrimitiveBlock, PrimitiveGroup, StringTable, Way, read};
+    use flate2::Compression;
+    use flate2::write::ZlibEncoder;
+    use prost::Message;
+    use std::io::Write;
+
+    fn blob(r#type: &str, data: Vec<u8>, compress: bool) -> Vec<u8> {
+        let blob = if compress {
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(&data).expect("failed to compress");
+            Blob {
+                raw: None,
+                raw_size: Some(data.len() as i32),
+                zlib_data: Some(encoder.finish().expect("failed to compress")),
+            }
+        } else {
+            Blob {
+                raw: Some(data),
+                raw_size: None,
+                zlib_data: None,
+            }
+        }
+        .encode_to_vec();
+        let header = BlobHeader {
+            r#type: r#type.to_string(),
+            datasize: blob.len() as i32,
+        }
+        .encode_to_vec();
+        [(header.len() as u32).to_be_bytes().to_vec(), header, blob].concat()
+    }
+
+    #[test]
+    fn read_pbf() {
+        let strings = ["", "shop", "farm", "name", "Hof", "amenity", "vending_machine", "vending", "milk"];
+        let block = PrimitiveBlock {
+            stringtable: StringTable {
+                s: strings.iter().map(|s| s.as_bytes().to_vec()).collect(),
+            },
+            primitivegroup: vec![
+                PrimitiveGroup {
+                    nodes: vec![PbfNode {
+                        id: 1,
+                        keys: vec![1, 3],
+                        vals: vec![2, 4],
+                        lat: 470_000_000,
+                        lon: 80_000_000,
+                    }],
+                    dense: None,
+                    ways: vec![],
+                },
+                PrimitiveGroup {
+                    nodes: vec![],
+                    // nodes 10, 11 and 12, only 12 is tagged
+                    dense: Some(DenseNodes {
+                        id: vec![10, 1, 1],
+                        lat: vec![460_000_000, 0, 3_000_000],
+                        lon: vec![70_000_000, 3_000_000, -3_000_000],
+                        keys_vals: vec![0, 0, 5, 6, 7, 8, 0],
+                    }),
+                    ways: vec![],
+                },
+                PrimitiveGroup {
+                    nodes: vec![],
+                    dense: None,
+                    ways: vec![Way {
+                        id: 20,
+                        keys: vec![1],
+                        vals: vec![2],
+                        refs: vec![10, 1, 1, -2],
+                    }],
+                },
+            ],
+            granularity: None,
+            lat_offset: None,
+            lon_offset: None,
+        };
+        let file = [
+            blob("OSMHeader", vec![], false),
+            blob("OSMData", block.encode_to_vec(), true),
+        ]
+        .concat();
+        let elements = read(|| Ok(file.as_slice())).expect("valid PBF");
+        let ids: Vec<&str> = elements.iter().map(|e| e.osm_id.as_str()).collect();
+        assert_eq!(vec!["node/1", "node/12", "way/20"], ids);
+        let close = |(lat, lon): (f64, f64), expected: (f64, f64)| {
+            (lat - expected.0).abs() < 1e-9 && (lon - expected.1).abs() < 1e-9
+        };
+        assert!(close(elements[0].position.expect("position"), (47.0, 8.0)));
+        assert_eq!(Some("Hof"), elements[0].tags.get("name").map(String::as_str));
+        assert!(close(elements[1].position.expect("position"), (46.3, 7.0)));
+        assert_eq!(Some("milk"), elements[1].tags.get("vending").map(String::as_str));
+        assert!(close(elements[2].position.expect("position"), (46.1, 7.1)));
+    }
+}
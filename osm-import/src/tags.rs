@@ -0,0 +1,185 @@
+use database::contact::{is_valid_email, normalize_phone_number};
+use database::location::NewGeoLocation;
+use database::osm;
+use database::osm_farm::OsmFarm;
+use std::collections::HashMap;
+
+/// Values of `vending` for machines selling farm produce.
+const FARM_VENDING: [&str; 11] = [
+    "bread", "cheese", "eggs", "farm", "food", "fruit", "honey", "meat", "milk", "potatoes", "vegetables",
+];
+
+/// Tagged OSM element, positioned at the node or the centre of the way.
+#[derive(Debug, PartialEq)]
+pub struct OsmElement {
+    /// Element type and id, like `node/123`.
+    pub osm_id: String,
+    pub position: Option<(f64, f64)>,
+    pub tags: HashMap<String, String>,
+}
+
+/// Whether the tags describe a farm shop or a vending machine selling farm produce.
+pub fn is_farm(tags: &HashMap<String, String>) -> bool {
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+    tag("shop") == Some("farm")
+        || (tag("amenity") == Some("vending_machine")
+            && tag("vending").is_some_and(|v| v.split(';').any(|v| FARM_VENDING.contains(&v.trim()))))
+}
+
+fn shop_types(tags: &HashMap<String, String>) -> Vec<String> {
+    let tag = |key: &str| tags.get(key).map(String::as_str);
+    let mut shop_types = vec![];
+    if tag("shop") == Some("farm") {
+        shop_types.push("store");
+    }
+    if tag("amenity") == Some("vending_machine") {
+        shop_types.push("vending machine");
+    }
+    if tag("self_service") == Some("yes") {
+        shop_types.push("self-service");
+    }
+    shop_types.into_iter().map(str::to_string).collect()
+}
+
+fn address(tags: &HashMap<String, String>) -> Option<String> {
+    let join = |keys: [&str; 2]| {
+        let parts: Vec<&str> = keys.iter().filter_map(|k| tags.get(*k).map(String::as_str)).collect();
+        (!parts.is_empty()).then(|| parts.join(" "))
+    };
+    let lines: Vec<String> = [
+        join(["addr:street", "addr:housenumber"]),
+        join(["addr:postcode", "addr:city"]),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    (!lines.is_empty()).then(|| lines.join(", "))
+}
+
+/// Maps the element to a farm. Opening hours, emails and phone numbers that cannot be parsed are
+/// left out and reported as warnings next to the farm. Dates without a year in them are resolved
+/// against `year`.
+pub fn to_farm(element: OsmElement, year: i32) -> Result<(OsmFarm, Vec<String>), String> {
+    let tags = element.tags;
+    let Some((lat, lon)) = element.position else {
+        return Err("no position".to_string());
+    };
+    let Some(name) = tags.get("name").or(tags.get("operator")).map(|n| n.trim().to_string()) else {
+        return Err("neither `name` nor `operator` tag".to_string());
+    };
+    let mut warnings = vec![];
+    let schedule = match tags.get("opening_hours").map(|h| osm::parse(h, year)) {
+        Some(Ok(schedule)) => Some(schedule),
+        Some(Err(err)) => {
+            warnings.push(format!("opening hours left out, {err}"));
+            None
+        }
+        None => None,
+    };
+    let contact = |keys: [&str; 2]| {
+        keys.iter()
+            .find_map(|k| tags.get(*k))
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    let email = match contact(["email", "contact:email"]).map(|e| e.to_lowercase()) {
+        Some(email) if !is_valid_email(&email) => {
+            warnings.push(format!("email left out, `{email}` is not a valid address"));
+            None
+        }
+        email => email,
+    };
+    let phone = match contact(["phone", "contact:phone"]).map(|p| normalize_phone_number(&p)) {
+        Some(Ok(phone)) => Some(phone),
+        Some(Err(messages)) => {
+            warnings.push(format!("phone left out, {}", messages.join(", ")));
+            None
+        }
+        None => None,
+    };
+    let farm = OsmFarm {
+        osm_id: element.osm_id,
+        name,
+        location: NewGeoLocation { lat, lon },
+        shop_types: shop_types(&tags),
+        schedule,
+        email,
+        phone,
+        address: address(&tags),
+    };
+    Ok((farm, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tags::{OsmElement, is_farm, to_farm};
+    use std::collections::HashMap;
+
+    fn tags(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn farm_tags() {
+        assert!(is_farm(&tags(&[("shop", "farm")])));
+        assert!(is_farm(&tags(&[("amenity", "vending_machine"), ("vending", "drinks;milk")])));
+        assert!(!is_farm(&tags(&[("amenity", "vending_machine"), ("vending", "cigarettes")])));
+        assert!(!is_farm(&tags(&[("shop", "bakery")])));
+    }
+
+    #[test]
+    fn map_farm() {
+        let element = OsmElement {
+            osm_id: "node/42".to_string(),
+            position: Some((47.1, 8.2)),
+            tags: tags(&[
+                ("shop", "farm"),
+                ("self_service", "yes"),
+                ("name", "Hof Sonnenberg"),
+                ("opening_hours", "Mo-Fr 08:00-18:00; Sa 08:00-12:00"),
+                ("contact:phone", "+41 44 000 00 00"),
+                ("email", " Hof@Sonnenberg.ch "),
+                ("addr:street", "Bergstrasse"),
+                ("addr:housenumber", "3"),
+                ("addr:postcode", "8000"),
+                ("addr:city", "Zürich"),
+            ]),
+        };
+        let (farm, warnings) = to_farm(element, 2026).expect("valid farm");
+        assert!(warnings.is_empty());
+        assert_eq!("node/42", farm.osm_id);
+        assert_eq!("Hof Sonnenberg", farm.name);
+        assert_eq!(vec!["store", "self-service"], farm.shop_types);
+        assert_eq!(6, farm.schedule.expect("opening hours").weekly.len());
+        assert_eq!(Some("+41440000000".to_string()), farm.phone);
+        assert_eq!(Some("hof@sonnenberg.ch".to_string()), farm.email);
+        assert_eq!(Some("Bergstrasse 3, 8000 Zürich".to_string()), farm.address);
+
+        let element = OsmElement {
+            osm_id: "way/7".to_string(),
+            position: Some((47.1, 8.2)),
+            tags: tags(&[
+                ("amenity", "vending_machine"),
+                ("vending", "milk"),
+                ("operator", "Milchhof"),
+                ("opening_hours", "sometimes"),
+                ("phone", "044 000 00 00"),
+                ("contact:email", "milchhof"),
+            ]),
+        };
+        let (farm, warnings) = to_farm(element, 2026).expect("valid farm");
+        assert_eq!("Milchhof", farm.name);
+        assert_eq!(vec!["vending machine"], farm.shop_types);
+        assert!(farm.schedule.is_none());
+        assert_eq!(None, farm.phone);
+        assert_eq!(None, farm.email);
+        assert_eq!(3, warnings.len());
+
+        let element = OsmElement {
+            osm_id: "way/8".to_string(),
+            position: None,
+            tags: tags(&[("shop", "farm"), ("name", "Hof")]),
+        };
+        assert!(to_farm(element, 2026).is_err());
+    }
+}
@@ -93,7 +93,7 @@ if [[ -z $STORAGE ]]; then
     -p $PORT:5432 \
     $DAEMON_FLAG \
     $TEST_FLAG \
-    postgres \
+    postgis/postgis:17-3.5-alpine \
     postgres -N $MAX_CONNECTIONS
 else
   docker run --name "$NAME" \
@@ -104,6 +104,6 @@ else
     -v $STORAGE:/var/lib/postgresql/data \
     $DAEMON_FLAG \
     $TEST_FLAG \
-    postgres \
+    postgis/postgis:17-3.5-alpine \
     postgres -N $MAX_CONNECTIONS
 fi
@@ -1,6 +1,22 @@
 use rocket::{Build, Rocket};
 
+mod admin;
+mod api_keys;
+mod autocomplete;
+mod contact;
+mod csv;
+mod farm_owner_requests;
 mod farms;
+mod geojson;
+mod locations;
+mod opening_hours;
+mod photos;
+mod pagination;
+mod products;
+mod search;
+mod shop_types;
+mod stock;
+mod tiles;
 mod users;
 pub mod ident;
 pub mod error;
@@ -8,8 +24,27 @@ mod types;
 
 pub fn mount(rocket: Rocket<Build>) -> Rocket<Build> {
     rocket
+        .manage(stock::StockEvents::default())
+        .manage(crate::photos::storage_from_env())
         .mount("/api/v1/farms", farms::routes())
+        .mount("/api/v1/farms", search::routes())
+        .mount("/api/v1/farms", opening_hours::routes())
+        .mount("/api/v1/farms", contact::routes())
+        .mount("/api/v1/farms", locations::routes())
+        .mount("/api/v1/farms", products::routes())
+        .mount("/api/v1/farms", stock::routes())
+        .mount("/api/v1/farms", api_keys::routes())
+        .mount("/api/v1/farms", photos::routes())
+        .mount("/api/v1/photos", photos::file_routes())
+        .mount("/api/v1/product_categories", products::category_routes())
+        .mount("/api/v1", geojson::routes())
+        .mount("/api/v1", csv::routes())
+        .mount("/api/v1/shop_types", shop_types::routes())
+        .mount("/api/v1/autocomplete", autocomplete::routes())
+        .mount("/api/v1/tiles", tiles::routes())
         .mount("/api/v1/users", users::routes())
+        .mount("/api/v1/farm_owner_requests", farm_owner_requests::routes())
+        .mount("/api/v1/admin", admin::routes())
         .mount("/api/v1/ident", ident::routes())
 }
 
@@ -91,7 +126,6 @@ pub mod test_utils {
             .dispatch()
             .await;
         assert_eq!(response.status(), Status::Ok);
-        let user = response.into_json::<ApiUser>().await.expect("failed to deserialize user");
-        user
+        response.into_json::<ApiUser>().await.expect("failed to deserialize user")
     }
 }
\ No newline at end of file
@@ -0,0 +1,404 @@
+//! Administration of all users and farms, only for sysadmins.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::farms::{ApiFarmPatch, FullApiFarm};
+use crate::api::v1::ident::SysAdmin;
+use crate::api::v1::opening_hours::requested_instant;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::photos::delete_photo_files;
+use crate::api::v1::types::ExtId;
+use crate::api::v1::users::{ApiFarmOwnerStatus, ApiUser};
+use crate::photos::PhotoStorage;
+use database::FarmDB;
+use database::stats::SystemStats;
+use database::user::{self, User};
+use rocket::serde::json::Json;
+use rocket::{State, delete, get, patch, post, put};
+use serde::{Deserialize, Serialize};
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        list_users,
+        lock_user,
+        unlock_user,
+        change_roles,
+        update_farm,
+        delete_farm,
+        system_stats,
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiAdminUser {
+    #[serde(flatten)]
+    pub user: ApiUser,
+    pub sysadmin: bool,
+    pub locked: bool,
+}
+
+impl From<User> for ApiAdminUser {
+    fn from(value: User) -> Self {
+        Self {
+            sysadmin: value.sysadmin != 0,
+            locked: value.locked,
+            user: value.into(),
+        }
+    }
+}
+
+/// Roles to change, unchanged if not given.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ApiRoles {
+    #[serde(default)]
+    pub sysadmin: Option<bool>,
+    /// `Yes` or `No`, requests are decided on `/farm_owner_requests`.
+    #[serde(default)]
+    pub farmowner: Option<ApiFarmOwnerStatus>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiSystemStats {
+    pub users: i64,
+    pub sysadmins: i64,
+    pub farm_owners: i64,
+    pub locked_users: i64,
+    pub pending_farm_owner_requests: i64,
+    pub farms: i64,
+    pub products: i64,
+    pub photos: i64,
+}
+
+impl From<SystemStats> for ApiSystemStats {
+    fn from(value: SystemStats) -> Self {
+        Self {
+            users: value.users,
+            sysadmins: value.sysadmins,
+            farm_owners: value.farm_owners,
+            locked_users: value.locked_users,
+            pending_farm_owner_requests: value.pending_farm_owner_requests,
+            farms: value.farms,
+            products: value.products,
+            photos: value.photos,
+        }
+    }
+}
+
+async fn user_from_ext_id(db: &FarmDB, user_id: ExtId) -> ApiResult<User> {
+    user::by_ext_id(db, user_id.0).await?.ok_or(ApiError::NotFound)
+}
+
+/// The user as changed by an admin action.
+async fn changed_user(db: &FarmDB, user: User) -> ApiResult<Json<ApiAdminUser>> {
+    let user = user::by_ext_id(db, user.ext_id).await?.ok_or(ApiError::NotFound)?;
+    Ok(Json(user.into()))
+}
+
+/// Users whose username, name or email contains `q`, ignoring case, ordered by username.
+#[get("/users?<q>&<page..>")]
+async fn list_users(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    q: Option<String>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiAdminUser>>> {
+    let request = page.validate(&["username"])?;
+    let text = q.map(|q| q.trim().to_string()).filter(|q| !q.is_empty());
+    let users = user::search_users(&db, text, request.after()?, request.fetch_limit()).await?;
+    Ok(Json(ApiPage::new(&request, users, |u| u.username.clone()).map(From::from)))
+}
+
+/// Keeps the user from logging in. Tokens already given out stop working as well.
+#[post("/users/<user_id>/lock")]
+async fn lock_user(db: FarmDB, sysadmin: SysAdmin, user_id: ExtId) -> ApiResult<Json<ApiAdminUser>> {
+    let user = user_from_ext_id(&db, user_id).await?;
+    if user.id == sysadmin.0.id {
+        return Err(ValidationApiError::for_field("locked", "Cannot lock yourself").into());
+    }
+    user::set_locked(&db, user.id, true).await?;
+    changed_user(&db, user).await
+}
+
+#[post("/users/<user_id>/unlock")]
+async fn unlock_user(db: FarmDB, _sysadmin: SysAdmin, user_id: ExtId) -> ApiResult<Json<ApiAdminUser>> {
+    let user = user_from_ext_id(&db, user_id).await?;
+    user::set_locked(&db, user.id, false).await?;
+    changed_user(&db, user).await
+}
+
+#[put("/users/<user_id>/roles", data = "<roles>")]
+async fn change_roles(
+    db: FarmDB,
+    sysadmin: SysAdmin,
+    user_id: ExtId,
+    roles: Json<ApiRoles>,
+) -> ApiResult<Json<ApiAdminUser>> {
+    let user = user_from_ext_id(&db, user_id).await?;
+    let roles = roles.into_inner();
+    if user.id == sysadmin.0.id && roles.sysadmin == Some(false) {
+        return Err(ValidationApiError::for_field("sysadmin", "Cannot revoke your own sysadmin role").into());
+    }
+    if let Some(ApiFarmOwnerStatus::Requested | ApiFarmOwnerStatus::Rejected) = roles.farmowner {
+        return Err(ValidationApiError::for_field("farmowner", "Expected Yes or No").into());
+    }
+    user::set_roles(&db, user.id, sysadmin.0.id, roles.sysadmin, roles.farmowner.map(Into::into)).await?;
+    changed_user(&db, user).await
+}
+
+/// Changes any farm like its admins can.
+#[patch("/farms/<farm_id>", data = "<patch>")]
+async fn update_farm(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    farm_id: ExtId,
+    patch: Json<ApiFarmPatch>,
+) -> ApiResult<Json<FullApiFarm>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let patch = patch.into_inner();
+    let known_shop_types = database::farm::assignable_shop_types(&db, farm_id).await?;
+    patch.validate(&known_shop_types)?;
+    database::farm::update_any_farm(&db, farm_id, patch.into_update(farm_id, &known_shop_types)).await?;
+    database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .map(|farm| Json(FullApiFarm::new(farm, requested_instant(None, None))))
+        .ok_or(ApiError::NotFound)
+}
+
+#[delete("/farms/<farm_id>")]
+async fn delete_farm(
+    db: FarmDB,
+    storage: &State<Box<dyn PhotoStorage>>,
+    _sysadmin: SysAdmin,
+    farm_id: ExtId,
+) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let photos = database::photo::list_photos(&db, farm_id).await?;
+    if !database::farm::delete_any_farm(&db, farm_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    for photo in &photos {
+        delete_photo_files(storage.inner().as_ref(), photo).await?;
+    }
+    Ok(())
+}
+
+#[get("/stats")]
+async fn system_stats(db: FarmDB, _sysadmin: SysAdmin) -> ApiResult<Json<ApiSystemStats>> {
+    Ok(Json(database::stats::system_stats(&db).await?.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::admin::{ApiAdminUser, ApiRoles, ApiSystemStats};
+    use crate::api::v1::farm_owner_requests::{ApiFarmOwnerRequest, NewApiFarmOwnerRequest};
+    use crate::api::v1::farms::{ApiFarm, ApiFarmPatch, FullApiFarm, NewApiFarm};
+    use crate::api::v1::ident::LoginCredentials;
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use crate::api::v1::users::ApiFarmOwnerStatus;
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE;
+    use database::user::{make_farmowner, set_sysadmin};
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    async fn users_page(client: &Client, token: &str, query: &str) -> ApiPage<ApiAdminUser> {
+        let response = client.get(format!("/api/v1/admin/users?{query}")).auth(token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{query}");
+        response
+            .into_json::<ApiPage<ApiAdminUser>>()
+            .await
+            .expect("failed to deserialize users")
+    }
+
+    async fn stats(client: &Client, token: &str) -> ApiSystemStats {
+        let response = client.get("/api/v1/admin/stats").auth(token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        response
+            .into_json::<ApiSystemStats>()
+            .await
+            .expect("failed to deserialize stats")
+    }
+
+    async fn change_roles(client: &Client, token: &str, user_id: &str, roles: ApiRoles) -> (Status, Option<ApiAdminUser>) {
+        let response = client
+            .put(format!("/api/v1/admin/users/{user_id}/roles"))
+            .body(serde_json::to_string(&roles).expect("failed to serialize roles"))
+            .auth(token)
+            .dispatch()
+            .await;
+        (response.status(), response.into_json::<ApiAdminUser>().await)
+    }
+
+    async fn login_status(client: &Client, username: &str, password: &str) -> Status {
+        let credentials = LoginCredentials {
+            identity: username.to_string(),
+            password: password.to_string(),
+        };
+        client
+            .post("/login-jwt")
+            .body(serde_json::to_string(&credentials).expect("failed to serialize login credentials"))
+            .dispatch()
+            .await
+            .status()
+    }
+
+    #[tokio::test]
+    async fn admin_actions() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        let admin = create_test_user(&client, "admin_api_admin", password).await;
+        set_sysadmin(&db, admin.id, true)
+            .await
+            .expect("failed to make user a sysadmin");
+        let admin_token = login_user(&client, &admin.username, password).await;
+        let admin_id = URL_SAFE.encode(admin.ext_id);
+        let target = create_test_user(&client, "admin_api_user", password).await;
+        make_farmowner(&db, target.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &target.username, password).await;
+        let target_id = URL_SAFE.encode(target.ext_id);
+
+        // only sysadmins, others are forwarded to the web app or forbidden
+        let response = client.get("/api/v1/admin/stats").auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.post(format!("/api/v1/admin/users/{admin_id}/lock")).auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        // list and search users
+        let first = users_page(&client, &admin_token, "q=ADMIN_API&limit=1").await;
+        assert_eq!(2, first.total_estimate);
+        assert_eq!("admin_api_admin", first.items[0].user.username);
+        assert!(first.items[0].sysadmin);
+        let cursor = first.next_cursor.expect("expected a second page");
+        let second = users_page(&client, &admin_token, &format!("q=admin_api&limit=1&cursor={cursor}")).await;
+        assert_eq!("admin_api_user", second.items[0].user.username);
+        assert!(!second.items[0].sysadmin);
+        assert_eq!(None, second.next_cursor);
+        let by_email = users_page(&client, &admin_token, "q=admin_api_user@test").await;
+        assert_eq!(1, by_email.items.len());
+        let before = stats(&client, &admin_token).await;
+        assert!(before.users >= 2);
+        assert!(before.sysadmins >= 1);
+        assert!(before.farm_owners >= 1);
+
+        // lock and unlock
+        let response = client.post(format!("/api/v1/admin/users/{target_id}/lock")).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let locked = response.into_json::<ApiAdminUser>().await.expect("failed to deserialize user");
+        assert!(locked.locked);
+        assert_eq!(login_status(&client, &target.username, password).await, Status::Forbidden);
+        let response = client.get("/api/v1/users/current-user").auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(before.locked_users + 1, stats(&client, &admin_token).await.locked_users);
+        let response = client.post(format!("/api/v1/admin/users/{admin_id}/lock")).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.post(format!("/api/v1/admin/users/{target_id}/unlock")).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(login_status(&client, &target.username, password).await, Status::Ok);
+        let unknown = URL_SAFE.encode(uuid::Uuid::new_v4());
+        let response = client.post(format!("/api/v1/admin/users/{unknown}/lock")).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        // edit and delete any farm
+        let new_farm = NewApiFarm { name: "Admin Api Farm".to_string(), lat: -42.0, lon: 171.0 };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let patch = ApiFarmPatch {
+            name: Some("Renamed Admin Api Farm".to_string()),
+            ..Default::default()
+        };
+        let response = client
+            .patch(format!("/api/v1/admin/farms/{}", farm.id))
+            .body(serde_json::to_string(&patch).expect("failed to serialize patch"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let patched = response.into_json::<FullApiFarm>().await.expect("failed to deserialize farm");
+        assert_eq!("Renamed Admin Api Farm", patched.name);
+        let patch = ApiFarmPatch {
+            name: Some(String::new()),
+            ..Default::default()
+        };
+        let response = client
+            .patch(format!("/api/v1/admin/farms/{}", farm.id))
+            .body(serde_json::to_string(&patch).expect("failed to serialize patch"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.delete(format!("/api/v1/admin/farms/{}", farm.id)).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get(format!("/api/v1/farms/{}", farm.id)).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let response = client.delete(format!("/api/v1/admin/farms/{}", farm.id)).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        // change roles
+        let roles = ApiRoles { sysadmin: Some(true), farmowner: Some(ApiFarmOwnerStatus::No) };
+        let (status, changed) = change_roles(&client, &admin_token, &target_id, roles).await;
+        assert_eq!(status, Status::Ok);
+        let changed = changed.expect("expected changed user");
+        assert!(changed.sysadmin);
+        assert_eq!(ApiFarmOwnerStatus::No, changed.user.farmowner);
+        let roles = ApiRoles { farmowner: Some(ApiFarmOwnerStatus::Requested), ..Default::default() };
+        let (status, _) = change_roles(&client, &admin_token, &target_id, roles).await;
+        assert_eq!(status, Status::BadRequest);
+        let roles = ApiRoles { sysadmin: Some(false), ..Default::default() };
+        let (status, _) = change_roles(&client, &admin_token, &admin_id, roles).await;
+        assert_eq!(status, Status::BadRequest);
+
+        // setting the status directly decides a pending request
+        let request = NewApiFarmOwnerRequest {
+            motivation: "I run a small dairy farm".to_string(),
+            evidence: None,
+        };
+        let response = client
+            .post("/api/v1/users/request-admin")
+            .body(serde_json::to_string(&request).expect("failed to serialize request"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let roles = ApiRoles { sysadmin: Some(false), farmowner: Some(ApiFarmOwnerStatus::Yes) };
+        let (status, changed) = change_roles(&client, &admin_token, &target_id, roles).await;
+        assert_eq!(status, Status::Ok);
+        let changed = changed.expect("expected changed user");
+        assert!(!changed.sysadmin);
+        assert_eq!(ApiFarmOwnerStatus::Yes, changed.user.farmowner);
+        let request = client
+            .get("/api/v1/users/request-admin")
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarmOwnerRequest>()
+            .await
+            .expect("failed to deserialize request");
+        assert_eq!(ApiFarmOwnerStatus::Yes, request.status);
+        assert!(request.decided.is_some());
+
+        user::delete(&db, target.id)
+            .await
+            .expect("failed to delete user");
+        user::delete(&db, admin.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -0,0 +1,115 @@
+//! API keys of a farm, for devices like vending machines reporting their stock. The key itself is
+//! only returned once on creation.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::types::ExtId;
+use crate::validation::{StringLengthCriteria, StringValidator, Validator};
+use chrono::{DateTime, Utc};
+use database::FarmDB;
+use database::api_key::FarmApiKey;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list_api_keys, create_api_key, delete_api_key]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmApiKey {
+    pub id: i32,
+    pub name: String,
+    pub created: DateTime<Utc>,
+    /// Value for the `X-Api-Key` header, only set on creation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl From<FarmApiKey> for ApiFarmApiKey {
+    fn from(value: FarmApiKey) -> Self {
+        Self {
+            id: value.id,
+            name: value.name,
+            created: value.created,
+            key: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewApiFarmApiKey {
+    /// Tells keys apart, like `vending machine at the road`.
+    pub name: String,
+}
+
+impl NewApiFarmApiKey {
+    fn validate(&self) -> Result<(), ValidationApiError> {
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::new(1, 50));
+        match validator.validate(self.name.trim()) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(ValidationApiError::for_fields(HashMap::from([(
+                "name".to_string(),
+                err.messages,
+            )]))),
+        }
+    }
+}
+
+/// Keys of the farm in the order they were created.
+#[get("/<farm_id>/api_keys?<page..>")]
+async fn list_api_keys(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiFarmApiKey>>> {
+    let request = page.validate(&["created"])?;
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    let keys = database::api_key::list_api_keys(&db, farm_id).await?;
+    Ok(Json(ApiPage::of_sorted(&request, keys, |k| k.id)?.map(From::from)))
+}
+
+#[post("/<farm_id>/api_keys", data = "<key>")]
+async fn create_api_key(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    key: Json<NewApiFarmApiKey>,
+) -> ApiResult<Json<ApiFarmApiKey>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    key.validate()?;
+    let name = key.into_inner().name.trim().to_string();
+    let (key, plain) = database::api_key::create_api_key(&db, farm_owner.0.id, farm_id, name)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+    Ok(Json(ApiFarmApiKey {
+        key: Some(plain),
+        ..key.into()
+    }))
+}
+
+#[delete("/<farm_id>/api_keys/<key_id>")]
+async fn delete_api_key(db: FarmDB, farm_id: ExtId, key_id: i32, farm_owner: FarmOwner) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    if !database::api_key::delete_api_key(&db, farm_owner.0.id, farm_id, key_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
@@ -0,0 +1,311 @@
+//! Suggestions while typing into the search field, mixing shop types, farms and products whose
+//! names start with the typed text.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use database::FarmDB;
+use database::farm::Farm;
+use database::search::{ShopTypeSuggestion, Suggestions};
+use rocket::get;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Suggestions returned when no `limit` is given.
+const DEFAULT_LIMIT: usize = 10;
+/// Most suggestions returned at once.
+const MAX_LIMIT: usize = 50;
+/// Longest text completed.
+const MAX_PREFIX_LENGTH: usize = 100;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![autocomplete]
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiSuggestionType {
+    Farm,
+    Product,
+    ShopType,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiSuggestion {
+    #[serde(rename = "type")]
+    pub suggestion_type: ApiSuggestionType,
+    /// Text to show, for shop types translated into the requested language if possible.
+    pub text: String,
+    /// `ExtId` of the farm, for products of the farm selling it. Not set for shop types.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Name of the farm selling the product.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub farm: Option<String>,
+    /// Name of the shop type, as taken by the `shop_type` filters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shop_type: Option<String>,
+}
+
+impl From<Farm> for ApiSuggestion {
+    fn from(value: Farm) -> Self {
+        Self {
+            suggestion_type: ApiSuggestionType::Farm,
+            text: value.name,
+            id: Some(URL_SAFE.encode(value.ext_id)),
+            farm: None,
+            shop_type: None,
+        }
+    }
+}
+
+impl From<(String, Farm)> for ApiSuggestion {
+    fn from((product, farm): (String, Farm)) -> Self {
+        Self {
+            suggestion_type: ApiSuggestionType::Product,
+            text: product,
+            id: Some(URL_SAFE.encode(farm.ext_id)),
+            farm: Some(farm.name),
+            shop_type: None,
+        }
+    }
+}
+
+impl From<ShopTypeSuggestion> for ApiSuggestion {
+    fn from(value: ShopTypeSuggestion) -> Self {
+        Self {
+            suggestion_type: ApiSuggestionType::ShopType,
+            text: value.translation.unwrap_or_else(|| value.shop_type.name.clone()),
+            id: None,
+            farm: None,
+            shop_type: Some(value.shop_type.name),
+        }
+    }
+}
+
+/// At most `limit` suggestions, taken in turns from shop types, farms and products so a long list
+/// of one kind doesn't crowd out the others. Grouped by kind in that order.
+fn mix(suggestions: Suggestions, limit: usize) -> Vec<ApiSuggestion> {
+    let groups: [Vec<ApiSuggestion>; 3] = [
+        suggestions.shop_types.into_iter().map(From::from).collect(),
+        suggestions.farms.into_iter().map(From::from).collect(),
+        suggestions.products.into_iter().map(From::from).collect(),
+    ];
+    let mut taken = [0; 3];
+    let mut total = 0;
+    while total < limit {
+        let before = total;
+        for (group, taken) in groups.iter().zip(taken.iter_mut()) {
+            if total < limit && *taken < group.len() {
+                *taken += 1;
+                total += 1;
+            }
+        }
+        if total == before {
+            break;
+        }
+    }
+    groups
+        .into_iter()
+        .zip(taken)
+        .flat_map(|(group, taken)| group.into_iter().take(taken))
+        .collect()
+}
+
+/// Shop types, farms and products whose name starts with `q`, ignoring case. Shop types also match
+/// by their translation into `lang`.
+#[get("/?<q>&<lang>&<limit>")]
+async fn autocomplete(
+    db: FarmDB,
+    q: String,
+    lang: Option<String>,
+    limit: Option<usize>,
+) -> ApiResult<Json<Vec<ApiSuggestion>>> {
+    let prefix = q.trim_start().to_string();
+    let limit = limit.unwrap_or(DEFAULT_LIMIT);
+    let mut errors = HashMap::new();
+    if prefix.is_empty() || prefix.chars().count() > MAX_PREFIX_LENGTH {
+        errors.insert("q".to_string(), vec![format!("Expected 1 to {MAX_PREFIX_LENGTH} characters")]);
+    }
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        errors.insert("limit".to_string(), vec![format!("Expected a limit between 1 and {MAX_LIMIT}")]);
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::from(ValidationApiError::for_fields(errors)));
+    }
+    let suggestions = database::search::autocomplete(&db, prefix, lang, limit as i64).await?;
+    Ok(Json(mix(suggestions, limit)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::autocomplete::{ApiSuggestion, ApiSuggestionType};
+    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::products::NewApiProduct;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use diesel::RunQueryDsl;
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+    use std::time::{Duration, Instant};
+
+    async fn suggest(client: &Client, query: &str) -> Vec<ApiSuggestion> {
+        let response = client.get(format!("/api/v1/autocomplete?{query}")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{query}");
+        response
+            .into_json::<Vec<ApiSuggestion>>()
+            .await
+            .expect("failed to deserialize suggestions")
+    }
+
+    #[tokio::test]
+    async fn mixed_suggestions() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "mixed_suggestions", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let mut ext_ids = Vec::new();
+        for (name, lat) in [("Qwixel Meadow", -40.0), ("qwixel Barn", -40.5), ("Old Qwixel Farm", -41.0)] {
+            let new_farm = NewApiFarm { name: name.to_string(), lat, lon: -84.0 };
+            let farm = client
+                .post("/api/v1/farms")
+                .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiFarm>()
+                .await
+                .expect("failed to deserialize farm");
+            ext_ids.push(farm.id);
+        }
+        let product = NewApiProduct {
+            name: "Qwixelberries".to_string(),
+            category: Some("fruit".to_string()),
+            unit: "500 g".to_string(),
+            price: None,
+            currency: "EUR".to_string(),
+            organic: true,
+        };
+        let response = client
+            .post(format!("/api/v1/farms/{}/products", ext_ids[2]))
+            .body(serde_json::to_string(&product).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let suggestions = suggest(&client, "q=QWIXEL").await;
+        let texts: Vec<(ApiSuggestionType, &str)> =
+            suggestions.iter().map(|s| (s.suggestion_type, s.text.as_str())).collect();
+        assert_eq!(
+            vec![
+                (ApiSuggestionType::Farm, "qwixel Barn"),
+                (ApiSuggestionType::Farm, "Qwixel Meadow"),
+                (ApiSuggestionType::Product, "Qwixelberries"),
+            ],
+            texts
+        );
+        assert_eq!(Some(&ext_ids[1]), suggestions[0].id.as_ref());
+        assert_eq!(Some(&ext_ids[2]), suggestions[2].id.as_ref());
+        assert_eq!(Some("Old Qwixel Farm"), suggestions[2].farm.as_deref());
+
+        // every kind gets its turn
+        let suggestions = suggest(&client, "q=qwixel&limit=2").await;
+        let types: Vec<ApiSuggestionType> = suggestions.iter().map(|s| s.suggestion_type).collect();
+        assert_eq!(vec![ApiSuggestionType::Farm, ApiSuggestionType::Product], types);
+
+        let suggestions = suggest(&client, "q=qwixel_").await;
+        assert!(suggestions.is_empty());
+
+        let shop_types = suggest(&client, "q=sto").await;
+        assert!(shop_types.iter().any(|s| s.suggestion_type == ApiSuggestionType::ShopType
+            && s.shop_type.as_deref() == Some(&s.text)
+            && s.id.is_none()));
+
+        for query in ["q=", "q=%20", "q=qwixel&limit=0", "q=qwixel&limit=51"] {
+            let response = client.get(format!("/api/v1/autocomplete?{query}")).dispatch().await;
+            assert_eq!(response.status(), Status::BadRequest, "{query}");
+        }
+
+        for ext_id in ext_ids {
+            let response = client
+                .delete(format!("/api/v1/farms/{ext_id}"))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+
+    /// Seeds 100k farms with two products each and measures the response time of the endpoint.
+    /// Run with `cargo test autocomplete_benchmark -- --ignored --nocapture`.
+    #[tokio::test]
+    #[ignore = "benchmark, seeds 100k farms"]
+    async fn autocomplete_benchmark() {
+        let client = create_untracked_client().await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        let seeding = Instant::now();
+        db.run(|conn| {
+            diesel::sql_query(
+                "INSERT INTO farms (name) \
+                SELECT 'Bench ' || initcap(substr(md5(i::text), 1, 6)) || ' Farm' FROM generate_series(1, 100000) i",
+            )
+            .execute(conn)?;
+            diesel::sql_query(
+                "INSERT INTO products (farm_id, name, unit) \
+                SELECT f.id, p.name || ' ' || substr(md5(f.id::text), 1, 4), 'kg' \
+                FROM farms f CROSS JOIN (VALUES ('Apples'), ('Benchberries')) p(name) \
+                WHERE f.name LIKE 'Bench %'",
+            )
+            .execute(conn)?;
+            diesel::sql_query("ANALYZE farms, products").execute(conn)
+        })
+        .await
+        .expect("failed to seed farms");
+        println!("seeded 100k farms in {:?}", seeding.elapsed());
+
+        let prefixes = ["b", "be", "ben", "bench", "bench a", "bench 3f", "apples", "benchb", "x", "bench ff"];
+        let mut timings = Vec::new();
+        for round in 0..20 {
+            for prefix in prefixes {
+                let start = Instant::now();
+                let response = client
+                    .get(format!("/api/v1/autocomplete?q={}", prefix.replace(' ', "%20")))
+                    .dispatch()
+                    .await;
+                let status = response.status();
+                let body = response.into_string().await.unwrap_or_default();
+                // the first round warms up caches
+                if round > 0 {
+                    timings.push(start.elapsed());
+                }
+                assert_eq!(status, Status::Ok, "{body}");
+            }
+        }
+
+        db.run(|conn| diesel::sql_query("DELETE FROM farms WHERE name LIKE 'Bench %'").execute(conn))
+            .await
+            .expect("failed to delete seeded farms");
+
+        timings.sort();
+        let median = timings[timings.len() / 2];
+        let p95 = timings[timings.len() * 95 / 100];
+        println!("autocomplete over 100k farms: median {median:?}, p95 {p95:?}");
+        assert!(p95 < Duration::from_millis(10), "p95 of {p95:?}");
+    }
+}
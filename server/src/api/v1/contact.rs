@@ -0,0 +1,221 @@
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::types::ExtId;
+use crate::validation::{
+    EmailValidator, StringLengthCriteria, StringValidator, Validator, normalize_phone_number,
+};
+use database::FarmDB;
+use database::farm::{Contact, FarmUpdate, NewContact};
+use rocket::serde::json::Json;
+use rocket::{get, put};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_contact, replace_contact]
+}
+
+/// Contact details of a farm. Fields that are not public are left out for everyone but the farm
+/// admins.
+#[derive(Serialize, Deserialize)]
+pub struct ApiContact {
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub address: Option<String>,
+    pub email_public: bool,
+    pub phone_public: bool,
+    pub address_public: bool,
+}
+
+impl From<Contact> for ApiContact {
+    fn from(value: Contact) -> Self {
+        Self {
+            email: value.email,
+            phone: value.phone,
+            address: value.address,
+            email_public: value.email_public,
+            phone_public: value.phone_public,
+            address_public: value.address_public,
+        }
+    }
+}
+
+impl ApiContact {
+    pub fn public(value: Contact) -> Self {
+        Self {
+            email: value.email.filter(|_| value.email_public),
+            phone: value.phone.filter(|_| value.phone_public),
+            address: value.address.filter(|_| value.address_public),
+            email_public: value.email_public,
+            phone_public: value.phone_public,
+            address_public: value.address_public,
+        }
+    }
+}
+
+fn public_by_default() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewApiContact {
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default = "public_by_default")]
+    pub email_public: bool,
+    #[serde(default = "public_by_default")]
+    pub phone_public: bool,
+    #[serde(default = "public_by_default")]
+    pub address_public: bool,
+}
+
+impl Default for NewApiContact {
+    fn default() -> Self {
+        Self {
+            email: None,
+            phone: None,
+            address: None,
+            email_public: public_by_default(),
+            phone_public: public_by_default(),
+            address_public: public_by_default(),
+        }
+    }
+}
+
+impl NewApiContact {
+    pub fn sanitize(&mut self) {
+        let trimmed = |value: &mut Option<String>| {
+            *value = value
+                .take()
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty());
+        };
+        trimmed(&mut self.email);
+        trimmed(&mut self.phone);
+        trimmed(&mut self.address);
+        self.email = self.email.take().map(|email| email.to_lowercase());
+    }
+
+    /// Validates all fields and normalizes the phone number to E.164.
+    pub fn validate(&mut self) -> Result<(), ValidationApiError> {
+        let mut errors = HashMap::new();
+        if let Some(email) = &self.email
+            && let Err(err) = EmailValidator.validate(email)
+        {
+            errors.insert("email".to_string(), err.messages);
+        }
+        if let Some(phone) = &self.phone {
+            match normalize_phone_number(phone) {
+                Ok(normalized) => self.phone = Some(normalized),
+                Err(err) => {
+                    errors.insert("phone".to_string(), err.messages);
+                }
+            }
+        }
+        if let Some(address) = &self.address {
+            let mut validator = StringValidator::new();
+            validator.add_criteria(StringLengthCriteria::max(500));
+            if let Err(err) = validator.validate(address) {
+                errors.insert("address".to_string(), err.messages);
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationApiError::for_fields(errors))
+        }
+    }
+
+    pub fn into_new_contact(self, farm_id: i32) -> NewContact {
+        NewContact {
+            farm_id,
+            email: self.email,
+            phone: self.phone,
+            address: self.address,
+            email_public: self.email_public,
+            phone_public: self.phone_public,
+            address_public: self.address_public,
+        }
+    }
+}
+
+/// Full contact details including private fields. Only available to farm admins.
+#[get("/<farm_id>/contact")]
+async fn get_contact(db: FarmDB, farm_id: ExtId, farm_owner: FarmOwner) -> ApiResult<Json<ApiContact>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .and_then(|farm| farm.contact)
+        .map(|contact| Json(contact.into()))
+        .ok_or(ApiError::NotFound)
+}
+
+#[put("/<farm_id>/contact", data = "<contact>")]
+async fn replace_contact(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    contact: Json<NewApiContact>,
+) -> ApiResult<Json<ApiContact>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let mut contact = contact.into_inner();
+    contact.sanitize();
+    contact.validate()?;
+    let update = FarmUpdate {
+        contact: Some(contact.into_new_contact(farm_id)),
+        ..Default::default()
+    };
+    if !database::farm::update_farm(&db, farm_owner.0.id, farm_id, update).await? {
+        return Err(ApiError::Forbidden);
+    }
+    database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .and_then(|farm| farm.contact)
+        .map(|contact| Json(contact.into()))
+        .ok_or(ApiError::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::contact::NewApiContact;
+
+    #[test]
+    fn sanitize_and_validate_contact() {
+        let mut contact = NewApiContact {
+            email: Some(" Farm@Example.com ".to_string()),
+            phone: Some("0041 79 123 45 67".to_string()),
+            address: Some("   ".to_string()),
+            email_public: true,
+            phone_public: false,
+            address_public: true,
+        };
+        contact.sanitize();
+        assert!(contact.validate().is_ok());
+        assert_eq!(contact.email, Some("farm@example.com".to_string()));
+        assert_eq!(contact.phone, Some("+41791234567".to_string()));
+        assert_eq!(contact.address, None);
+
+        let mut contact = NewApiContact {
+            email: Some("no email".to_string()),
+            phone: Some("079 123 45 67".to_string()),
+            address: None,
+            email_public: true,
+            phone_public: true,
+            address_public: true,
+        };
+        contact.sanitize();
+        assert!(contact.validate().is_err());
+    }
+}
@@ -0,0 +1,374 @@
+//! Bulk import and export of farms as CSV with a header row and these columns:
+//!
+//! | Column          | Content                                                           |
+//! |-----------------|-------------------------------------------------------------------|
+//! | `id`            | Farm id, only filled by the export and ignored by the import      |
+//! | `name`          | Farm name                                                         |
+//! | `lat`, `lon`    | Position in decimal degrees                                       |
+//! | `shop_types`    | Shop type names separated by `;`, e.g. `store;self-service`       |
+//! | `opening_hours` | Opening hours in the OpenStreetMap `opening_hours` syntax         |
+//! | `email`         | Contact e-mail address                                            |
+//! | `phone`         | Contact phone number                                              |
+//! | `address`       | Postal address                                                    |
+//!
+//! All columns but `name`, `lat` and `lon` are optional and may be left empty or out. The export
+//! only contains public contact details.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::contact::NewApiContact;
+use crate::api::v1::error::ValidationError as ValidationApiError;
+use crate::api::v1::farms::{ApiFarmPatch, FullApiFarm, NewApiFarm};
+use crate::api::v1::ident::SysAdmin;
+use crate::api::v1::opening_hours::{requested_instant, validate_osm_schedule};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use chrono::{Datelike, Utc};
+use csv::StringRecord;
+use database::FarmDB;
+use database::farm::{FarmFilter, FarmUpdate, NewFarm, NewFarmEntry, ShopType};
+use database::location::NewGeoLocation;
+use rocket::data::{Data, ToByteUnit};
+use rocket::http::{ContentType, Status};
+use rocket::serde::json::Json;
+use rocket::{get, post};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![export_farms, import_farms]
+}
+
+/// Largest CSV file accepted by the import, in mebibytes.
+const MAX_CSV_MIB: u64 = 10;
+
+/// Row of the import and export.
+#[derive(Serialize, Deserialize)]
+pub struct CsvFarm {
+    #[serde(default)]
+    pub id: Option<String>,
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default)]
+    pub shop_types: Option<String>,
+    #[serde(default)]
+    pub opening_hours: Option<String>,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub phone: Option<String>,
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+/// Outcome of an import. Farms are only created if every row is valid and it is no dry run.
+#[derive(Serialize, Deserialize)]
+pub struct ApiCsvImportReport {
+    pub dry_run: bool,
+    /// Number of valid rows.
+    pub valid: usize,
+    /// Ids of the created farms in the order of the rows.
+    pub created: Vec<String>,
+    pub errors: Vec<ApiCsvRowError>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiCsvRowError {
+    /// Line of the row in the file, the header being line 1.
+    pub line: u64,
+    pub invalid_fields: HashMap<String, Vec<String>>,
+}
+
+/// Farms as CSV, optionally only those with the given shop type.
+#[get("/farms.csv?<shop_type>")]
+async fn export_farms(db: FarmDB, shop_type: Option<String>) -> ApiResult<(ContentType, String)> {
+    let at = requested_instant(None, None);
+    let mut writer = csv::Writer::from_writer(vec![]);
+    let farm_ids = database::farm::list_farms(&db, FarmFilter { shop_type, ..Default::default() })
+        .await?
+        .into_iter()
+        .map(|farm| farm.id)
+        .collect();
+    for full_farm in database::farm::load_full_farms(&db, farm_ids).await? {
+        let farm = FullApiFarm::new(full_farm, at);
+        let contact = farm.contact.as_ref();
+        let row = CsvFarm {
+            id: Some(farm.id),
+            name: farm.name,
+            lat: farm.lat,
+            lon: farm.lon,
+            shop_types: Some(farm.shop_types.join(";")),
+            opening_hours: Some(farm.osm_opening_hours),
+            email: contact.and_then(|c| c.email.clone()),
+            phone: contact.and_then(|c| c.phone.clone()),
+            address: contact.and_then(|c| c.address.clone()),
+        };
+        writer.serialize(row).expect("failed to write CSV row to memory");
+    }
+    let csv = writer.into_inner().expect("failed to flush CSV to memory");
+    Ok((ContentType::CSV, String::from_utf8(csv).expect("CSV of valid strings")))
+}
+
+/// Validates every row of the CSV and creates all farms in a single transaction if all rows are
+/// valid, administrated by the importing sysadmin. With `dry_run` only the validation is done.
+/// Dates without a year in the opening hours are resolved against the current year.
+#[post("/farms.csv?<dry_run>", data = "<data>")]
+async fn import_farms(
+    db: FarmDB,
+    sysadmin: SysAdmin,
+    dry_run: Option<bool>,
+    data: Data<'_>,
+) -> ApiResult<(Status, Json<ApiCsvImportReport>)> {
+    let dry_run = dry_run.unwrap_or(false);
+    let content = data
+        .open(MAX_CSV_MIB.mebibytes())
+        .into_string()
+        .await
+        .map_err(|err| ValidationApiError::for_field("file", err.to_string()))?;
+    if !content.is_complete() {
+        return Err(ValidationApiError::for_field("file", format!("Exceeds the maximum size of {MAX_CSV_MIB} MiB")).into());
+    }
+
+    let known_shop_types = database::shop_type::list_shop_types(&db, false).await?;
+    let year = Utc::now().year();
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|err| ValidationApiError::for_field("file", err.to_string()))?
+        .clone();
+    let mut entries = vec![];
+    let mut errors = vec![];
+    let mut record = StringRecord::new();
+    loop {
+        let line = reader.position().line();
+        match reader.read_record(&mut record) {
+            Ok(false) => break,
+            Ok(true) => {}
+            Err(err) => return Err(ValidationApiError::for_field("file", err.to_string()).into()),
+        }
+        let row = record
+            .deserialize::<CsvFarm>(Some(&headers))
+            .map_err(|err| ValidationApiError::for_field("row", err.to_string()).into_invalid_fields())
+            .and_then(|row| validate_row(row, &known_shop_types, year));
+        match row {
+            Ok(entry) => entries.push(entry),
+            Err(invalid_fields) => errors.push(ApiCsvRowError { line, invalid_fields }),
+        }
+    }
+
+    let mut report = ApiCsvImportReport {
+        dry_run,
+        valid: entries.len(),
+        created: vec![],
+        errors,
+    };
+    if !report.errors.is_empty() {
+        let status = if dry_run { Status::Ok } else { Status::UnprocessableEntity };
+        return Ok((status, Json(report)));
+    }
+    if !dry_run {
+        report.created = database::farm::create_farms(&db, &sysadmin.0, entries)
+            .await?
+            .into_iter()
+            .map(|farm| URL_SAFE.encode(farm.ext_id))
+            .collect();
+    }
+    Ok((Status::Ok, Json(report)))
+}
+
+/// Validates a row with the rules of the farm, shop type, opening hours and contact endpoints.
+fn validate_row(
+    row: CsvFarm,
+    known_shop_types: &[ShopType],
+    year: i32,
+) -> Result<NewFarmEntry, HashMap<String, Vec<String>>> {
+    let new_farm = NewApiFarm {
+        name: row.name,
+        lat: row.lat,
+        lon: row.lon,
+    };
+    let mut errors = new_farm
+        .validate()
+        .err()
+        .map(ValidationApiError::into_invalid_fields)
+        .unwrap_or_default();
+
+    let patch = ApiFarmPatch {
+        shop_types: Some(
+            row.shop_types
+                .iter()
+                .flat_map(|names| names.split(';'))
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect(),
+        ),
+        ..Default::default()
+    };
+    if let Err(err) = patch.validate(known_shop_types) {
+        errors.extend(err.into_invalid_fields());
+    }
+
+    let schedule = match row.opening_hours.map(|hours| database::osm::parse(&hours, year)) {
+        Some(Ok(schedule)) => {
+            if let Err(err) = validate_osm_schedule(&schedule) {
+                errors.extend(err.into_invalid_fields());
+            }
+            Some(schedule)
+        }
+        Some(Err(err)) => {
+            errors.insert("opening_hours".to_string(), vec![err.to_string()]);
+            None
+        }
+        None => None,
+    };
+
+    let mut contact = NewApiContact {
+        email: row.email,
+        phone: row.phone,
+        address: row.address,
+        ..Default::default()
+    };
+    contact.sanitize();
+    if let Err(err) = contact.validate() {
+        errors.extend(err.into_invalid_fields());
+    }
+
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    let has_contact = contact.email.is_some() || contact.phone.is_some() || contact.address.is_some();
+    // farm ids are assigned once the farms are created
+    let details = FarmUpdate {
+        opening_hours: schedule.as_ref().map(|s| s.opening_hours(0)),
+        opening_hours_exceptions: schedule.as_ref().map(|s| s.opening_hours_exceptions(0)),
+        contact: has_contact.then(|| contact.into_new_contact(0)),
+        ..patch.into_update(0, known_shop_types)
+    };
+    Ok(NewFarmEntry {
+        location: NewGeoLocation {
+            lat: new_farm.lat,
+            lon: new_farm.lon,
+        },
+        farm: NewFarm::from(new_farm),
+        details,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::csv::ApiCsvImportReport;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use base64::Engine;
+    use base64::engine::general_purpose::URL_SAFE;
+    use database::user::set_sysadmin;
+    use database::{FarmDB, farm, user};
+    use rocket::http::{ContentType, Status};
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn csv_import_export() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        let admin = create_test_user(&client, "csv_admin", password).await;
+        set_sysadmin(&db, admin.id, true)
+            .await
+            .expect("failed to make user a sysadmin");
+        let admin_token = login_user(&client, &admin.username, password).await;
+        let other = create_test_user(&client, "csv_other", password).await;
+        let other_token = login_user(&client, &other.username, password).await;
+
+        let valid_rows = "name,lat,lon,shop_types,opening_hours,email,phone,address\n\
+            F csv one,-54.44,3.35,store;self-service,Mo-Fr 08:00-18:00,one@example.com,,Farm road 1\n\
+            F csv two,-54.45,3.36,,,,,\n";
+        let invalid_rows = format!("{valid_rows}1 csv,-54.46,3.37,barn,Mo 25:00-26:00,not an email,,\nF csv four,north,3.38\n");
+        let import = |csv: String, token: String, dry_run: bool| {
+            let client = &client;
+            async move {
+                client
+                    .post(format!("/api/v1/farms.csv?dry_run={dry_run}"))
+                    .header(ContentType::CSV)
+                    .body(csv)
+                    .auth(&token)
+                    .dispatch()
+                    .await
+            }
+        };
+
+        let response = import(valid_rows.to_string(), other_token.clone(), false).await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let response = import(invalid_rows.clone(), admin_token.clone(), true).await;
+        assert_eq!(response.status(), Status::Ok);
+        let report = response
+            .into_json::<ApiCsvImportReport>()
+            .await
+            .expect("failed to deserialize report");
+        assert!(report.dry_run);
+        assert_eq!(2, report.valid);
+        assert!(report.created.is_empty());
+        assert_eq!(2, report.errors.len());
+        assert_eq!(4, report.errors[0].line);
+        let invalid_fields = &report.errors[0].invalid_fields;
+        for field in ["name", "shop_types", "opening_hours", "email"] {
+            assert!(invalid_fields.contains_key(field), "expected error for {field}");
+        }
+        assert_eq!(5, report.errors[1].line);
+        assert!(report.errors[1].invalid_fields.contains_key("row"));
+
+        // nothing is created if a single row is invalid
+        let response = import(invalid_rows, admin_token.clone(), false).await;
+        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let export = client
+            .get("/api/v1/farms.csv")
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .expect("expected CSV");
+        assert!(!export.contains("F csv one"));
+
+        let response = import(valid_rows.to_string(), admin_token.clone(), false).await;
+        assert_eq!(response.status(), Status::Ok);
+        let report = response
+            .into_json::<ApiCsvImportReport>()
+            .await
+            .expect("failed to deserialize report");
+        assert_eq!(2, report.created.len());
+
+        let response = client.get("/api/v1/farms.csv?shop_type=self-service").dispatch().await;
+        assert_eq!(Some(ContentType::CSV), response.content_type());
+        let export = response.into_string().await.expect("expected CSV");
+        let mut lines = export.lines();
+        assert_eq!(
+            Some("id,name,lat,lon,shop_types,opening_hours,email,phone,address"),
+            lines.next()
+        );
+        let row = format!(
+            "{},F csv one,-54.44,3.35,self-service;store,Mo-Fr 08:00-18:00,one@example.com,,Farm road 1",
+            report.created[0]
+        );
+        assert_eq!(vec![row.as_str()], lines.collect::<Vec<_>>());
+
+        for ext_id in report.created {
+            let ext_id = Uuid::from_slice(&URL_SAFE.decode(ext_id).expect("valid base64")).expect("valid uuid");
+            let farm_id = farm::id_from_ext_id(&db, ext_id)
+                .await
+                .expect("failed to look up farm")
+                .expect("farm exists");
+            assert!(farm::delete_farm(&db, admin.id, farm_id).await.expect("failed to delete farm"));
+        }
+        user::delete(&db, admin.id)
+            .await
+            .expect("failed to delete user");
+        user::delete(&db, other.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -6,6 +6,7 @@ use serde::Serialize;
 use std::collections::HashMap;
 use std::io::Cursor;
 use database::DatabaseError;
+use crate::photos::StorageError;
 
 #[derive(From)]
 pub enum ApiError {
@@ -14,6 +15,8 @@ pub enum ApiError {
     Validation(ValidationError),
     Base64Decode(base64::DecodeError),
     NotFound,
+    Forbidden,
+    Storage(StorageError),
 }
 
 impl<'r> Responder<'r, 'static> for ApiError {
@@ -29,12 +32,17 @@ impl<'r> Responder<'r, 'static> for ApiError {
                 let body = format!("Invalid base64: {}", error);
                 Response::build().status(Status::BadRequest).sized_body(body.len(), Cursor::new(body)).ok()
             },
-            ApiError::NotFound => Response::build().status(Status::NotFound).ok()
+            ApiError::NotFound => Response::build().status(Status::NotFound).ok(),
+            ApiError::Forbidden => Response::build().status(Status::Forbidden).ok(),
+            ApiError::Storage(error) => {
+                rocket::error!("Photo storage failed: {}", error.0);
+                Response::build().status(Status::InternalServerError).ok()
+            },
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Debug)]
 pub struct ValidationError {
     message: String,
     invalid_fields: HashMap<String, Vec<String>>,
@@ -54,4 +62,12 @@ impl ValidationError {
             invalid_fields: fields,
         }
     }
+
+    pub fn for_field(field: &str, message: impl Into<String>) -> Self {
+        Self::for_fields(HashMap::from([(field.to_string(), vec![message.into()])]))
+    }
+
+    pub fn into_invalid_fields(self) -> HashMap<String, Vec<String>> {
+        self.invalid_fields
+    }
 }
@@ -0,0 +1,298 @@
+//! Queue of requests to become a farm owner. Users submit them on `/users/request-admin`, sysadmins
+//! list them here and approve or reject them.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::SysAdmin;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::users::{ApiFarmOwnerStatus, ApiUser};
+use crate::validation::{StringLengthCriteria, StringValidator, Validator};
+use chrono::{DateTime, Utc};
+use database::FarmDB;
+use database::farm_owner_request::FarmOwnerRequest;
+use database::user::FarmOwnerStatus;
+use rocket::serde::json::Json;
+use rocket::{get, put};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list_requests, decide_request]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmOwnerRequest {
+    pub id: i32,
+    pub motivation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<String>,
+    /// `Requested` until decided, then `Yes` or `Rejected`.
+    pub status: ApiFarmOwnerStatus,
+    pub created: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decided: Option<DateTime<Utc>>,
+    /// Reason given with the decision.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// Requesting user, only listed for sysadmins.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user: Option<ApiUser>,
+}
+
+impl From<FarmOwnerRequest> for ApiFarmOwnerRequest {
+    fn from(value: FarmOwnerRequest) -> Self {
+        Self {
+            id: value.id,
+            motivation: value.motivation,
+            evidence: value.evidence,
+            status: value.status.into(),
+            created: value.created,
+            decided: value.decided,
+            reason: value.reason,
+            user: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewApiFarmOwnerRequest {
+    /// Why the user wants to manage farms.
+    pub motivation: String,
+    /// Anything backing the request, like the website of the farm.
+    #[serde(default)]
+    pub evidence: Option<String>,
+}
+
+impl NewApiFarmOwnerRequest {
+    pub fn sanitize(&mut self) {
+        self.motivation = self.motivation.trim().to_string();
+        self.evidence = self
+            .evidence
+            .take()
+            .map(|evidence| evidence.trim().to_string())
+            .filter(|evidence| !evidence.is_empty());
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationApiError> {
+        let mut errors = HashMap::new();
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::new(10, 2000));
+        if let Err(err) = validator.validate(&self.motivation) {
+            errors.insert("motivation".to_string(), err.messages);
+        }
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::max(2000));
+        if let Some(Err(err)) = self.evidence.as_deref().map(|evidence| validator.validate(evidence)) {
+            errors.insert("evidence".to_string(), err.messages);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationApiError::for_fields(errors))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmOwnerDecision {
+    pub approve: bool,
+    /// Shown to the user, required for rejections.
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+impl ApiFarmOwnerDecision {
+    fn validate(&self) -> Result<(), ValidationApiError> {
+        let mut validator = StringValidator::new();
+        let min = if self.approve { 0 } else { 1 };
+        validator.add_criteria(StringLengthCriteria::new(min, 1000));
+        match validator.validate(self.reason.as_deref().unwrap_or_default().trim()) {
+            Ok(()) => Ok(()),
+            Err(err) => Err(ValidationApiError::for_fields(HashMap::from([(
+                "reason".to_string(),
+                err.messages,
+            )]))),
+        }
+    }
+}
+
+/// Requests with the given status, those waiting for a decision by default, oldest first.
+#[get("/?<status>&<page..>")]
+async fn list_requests(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    status: Option<ApiFarmOwnerStatus>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiFarmOwnerRequest>>> {
+    let request = page.validate(&["created"])?;
+    let status = match status.unwrap_or(ApiFarmOwnerStatus::Requested) {
+        ApiFarmOwnerStatus::No => {
+            return Err(ValidationApiError::for_field("status", "Expected one of requested, yes, rejected").into());
+        }
+        status => FarmOwnerStatus::from(status),
+    };
+    let requests = database::farm_owner_request::list(&db, status, request.after()?, request.fetch_limit()).await?;
+    let page = ApiPage::new(&request, requests, |(r, _)| r.id).map(|(request, user)| ApiFarmOwnerRequest {
+        user: Some(user.into()),
+        ..request.into()
+    });
+    Ok(Json(page))
+}
+
+/// Approves or rejects a request waiting for a decision, making its user a farm owner if approved.
+#[put("/<request_id>/decision", data = "<decision>")]
+async fn decide_request(
+    db: FarmDB,
+    sysadmin: SysAdmin,
+    request_id: i32,
+    decision: Json<ApiFarmOwnerDecision>,
+) -> ApiResult<Json<ApiFarmOwnerRequest>> {
+    decision.validate()?;
+    let decision = decision.into_inner();
+    let reason = decision.reason.map(|reason| reason.trim().to_string()).filter(|reason| !reason.is_empty());
+    let request = database::farm_owner_request::decide(&db, request_id, sysadmin.0.id, decision.approve, reason)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(request.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farm_owner_requests::{ApiFarmOwnerDecision, ApiFarmOwnerRequest, NewApiFarmOwnerRequest};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, get_current_user, login_user, WithAuthorization};
+    use crate::api::v1::users::ApiFarmOwnerStatus;
+    use database::user::set_sysadmin;
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    async fn submit(client: &Client, token: &str, motivation: &str) -> Status {
+        let request = NewApiFarmOwnerRequest {
+            motivation: motivation.to_string(),
+            evidence: Some(" https://example.com/farm ".to_string()),
+        };
+        client
+            .post("/api/v1/users/request-admin")
+            .body(serde_json::to_string(&request).expect("failed to serialize request"))
+            .auth(token)
+            .dispatch()
+            .await
+            .status()
+    }
+
+    async fn decide(client: &Client, token: &str, id: i32, approve: bool, reason: Option<&str>) -> Status {
+        let decision = ApiFarmOwnerDecision {
+            approve,
+            reason: reason.map(str::to_string),
+        };
+        client
+            .put(format!("/api/v1/farm_owner_requests/{id}/decision"))
+            .body(serde_json::to_string(&decision).expect("failed to serialize decision"))
+            .auth(token)
+            .dispatch()
+            .await
+            .status()
+    }
+
+    async fn pending_of(client: &Client, token: &str, username: &str) -> Option<ApiFarmOwnerRequest> {
+        let response = client.get("/api/v1/farm_owner_requests?limit=100").auth(token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        response
+            .into_json::<ApiPage<ApiFarmOwnerRequest>>()
+            .await
+            .expect("failed to deserialize requests")
+            .items
+            .into_iter()
+            .find(|r| r.user.as_ref().is_some_and(|u| u.username == username))
+    }
+
+    async fn latest(client: &Client, token: &str) -> ApiFarmOwnerRequest {
+        let response = client.get("/api/v1/users/request-admin").auth(token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        response
+            .into_json::<ApiFarmOwnerRequest>()
+            .await
+            .expect("failed to deserialize request")
+    }
+
+    #[tokio::test]
+    async fn farm_owner_request_workflow() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        let admin = create_test_user(&client, "owner_request_admin", password).await;
+        set_sysadmin(&db, admin.id, true)
+            .await
+            .expect("failed to make user a sysadmin");
+        let admin_token = login_user(&client, &admin.username, password).await;
+        let applicant = create_test_user(&client, "owner_request_user", password).await;
+        let token = login_user(&client, &applicant.username, password).await;
+
+        let response = client.get("/api/v1/users/request-admin").auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(submit(&client, &token, "too short").await, Status::BadRequest);
+        assert_eq!(submit(&client, &token, "We run a dairy farm with a shop.").await, Status::Ok);
+        // one request at a time
+        assert_eq!(submit(&client, &token, "We run a dairy farm with a shop.").await, Status::BadRequest);
+        assert_eq!(get_current_user(&client, token.clone()).await.farmowner, ApiFarmOwnerStatus::Requested);
+
+        // only sysadmins see and decide requests, others are forwarded to the web app
+        let response = client.get("/api/v1/farm_owner_requests").auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let request = pending_of(&client, &admin_token, &applicant.username)
+            .await
+            .expect("expected pending request");
+        assert_eq!("We run a dairy farm with a shop.", request.motivation);
+        assert_eq!(Some("https://example.com/farm"), request.evidence.as_deref());
+        assert_eq!(decide(&client, &token, request.id, true, None).await, Status::Forbidden);
+
+        // rejections need a reason
+        assert_eq!(decide(&client, &admin_token, request.id, false, Some(" ")).await, Status::BadRequest);
+        assert_eq!(decide(&client, &admin_token, request.id, false, Some("No farm found")).await, Status::Ok);
+        assert_eq!(decide(&client, &admin_token, request.id, true, None).await, Status::NotFound);
+        assert!(pending_of(&client, &admin_token, &applicant.username).await.is_none());
+        let rejected = latest(&client, &token).await;
+        assert_eq!(ApiFarmOwnerStatus::Rejected, rejected.status);
+        assert_eq!(Some("No farm found"), rejected.reason.as_deref());
+        assert!(rejected.decided.is_some());
+        assert_eq!(get_current_user(&client, token.clone()).await.farmowner, ApiFarmOwnerStatus::Rejected);
+        let response = client
+            .get("/api/v1/farm_owner_requests?status=rejected")
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        let rejected_ids: Vec<i32> = response
+            .into_json::<ApiPage<ApiFarmOwnerRequest>>()
+            .await
+            .expect("failed to deserialize requests")
+            .items
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        assert!(rejected_ids.contains(&request.id));
+        let response = client.get("/api/v1/farm_owner_requests?status=no").auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // rejected users may try again
+        assert_eq!(submit(&client, &token, "Here is our farm shop website.").await, Status::Ok);
+        let request = pending_of(&client, &admin_token, &applicant.username)
+            .await
+            .expect("expected pending request");
+        assert_eq!(decide(&client, &admin_token, request.id, true, Some("Welcome")).await, Status::Ok);
+        let approved = latest(&client, &token).await;
+        assert_eq!(ApiFarmOwnerStatus::Yes, approved.status);
+        assert_eq!(Some("Welcome"), approved.reason.as_deref());
+        assert_eq!(get_current_user(&client, token.clone()).await.farmowner, ApiFarmOwnerStatus::Yes);
+        assert_eq!(submit(&client, &token, "We run a second farm as well.").await, Status::BadRequest);
+
+        user::delete(&db, applicant.id)
+            .await
+            .expect("failed to delete user");
+        user::delete(&db, admin.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -1,33 +1,73 @@
 use crate::api::Result as ApiResult;
 use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::contact::ApiContact;
 use crate::api::v1::ident::FarmOwner;
-use crate::api::v1::types::ExtId;
+use crate::api::v1::locations::ApiFarmLocation;
+use crate::api::v1::photos::{ApiPhoto, delete_photo_files};
+use crate::api::v1::products::ApiProduct;
+use crate::api::v1::pagination::{ApiPage, PageRequest, Pagination};
+use crate::api::v1::opening_hours::{
+    ApiOpenStatus, ApiOpeningHours, ApiOpeningHoursException, NewApiOpeningHours, requested_instant,
+    validate_schedule,
+};
+use crate::api::v1::types::{ApiDate, ApiTimeZone, ApiTimestamp, ExtId};
+use crate::photos::PhotoStorage;
+use crate::clustering::{CLUSTER_MAX_ZOOM, cell_size};
+use crate::schedule::open_status;
+use crate::tiles::MAX_ZOOM;
 use database::FarmDB;
-use database::farm::{Farm, FullFarm, NewFarm, OpeningHours, get_farms_owned_by};
-use database::location::NewGeoLocation;
+use database::farm::{
+    Farm, FarmCluster, FarmFilter, FarmOrder, FarmUpdate, FullFarm, LocatedFarm, NearbyFarm, NearbyOrder, NewFarm,
+    ShopType, get_farms_owned_by,
+};
+use database::location::{BoundingBox, NewGeoLocation};
+use database::osm::{OsmSchedule, to_osm};
+use database::product::DateRange;
 use crate::validation::{StringLengthCriteria, StringValidator, Validator};
 use base64::Engine;
 use base64::engine::general_purpose::URL_SAFE;
+use chrono::{DateTime, NaiveDateTime};
+use chrono_tz::Tz;
 use rocket::serde::json::Json;
-use rocket::{delete, get, post};
+use rocket::{State, delete, get, patch, post, put};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::collections::HashMap;
 
+/// Largest radius accepted by radius searches, half the earth's circumference.
+const MAX_RADIUS_KM: f64 = 20_000.0;
+
+/// Most farms returned for a bounding box. Clients should zoom in if the result is truncated.
+const MAX_BBOX_FARMS: usize = 500;
+
+/// Sorts of farm lists, the default first.
+const FARM_SORTS: [&str; 2] = ["name", "newest"];
+
+/// Sorts of radius searches, the default first.
+const NEARBY_SORTS: [&str; 2] = ["distance", "name"];
+
 pub fn routes() -> Vec<rocket::Route> {
     rocket::routes![
         list_farms,
         get_farms_near,
+        get_farms_in_bbox,
+        get_farm_clusters,
         get_full_farm,
         create_farm,
         get_owned,
+        update_farm,
+        replace_shop_types,
         delete_farm
     ]
 }
 
 #[derive(Serialize, Deserialize)]
-struct ApiFarm {
-    id: String,
-    name: String,
+pub struct ApiFarm {
+    pub id: String,
+    pub name: String,
+    /// Distance in kilometres from the searched position, only set by searches near a position.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance_km: Option<f64>,
 }
 
 impl From<Farm> for ApiFarm {
@@ -35,57 +75,134 @@ impl From<Farm> for ApiFarm {
         Self {
             id: URL_SAFE.encode(value.ext_id),
             name: value.name,
+            distance_km: None,
+        }
+    }
+}
+
+impl From<NearbyFarm> for ApiFarm {
+    fn from(value: NearbyFarm) -> Self {
+        Self {
+            distance_km: Some((value.distance * 1000.0).round() / 1000.0),
+            ..value.farm.into()
+        }
+    }
+}
+
+/// Farm at one of its locations, for placing it on the map. A farm with several locations is
+/// listed once per location.
+#[derive(Serialize, Deserialize)]
+pub struct ApiLocatedFarm {
+    #[serde(flatten)]
+    pub farm: ApiFarm,
+    pub location_id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl From<LocatedFarm> for ApiLocatedFarm {
+    fn from(value: LocatedFarm) -> Self {
+        Self {
+            farm: value.farm.into(),
+            location_id: value.location_id,
+            label: value.label,
+            lat: value.lat,
+            lon: value.lon,
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct ApiOpeningHours {
-    pub farm_id: i32,
-    pub weekday: i32,
-    pub open: chrono::NaiveTime,
-    pub close: chrono::NaiveTime,
+pub struct ApiBboxFarms {
+    pub farms: Vec<ApiLocatedFarm>,
+    /// More farms are inside the bounding box than returned.
+    pub truncated: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiCluster {
+    pub lat: f64,
+    pub lon: f64,
+    pub count: usize,
 }
 
-impl From<OpeningHours> for ApiOpeningHours {
-    fn from(value: OpeningHours) -> Self {
+impl From<FarmCluster> for ApiCluster {
+    fn from(value: FarmCluster) -> Self {
         Self {
-            farm_id: value.farm_id,
-            weekday: value.weekday,
-            open: value.open,
-            close: value.close,
+            lat: value.lat,
+            lon: value.lon,
+            count: value.count as usize,
         }
     }
 }
 
+/// Farms of a viewport, either aggregated into clusters or, once zoomed in far enough,
+/// individually.
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmClusters {
+    pub clusters: Vec<ApiCluster>,
+    pub farms: Vec<ApiLocatedFarm>,
+}
+
 #[derive(Serialize, Deserialize)]
-struct FullApiFarm {
+pub struct FullApiFarm {
     pub id: String,
     pub name: String,
-    pub lat: f32,
-    pub lon: f32,
+    /// Position of the main location.
+    pub lat: f64,
+    pub lon: f64,
+    pub locations: Vec<ApiFarmLocation>,
     pub shop_types: Vec<String>,
     pub opening_hours: Vec<ApiOpeningHours>,
+    pub opening_hours_exceptions: Vec<ApiOpeningHoursException>,
+    pub osm_opening_hours: String,
+    pub open_status: ApiOpenStatus,
+    pub contact: Option<ApiContact>,
+    pub products: Vec<ApiProduct>,
+    pub photos: Vec<ApiPhoto>,
 }
 
-impl From<FullFarm> for FullApiFarm {
-    fn from(value: FullFarm) -> Self {
+impl FullApiFarm {
+    pub fn new(value: FullFarm, at: DateTime<Tz>) -> Self {
+        let open_status =
+            open_status(&value.opening_hours, &value.opening_hours_exceptions, at).into();
+        let osm_opening_hours =
+            to_osm(&OsmSchedule::from_rows(&value.opening_hours, &value.opening_hours_exceptions));
+        let (lat, lon) = value.main_location().map(|l| (l.lat, l.lon)).unwrap_or_default();
+        let locations = value
+            .locations
+            .into_iter()
+            .map(|l| ApiFarmLocation::new(l, &value.opening_hours, &value.opening_hours_exceptions, at))
+            .collect();
         Self {
             id: URL_SAFE.encode(value.ext_id),
             name: value.name,
-            lat: value.lat,
-            lon: value.lon,
+            lat,
+            lon,
+            locations,
             shop_types: value.shop_types.into_iter().map(|t| t.name).collect(),
             opening_hours: value.opening_hours.into_iter().map(From::from).collect(),
+            opening_hours_exceptions: value
+                .opening_hours_exceptions
+                .into_iter()
+                .map(From::from)
+                .collect(),
+            osm_opening_hours,
+            open_status,
+            contact: value.contact.map(ApiContact::public),
+            products: value.products.into_iter().map(From::from).collect(),
+            photos: value.photos.into_iter().map(From::from).collect(),
         }
     }
 }
 
 #[derive(Serialize, Deserialize)]
-struct NewApiFarm {
-    name: String,
-    lat: f32,
-    lon: f32,
+pub struct NewApiFarm {
+    pub name: String,
+    pub lat: f64,
+    pub lon: f64,
 }
 
 impl From<NewApiFarm> for NewFarm {
@@ -95,11 +212,14 @@ impl From<NewApiFarm> for NewFarm {
 }
 
 impl NewApiFarm {
-    fn validate(&self) -> Result<(), ValidationApiError> {
+    pub fn validate(&self) -> Result<(), ValidationApiError> {
         let mut errors = HashMap::new();
         if let Some(err) = self.validate_name() {
             errors.insert("name".to_string(), err);
         }
+        if let Some(err) = validate_position(self.lat, self.lon) {
+            errors.insert("location".to_string(), err);
+        }
         if errors.is_empty() {
             Ok(())
         } else {
@@ -108,45 +228,338 @@ impl NewApiFarm {
     }
 
     fn validate_name(&self) -> Option<Vec<String>> {
-        let mut validator = StringValidator::new();
-        validator.add_criteria(StringLengthCriteria::min(3));
-        if let Err(err) = validator.validate(&self.name) {
-            return Some(err.messages);
+        validate_farm_name(&self.name)
+    }
+}
+
+pub(crate) fn validate_position(lat: f64, lon: f64) -> Option<Vec<String>> {
+    if (-90.0..=90.0).contains(&lat) && (-180.0..=180.0).contains(&lon) {
+        None
+    } else {
+        Some(vec![
+            "Expected a latitude between -90 and 90 and a longitude between -180 and 180".to_string(),
+        ])
+    }
+}
+
+fn validate_farm_name(name: &str) -> Option<Vec<String>> {
+    let mut validator = StringValidator::new();
+    validator.add_criteria(StringLengthCriteria::min(3));
+    if let Err(err) = validator.validate(name) {
+        return Some(err.messages);
+    }
+    if name
+        .chars()
+        .any(|c| !c.is_alphanumeric() && !" -._".contains(c))
+    {
+        return Some(vec![
+            "Only letters, numbers and characters `-._` allowed".to_string(),
+        ]);
+    }
+    if !name.chars().next().unwrap().is_alphabetic() {
+        return Some(vec!["Has to begin with a letter".to_string()]);
+    }
+    None
+}
+
+/// Partial farm change for `PATCH`. Fields left out of the request body are not touched.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ApiFarmPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shop_types: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_hours: Option<Vec<NewApiOpeningHours>>,
+}
+
+impl ApiFarmPatch {
+    pub fn validate(&self, known_shop_types: &[ShopType]) -> Result<(), ValidationApiError> {
+        let mut errors = HashMap::new();
+        if let Some(name) = &self.name
+            && let Some(err) = validate_farm_name(name)
+        {
+            errors.insert("name".to_string(), err);
         }
-        if self
-            .name
-            .chars()
-            .any(|c| !c.is_alphanumeric() && !" -._".contains(c))
+        if self.lat.is_some() != self.lon.is_some() {
+            errors.insert(
+                "location".to_string(),
+                vec!["`lat` and `lon` have to be changed together".to_string()],
+            );
+        } else if let Some((lat, lon)) = self.lat.zip(self.lon)
+            && let Some(err) = validate_position(lat, lon)
         {
-            return Some(vec![
-                "Only letters, numbers and characters `-._` allowed".to_string(),
-            ]);
+            errors.insert("location".to_string(), err);
         }
-        if !self.name.chars().next().unwrap().is_alphabetic() {
-            return Some(vec!["Has to begin with a letter".to_string()]);
+        if let Some(err) = self.validate_shop_types(known_shop_types) {
+            errors.insert("shop_types".to_string(), err);
         }
-        None
+        if let Some(hours) = &self.opening_hours {
+            errors.extend(validate_schedule(hours));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationApiError::for_fields(errors))
+        }
+    }
+
+    fn validate_shop_types(&self, known_shop_types: &[ShopType]) -> Option<Vec<String>> {
+        let errors: Vec<String> = self
+            .shop_types
+            .iter()
+            .flatten()
+            .filter(|name| !known_shop_types.iter().any(|t| &t.name == *name))
+            .map(|name| format!("Unknown or retired shop type `{name}`"))
+            .collect();
+        if errors.is_empty() { None } else { Some(errors) }
+    }
+
+    pub fn into_update(self, farm_id: i32, known_shop_types: &[ShopType]) -> FarmUpdate {
+        let location = self.lat.zip(self.lon).map(|(lat, lon)| NewGeoLocation { lat, lon });
+        let shop_types = self.shop_types.map(|names| {
+            known_shop_types
+                .iter()
+                .filter(|t| names.contains(&t.name))
+                .map(|t| t.id)
+                .collect()
+        });
+        let opening_hours = self.opening_hours.map(|hours| {
+            hours
+                .into_iter()
+                .map(|h| h.into_new_opening_hours(farm_id))
+                .collect()
+        });
+        FarmUpdate {
+            name: self.name,
+            location,
+            shop_types,
+            opening_hours,
+            ..Default::default()
+        }
+    }
+}
+
+/// Local time of `open_at` in `tz`, the time zone opening hours are interpreted in.
+fn local_open_at(open_at: Option<ApiTimestamp>, tz: Option<ApiTimeZone>) -> Option<NaiveDateTime> {
+    open_at.map(|at| requested_instant(Some(at), tz).naive_local())
+}
+
+/// Keeps the dates products have to be available on, a single date if only one is given.
+fn availability_range(from: Option<ApiDate>, to: Option<ApiDate>) -> Result<Option<DateRange>, ValidationApiError> {
+    let range = match (from, to) {
+        (None, None) => return Ok(None),
+        (Some(from), Some(to)) => DateRange { from: from.0, to: to.0 },
+        (Some(date), None) | (None, Some(date)) => DateRange { from: date.0, to: date.0 },
+    };
+    if range.from > range.to {
+        return Err(ValidationApiError::for_field(
+            "available_from",
+            "Expected `available_from` not to be after `available_to`",
+        ));
+    }
+    Ok(Some(range))
+}
+
+fn farm_order(request: &PageRequest) -> Result<FarmOrder, ValidationApiError> {
+    Ok(match request.sort {
+        "newest" => FarmOrder::Newest(request.after()?),
+        _ => FarmOrder::Name(request.after()?),
+    })
+}
+
+fn farm_key(sort: &str, farm: &Farm) -> serde_json::Value {
+    match sort {
+        "newest" => json!(farm.id),
+        _ => json!((&farm.name, farm.id)),
+    }
+}
+
+/// All farms, sorted by `name` or the `newest` first. `product` keeps farms selling a product with
+/// that text in its name, together with `available_from` and `available_to` only if the product
+/// is available on any of these dates. `open_at` keeps farms open at that instant, with opening
+/// hours read in `tz`.
+#[get("/?<shop_type>&<product>&<available_from>&<available_to>&<open_at>&<tz>&<page..>")]
+#[allow(clippy::too_many_arguments)]
+async fn list_farms(
+    db: FarmDB,
+    shop_type: Option<String>,
+    product: Option<String>,
+    available_from: Option<ApiDate>,
+    available_to: Option<ApiDate>,
+    open_at: Option<ApiTimestamp>,
+    tz: Option<ApiTimeZone>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiFarm>>> {
+    let available = availability_range(available_from, available_to)?;
+    let request = page.validate(&FARM_SORTS)?;
+    let filter = FarmFilter {
+        shop_type,
+        product,
+        available,
+        open_at: local_open_at(open_at, tz),
+    };
+    let farms = database::farm::list_farms_page(&db, filter, farm_order(&request)?, request.fetch_limit()).await?;
+    let page = ApiPage::new(&request, farms, |f| farm_key(request.sort, f));
+    Ok(Json(page.map(ApiFarm::from)))
+}
+
+fn validate_radius_search(lat: f64, lon: f64, radius: f64) -> Result<(), ValidationApiError> {
+    let mut errors = HashMap::new();
+    if !(-90.0..=90.0).contains(&lat) {
+        errors.insert("lat".to_string(), vec!["Expected a latitude between -90 and 90".to_string()]);
+    }
+    if !(-180.0..=180.0).contains(&lon) {
+        errors.insert("lon".to_string(), vec!["Expected a longitude between -180 and 180".to_string()]);
+    }
+    if !(radius > 0.0 && radius <= MAX_RADIUS_KM) {
+        errors.insert(
+            "radius".to_string(),
+            vec![format!("Expected a radius in kilometres above 0 and up to {MAX_RADIUS_KM}")],
+        );
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+/// Farms within `radius` kilometres, sorted by `distance` or `name`. Filters and pages like
+/// [list_farms], so farms nearby selling asparagus this week are
+/// `find_near?lat=47.4&lon=8.5&radius=20&product=asparagus&available_from=2026-05-04&available_to=2026-05-10`.
+#[get("/find_near?<lat>&<lon>&<radius>&<shop_type>&<product>&<available_from>&<available_to>&<open_at>&<tz>&<page..>")]
+#[allow(clippy::too_many_arguments)]
+async fn get_farms_near(
+    db: FarmDB,
+    lat: f64,
+    lon: f64,
+    radius: f64,
+    shop_type: Option<String>,
+    product: Option<String>,
+    available_from: Option<ApiDate>,
+    available_to: Option<ApiDate>,
+    open_at: Option<ApiTimestamp>,
+    tz: Option<ApiTimeZone>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiFarm>>> {
+    validate_radius_search(lat, lon, radius)?;
+    let available = availability_range(available_from, available_to)?;
+    let request = page.validate(&NEARBY_SORTS)?;
+    // distances are kept as bits, json numbers might not parse back to the exact same value
+    let order = match request.sort {
+        "name" => NearbyOrder::Name(request.after()?),
+        _ => NearbyOrder::Distance(request.after::<(u64, i32)>()?.map(|(bits, id)| (f64::from_bits(bits), id))),
+    };
+    let filter = FarmFilter {
+        shop_type,
+        product,
+        available,
+        open_at: local_open_at(open_at, tz),
+    };
+    let farms = database::farm::get_farms_near(&db, lat, lon, radius, filter, order, request.fetch_limit()).await?;
+    let page = ApiPage::new(&request, farms, |f| match request.sort {
+        "name" => json!((&f.farm.name, f.farm.id)),
+        _ => json!((f.distance.to_bits(), f.farm.id)),
+    });
+    Ok(Json(page.map(ApiFarm::from)))
+}
+
+fn validate_bbox(bbox: &BoundingBox) -> Result<(), ValidationApiError> {
+    let mut errors = HashMap::new();
+    for (name, lat) in [("min_lat", bbox.min_lat), ("max_lat", bbox.max_lat)] {
+        if !(-90.0..=90.0).contains(&lat) {
+            errors.insert(name.to_string(), vec!["Expected a latitude between -90 and 90".to_string()]);
+        }
+    }
+    for (name, lon) in [("min_lon", bbox.min_lon), ("max_lon", bbox.max_lon)] {
+        if !(-180.0..=180.0).contains(&lon) {
+            errors.insert(name.to_string(), vec!["Expected a longitude between -180 and 180".to_string()]);
+        }
+    }
+    if errors.is_empty() && bbox.min_lat > bbox.max_lat {
+        errors.insert("min_lat".to_string(), vec!["Expected `min_lat` not to exceed `max_lat`".to_string()]);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
     }
 }
 
-#[get("/")]
-async fn list_farms(db: FarmDB) -> ApiResult<Json<Vec<ApiFarm>>> {
-    let farms = database::farm::list_farms(&db).await?;
-    Ok(Json(farms.into_iter().map(ApiFarm::from).collect()))
+/// Farms inside the map viewport. A `min_lon` greater than `max_lon` selects a viewport crossing
+/// the antimeridian.
+#[get("/in_bbox?<min_lat>&<min_lon>&<max_lat>&<max_lon>")]
+async fn get_farms_in_bbox(
+    db: FarmDB,
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+) -> ApiResult<Json<ApiBboxFarms>> {
+    let bbox = BoundingBox {
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+    };
+    validate_bbox(&bbox)?;
+    let mut farms = database::farm::get_farms_in_bbox(&db, bbox, MAX_BBOX_FARMS as i64 + 1).await?;
+    let truncated = farms.len() > MAX_BBOX_FARMS;
+    farms.truncate(MAX_BBOX_FARMS);
+    Ok(Json(ApiBboxFarms {
+        farms: farms.into_iter().map(ApiLocatedFarm::from).collect(),
+        truncated,
+    }))
 }
 
-#[get("/find_near?<lat>&<lon>&<radius>")]
-async fn get_farms_near(db: FarmDB, lat: f32, lon: f32, radius: f32) -> ApiResult<Json<Vec<ApiFarm>>> {
-    let farms = database::farm::get_farms_near(&db, lat, lon, radius).await?;
-    Ok(Json(farms.into_iter().map(ApiFarm::from).collect()))
+/// Clusters of the farms in the viewport. Below zoom level [`CLUSTER_MAX_ZOOM`], or when the
+/// viewport contains more than [`MAX_BBOX_FARMS`] farms, only clusters are returned.
+#[get("/clusters?<zoom>&<min_lat>&<min_lon>&<max_lat>&<max_lon>")]
+async fn get_farm_clusters(
+    db: FarmDB,
+    zoom: u8,
+    min_lat: f64,
+    min_lon: f64,
+    max_lat: f64,
+    max_lon: f64,
+) -> ApiResult<Json<ApiFarmClusters>> {
+    let bbox = BoundingBox {
+        min_lat,
+        min_lon,
+        max_lat,
+        max_lon,
+    };
+    validate_bbox(&bbox)?;
+    if zoom > MAX_ZOOM {
+        return Err(ValidationApiError::for_field("zoom", format!("Expected a zoom level up to {MAX_ZOOM}")).into());
+    }
+    if zoom >= CLUSTER_MAX_ZOOM {
+        let farms = database::farm::get_farms_in_bbox(&db, bbox, MAX_BBOX_FARMS as i64 + 1).await?;
+        if farms.len() <= MAX_BBOX_FARMS {
+            return Ok(Json(ApiFarmClusters {
+                clusters: vec![],
+                farms: farms.into_iter().map(ApiLocatedFarm::from).collect(),
+            }));
+        }
+    }
+    let clusters = database::farm::cluster_farms_in_bbox(&db, bbox, cell_size(zoom)).await?;
+    Ok(Json(ApiFarmClusters {
+        clusters: clusters.into_iter().map(ApiCluster::from).collect(),
+        farms: vec![],
+    }))
 }
 
-#[get("/<farm_id>")]
-async fn get_full_farm(db: FarmDB, farm_id: ExtId) -> ApiResult<Json<FullApiFarm>> {
+#[get("/<farm_id>?<tz>")]
+async fn get_full_farm(db: FarmDB, farm_id: ExtId, tz: Option<ApiTimeZone>) -> ApiResult<Json<FullApiFarm>> {
     if let Some(farm_id) = database::farm::id_from_ext_id(&db, farm_id.0).await?
         && let Some(full_farm) = database::farm::load_full_farm(&db, farm_id).await?
     {
-        return Ok(Json(full_farm.into()));
+        return Ok(Json(FullApiFarm::new(full_farm, requested_instant(None, tz))));
     }
     Err(ApiError::NotFound)
 }
@@ -165,35 +578,94 @@ async fn create_farm(db: FarmDB, farm_owner: FarmOwner, farm: Json<NewApiFarm>)
     Ok(Json(new_farm.into()))
 }
 
-#[get("/owned")]
-async fn get_owned(db: FarmDB, farm_owner: FarmOwner) -> ApiResult<Json<Vec<ApiFarm>>> {
-    let farms = get_farms_owned_by(&db, &farm_owner.0).await?;
-    Ok(Json(farms.into_iter().map(ApiFarm::from).collect()))
+/// Farms the user is an admin of, sorted like [list_farms].
+#[get("/owned?<page..>")]
+async fn get_owned(db: FarmDB, farm_owner: FarmOwner, page: Pagination) -> ApiResult<Json<ApiPage<ApiFarm>>> {
+    let request = page.validate(&FARM_SORTS)?;
+    let farms = get_farms_owned_by(&db, &farm_owner.0, farm_order(&request)?, request.fetch_limit()).await?;
+    Ok(Json(ApiPage::new(&request, farms, |f| farm_key(request.sort, f)).map(ApiFarm::from)))
+}
+
+#[patch("/<farm_id>", data = "<patch>")]
+async fn update_farm(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    patch: Json<ApiFarmPatch>,
+) -> ApiResult<Json<FullApiFarm>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let patch = patch.into_inner();
+    let known_shop_types = database::farm::assignable_shop_types(&db, farm_id).await?;
+    patch.validate(&known_shop_types)?;
+    let update = patch.into_update(farm_id, &known_shop_types);
+    if !database::farm::update_farm(&db, farm_owner.0.id, farm_id, update).await? {
+        return Err(ApiError::Forbidden);
+    }
+    database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .map(|farm| Json(FullApiFarm::new(farm, requested_instant(None, None))))
+        .ok_or(ApiError::NotFound)
+}
+
+/// Replaces the shop types of a farm. Retired shop types can only be kept, not newly assigned.
+#[put("/<farm_id>/shop_types", data = "<shop_types>")]
+async fn replace_shop_types(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    shop_types: Json<Vec<String>>,
+) -> ApiResult<Json<Vec<String>>> {
+    let patch = ApiFarmPatch {
+        shop_types: Some(shop_types.into_inner()),
+        ..Default::default()
+    };
+    let farm = update_farm(db, farm_id, farm_owner, Json(patch)).await?;
+    Ok(Json(farm.into_inner().shop_types))
 }
 
 #[delete("/<farm_id>")]
-async fn delete_farm(db: FarmDB, farm_id: ExtId, farm_owner: FarmOwner) -> ApiResult<()> {
+async fn delete_farm(
+    db: FarmDB,
+    storage: &State<Box<dyn PhotoStorage>>,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+) -> ApiResult<()> {
     let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
         .await?
         .ok_or(ApiError::NotFound)?;
-    database::farm::delete_farm(&db, farm_owner.0.id, farm_id).await?;
+    let photos = database::photo::list_photos(&db, farm_id).await?;
+    if database::farm::delete_farm(&db, farm_owner.0.id, farm_id).await? {
+        for photo in &photos {
+            delete_photo_files(storage.inner().as_ref(), photo).await?;
+        }
+    }
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::contact::{ApiContact, NewApiContact};
+    use crate::api::v1::farms::{ApiBboxFarms, ApiFarm, ApiFarmClusters, ApiFarmPatch, FullApiFarm, NewApiFarm};
+    use crate::api::v1::opening_hours::{
+        ApiOpenStatus, ApiOpeningHours, ApiOpeningHoursException, ApiOsmOpeningHours,
+        NewApiOpeningHours, NewApiOpeningHoursException,
+    };
+    use crate::api::v1::pagination::ApiPage;
     use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use chrono::{NaiveDate, NaiveTime};
     use database::user::make_farmowner;
     use database::{FarmDB, user};
     use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
 
     #[tokio::test]
     async fn farm_api_crud() {
         let client = create_untracked_client().await;
         let password = "Abc123!.";
 
-        let user = create_test_user(&client, "farm_api_crud", &password).await;
+        let user = create_test_user(&client, "farm_api_crud", password).await;
         let db = FarmDB::get_one(client.rocket())
             .await
             .expect("failed to get db");
@@ -201,7 +673,7 @@ mod tests {
         make_farmowner(&db, user_id)
             .await
             .expect("failed to make user a farm owner");
-        let token = login_user(&client, &user.username, &password).await;
+        let token = login_user(&client, &user.username, password).await;
 
         let new_farm = NewApiFarm {
             name: "F farm_api_crud".to_string(),
@@ -241,12 +713,290 @@ mod tests {
             .dispatch()
             .await;
         let api_farms = response
-            .into_json::<Vec<ApiFarm>>()
+            .into_json::<ApiPage<ApiFarm>>()
             .await
-            .expect("failed to deserialize owned farms list");
+            .expect("failed to deserialize owned farms list")
+            .items;
         assert_eq!(1, api_farms.len());
 
         // update
+        let patch = ApiFarmPatch {
+            name: Some("F farm_api_crud changed".to_string()),
+            shop_types: Some(vec!["store".to_string(), "self-service".to_string()]),
+            opening_hours: Some(vec![NewApiOpeningHours {
+                weekday: 2,
+                open: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+                close: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+                overnight: false,
+            }]),
+            ..Default::default()
+        };
+        let req = client.patch(format!("/api/v1/farms/{}", ext_id));
+        let response = req
+            .body(serde_json::to_string(&patch).expect("failed to serialize farm patch"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let full_farm = response
+            .into_json::<FullApiFarm>()
+            .await
+            .expect("failed to deserialize full api farm");
+        assert_eq!("F farm_api_crud changed", full_farm.name);
+        assert_eq!(1.5, full_farm.lat);
+        assert_eq!(2, full_farm.shop_types.len());
+        assert_eq!(1, full_farm.opening_hours.len());
+
+        let invalid_patch = ApiFarmPatch {
+            lat: Some(2.0),
+            shop_types: Some(vec!["spaceport".to_string()]),
+            ..Default::default()
+        };
+        let req = client.patch(format!("/api/v1/farms/{}", ext_id));
+        let response = req
+            .body(serde_json::to_string(&invalid_patch).expect("failed to serialize farm patch"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // replace opening hours
+        let schedule = vec![NewApiOpeningHours {
+            weekday: 4,
+            open: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(2, 0, 0).unwrap(),
+            overnight: true,
+        }];
+        let req = client.put(format!("/api/v1/farms/{}/opening_hours", ext_id));
+        let response = req
+            .body(serde_json::to_string(&schedule).expect("failed to serialize opening hours"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let req = client.get(format!("/api/v1/farms/{}/opening_hours", ext_id));
+        let hours = req
+            .dispatch()
+            .await
+            .into_json::<Vec<ApiOpeningHours>>()
+            .await
+            .expect("failed to deserialize opening hours");
+        assert_eq!(1, hours.len());
+        assert!(hours[0].overnight);
+
+        // a 24 hour range survives a round trip
+        let mut schedule = schedule;
+        schedule.push(NewApiOpeningHours {
+            weekday: 1,
+            open: NaiveTime::MIN,
+            close: NaiveTime::MIN,
+            overnight: true,
+        });
+        let req = client.put(format!("/api/v1/farms/{}/opening_hours", ext_id));
+        let response = req
+            .body(serde_json::to_string(&schedule).expect("failed to serialize opening hours"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let req = client.get(format!("/api/v1/farms/{}/opening_hours", ext_id));
+        let hours = req
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .expect("failed to read opening hours");
+        let req = client.put(format!("/api/v1/farms/{}/opening_hours", ext_id));
+        let response = req.body(hours).auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let hours = response
+            .into_json::<Vec<ApiOpeningHours>>()
+            .await
+            .expect("failed to deserialize opening hours");
+        assert_eq!(2, hours.len());
+        assert!(hours.iter().all(|h| h.overnight));
+
+        // filter by opening hours
+        let req = client.get("/api/v1/farms?open_at=2025-06-07T01:00:00Z&tz=UTC");
+        let open_farms = req
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farm list")
+            .items;
+        assert!(open_farms.iter().any(|f| f.id == ext_id));
+        // filtered in the database, so pages stay full and the total only counts open farms
+        let req = client.get("/api/v1/farms?open_at=2025-06-07T01:00:00Z&tz=UTC&limit=1");
+        let open_page = req
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farm list");
+        assert_eq!(1, open_page.items.len());
+        let req = client.get("/api/v1/farms?open_at=2025-06-07T01:00:00Z&tz=UTC&limit=100");
+        let open_farms = req
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farm list");
+        if open_farms.next_cursor.is_none() {
+            assert_eq!(open_farms.items.len() as i64, open_page.total_estimate);
+        }
+        let req = client.get("/api/v1/farms?open_at=2025-06-07T03:00:00%2B01:00&tz=Europe/London");
+        let open_farms = req
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farm list")
+            .items;
+        assert!(!open_farms.iter().any(|f| f.id == ext_id));
+        let req = client.get(format!("/api/v1/farms/{}/open_status?at=2025-06-06T19:00:00Z", ext_id));
+        let status = req
+            .dispatch()
+            .await
+            .into_json::<ApiOpenStatus>()
+            .await
+            .expect("failed to deserialize open status");
+        assert!(!status.is_open);
+        assert_eq!(
+            status.next_opening.map(|t| t.to_rfc3339()),
+            Some("2025-06-06T20:00:00+00:00".to_string())
+        );
+
+        // opening hours exceptions
+        let closure = NewApiOpeningHoursException {
+            start_date: NaiveDate::from_ymd_opt(2025, 6, 6).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2025, 6, 7).unwrap(),
+            open: None,
+            close: None,
+            overnight: false,
+            description: Some("Harvest".to_string()),
+        };
+        let req = client.post(format!("/api/v1/farms/{}/opening_hours/exceptions", ext_id));
+        let response = req
+            .body(serde_json::to_string(&closure).expect("failed to serialize exception"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let exception = response
+            .into_json::<ApiOpeningHoursException>()
+            .await
+            .expect("failed to deserialize exception");
+        let exceptions = client
+            .get(format!("/api/v1/farms/{}/opening_hours/exceptions", ext_id))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiOpeningHoursException>>()
+            .await
+            .expect("failed to deserialize exceptions");
+        assert_eq!(1, exceptions.total_estimate);
+        assert_eq!(vec![exception.id], exceptions.items.iter().map(|e| e.id).collect::<Vec<_>>());
+        let req = client.get(format!("/api/v1/farms/{}/open_status?at=2025-06-07T01:00:00Z", ext_id));
+        let status = req
+            .dispatch()
+            .await
+            .into_json::<ApiOpenStatus>()
+            .await
+            .expect("failed to deserialize open status");
+        assert!(!status.is_open);
+        let req = client.get("/api/v1/farms?open_at=2025-06-07T01:00:00Z&tz=UTC&limit=100");
+        let open_farms = req
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farm list")
+            .items;
+        assert!(!open_farms.iter().any(|f| f.id == ext_id));
+        let other = create_test_user(&client, "farm_api_crud_other", password).await;
+        make_farmowner(&db, other.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let other_token = login_user(&client, &other.username, password).await;
+        let req = client.delete(format!("/api/v1/farms/{}/opening_hours/exceptions/{}", ext_id, exception.id));
+        let response = req.auth(&other_token).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+        user::delete(&db, other.id)
+            .await
+            .expect("failed to delete user");
+        let req = client.delete(format!("/api/v1/farms/{}/opening_hours/exceptions/{}", ext_id, exception.id));
+        let response = req.auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let req = client.delete(format!("/api/v1/farms/{}/opening_hours/exceptions/{}", ext_id, exception.id));
+        let response = req.auth(&token).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        // OSM opening hours
+        let req = client.put(format!("/api/v1/farms/{}/opening_hours/osm?year=2025", ext_id));
+        let response = req
+            .body("Mo-Fr 08:00-18:00; Sa 09:00-12:00; Dec 24-26 off; PH off")
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let osm = response
+            .into_json::<ApiOsmOpeningHours>()
+            .await
+            .expect("failed to deserialize OSM opening hours");
+        assert_eq!(6, osm.opening_hours.len());
+        assert_eq!(1, osm.opening_hours_exceptions.len());
+        assert_eq!(vec!["PH off".to_string()], osm.ignored_rules);
+        let req = client.get(format!("/api/v1/farms/{}/opening_hours/osm", ext_id));
+        let osm = req
+            .dispatch()
+            .await
+            .into_string()
+            .await
+            .expect("failed to read OSM opening hours");
+        assert_eq!("Mo-Fr 08:00-18:00; Sa 09:00-12:00; 2025 Dec 24-26 off", osm);
+        let req = client.put(format!("/api/v1/farms/{}/opening_hours/osm", ext_id));
+        let response = req
+            .body("Mo-Fr 08:00-12:00; Mo 11:00")
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // contact
+        let contact = NewApiContact {
+            email: Some("farm_api_crud@test.com".to_string()),
+            phone: Some("+41 79 123 45 67".to_string()),
+            address: Some("Farm road 1".to_string()),
+            email_public: true,
+            phone_public: false,
+            address_public: true,
+        };
+        let req = client.put(format!("/api/v1/farms/{}/contact", ext_id));
+        let response = req
+            .body(serde_json::to_string(&contact).expect("failed to serialize contact"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let req = client.get(format!("/api/v1/farms/{}/contact", ext_id));
+        let contact = req
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiContact>()
+            .await
+            .expect("failed to deserialize contact");
+        assert_eq!(Some("+41791234567".to_string()), contact.phone);
+        let req = client.get(format!("/api/v1/farms/{}", ext_id));
+        let full_farm = req
+            .dispatch()
+            .await
+            .into_json::<FullApiFarm>()
+            .await
+            .expect("failed to deserialize full api farm");
+        let public_contact = full_farm.contact.expect("expected public contact");
+        assert_eq!(None, public_contact.phone);
+        assert_eq!(Some("Farm road 1".to_string()), public_contact.address);
 
         // delete
         let req = client.delete(format!("/api/v1/farms/{}", ext_id));
@@ -259,4 +1009,270 @@ mod tests {
             .await
             .expect("failed to delete user");
     }
+
+    #[tokio::test]
+    async fn farms_near_sorted_by_distance() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farms_near_sorted", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        // one degree of longitude is only about 23 km this far north
+        let mut ext_ids = vec![];
+        for (name, lon) in [("F near far", 16.0), ("F near close", 15.0)] {
+            let new_farm = NewApiFarm {
+                name: name.to_string(),
+                lat: 78.0,
+                lon,
+            };
+            let farm = client
+                .post("/api/v1/farms")
+                .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiFarm>()
+                .await
+                .expect("failed to deserialize farm");
+            ext_ids.push(farm.id);
+        }
+
+        let farms = client
+            .get("/api/v1/farms/find_near?lat=78.0&lon=15.4&radius=30")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items;
+        let names: Vec<&str> = farms.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(vec!["F near close", "F near far"], names);
+        let distance = farms[0].distance_km.expect("expected distance");
+        assert!((distance - 9.3).abs() < 0.1, "{distance}");
+
+        let farms = client
+            .get("/api/v1/farms/find_near?lat=78.0&lon=15.4&radius=10")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items;
+        assert_eq!(1, farms.len());
+
+        let response = client
+            .get("/api/v1/farms/find_near?lat=78.0&lon=15.4&radius=-1")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        for ext_id in ext_ids {
+            let response = client
+                .delete(format!("/api/v1/farms/{}", ext_id))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+
+    async fn all_pages(client: &Client, token: &str, path: &str) -> Vec<ApiPage<ApiFarm>> {
+        let mut pages: Vec<ApiPage<ApiFarm>> = Vec::new();
+        loop {
+            let cursor = pages.last().and_then(|p| p.next_cursor.as_deref());
+            let url = match cursor {
+                Some(cursor) => format!("{path}&cursor={cursor}"),
+                None if pages.is_empty() => path.to_string(),
+                None => return pages,
+            };
+            let response = client.get(url).auth(token).dispatch().await;
+            assert_eq!(response.status(), Status::Ok, "{path}");
+            pages.push(response.into_json().await.expect("failed to deserialize farm page"));
+        }
+    }
+
+    fn page_names(pages: &[ApiPage<ApiFarm>]) -> Vec<Vec<&str>> {
+        pages.iter().map(|p| p.items.iter().map(|f| f.name.as_str()).collect()).collect()
+    }
+
+    #[tokio::test]
+    async fn farm_pages() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farm_pages", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let mut ext_ids = vec![];
+        for (name, lon) in [("F page b", -150.3), ("F page a", -150.1), ("F page c", -150.2), ("F page b", -150.4)] {
+            let new_farm = NewApiFarm { name: name.to_string(), lat: 61.0, lon };
+            let farm = client
+                .post("/api/v1/farms")
+                .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiFarm>()
+                .await
+                .expect("failed to deserialize farm");
+            ext_ids.push(farm.id);
+        }
+
+        // farms of the same name are told apart by their id
+        let pages = all_pages(&client, &token, "/api/v1/farms/owned?limit=2").await;
+        assert_eq!(vec![vec!["F page a", "F page b"], vec!["F page b", "F page c"]], page_names(&pages));
+        assert!(pages.iter().all(|p| p.total_estimate == 4));
+        assert_eq!(ext_ids[0], pages[0].items[1].id);
+
+        let pages = all_pages(&client, &token, "/api/v1/farms/owned?sort=newest&limit=3").await;
+        let ids: Vec<&str> = pages.iter().flat_map(|p| &p.items).map(|f| f.id.as_str()).collect();
+        let newest_first: Vec<&str> = ext_ids.iter().rev().map(String::as_str).collect();
+        assert_eq!(newest_first, ids);
+        assert_eq!(2, pages.len());
+
+        let pages = all_pages(&client, &token, "/api/v1/farms/find_near?lat=61.0&lon=-150.0&radius=30&limit=3").await;
+        assert_eq!(vec![vec!["F page a", "F page c", "F page b"], vec!["F page b"]], page_names(&pages));
+        assert_eq!(ext_ids[3], pages[1].items[0].id);
+        let pages =
+            all_pages(&client, &token, "/api/v1/farms/find_near?lat=61.0&lon=-150.0&radius=30&limit=1&sort=name").await;
+        assert_eq!(4, pages.len());
+        assert!(pages.iter().all(|p| p.total_estimate == 4));
+
+        let first = &all_pages(&client, &token, "/api/v1/farms/owned?limit=1").await[0];
+        let cursor = first.next_cursor.as_deref().expect("expected a next page");
+        for query in ["limit=0", "limit=101", "sort=size", "cursor=abc", &format!("sort=newest&cursor={cursor}")] {
+            let response = client
+                .get(format!("/api/v1/farms/owned?{query}"))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::BadRequest, "{query}");
+        }
+
+        for ext_id in ext_ids {
+            let response = client
+                .delete(format!("/api/v1/farms/{ext_id}"))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+
+    #[tokio::test]
+    async fn farms_in_bbox() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farms_in_bbox", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let mut ext_ids = vec![];
+        for (name, lon) in [("F bbox east", 179.5), ("F bbox west", -179.5), ("F bbox fiji", 170.0)] {
+            let new_farm = NewApiFarm {
+                name: name.to_string(),
+                lat: -17.5,
+                lon,
+            };
+            let farm = client
+                .post("/api/v1/farms")
+                .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiFarm>()
+                .await
+                .expect("failed to deserialize farm");
+            ext_ids.push(farm.id);
+        }
+
+        let result = client
+            .get("/api/v1/farms/in_bbox?min_lat=-18&min_lon=169.5&max_lat=-17&max_lon=170.5")
+            .dispatch()
+            .await
+            .into_json::<ApiBboxFarms>()
+            .await
+            .expect("failed to deserialize farms in bbox");
+        assert!(!result.truncated);
+        assert_eq!(1, result.farms.len());
+        assert_eq!("F bbox fiji", result.farms[0].farm.name);
+        assert_eq!((-17.5, 170.0), (result.farms[0].lat, result.farms[0].lon));
+
+        // crossing the antimeridian
+        let result = client
+            .get("/api/v1/farms/in_bbox?min_lat=-18&min_lon=179&max_lat=-17&max_lon=-179")
+            .dispatch()
+            .await
+            .into_json::<ApiBboxFarms>()
+            .await
+            .expect("failed to deserialize farms in bbox");
+        let mut names: Vec<String> = result.farms.into_iter().map(|f| f.farm.name).collect();
+        names.sort();
+        assert_eq!(vec!["F bbox east", "F bbox west"], names);
+
+        let response = client
+            .get("/api/v1/farms/in_bbox?min_lat=-17&min_lon=179&max_lat=-18&max_lon=-179")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // clustered when zoomed out, single farms when zoomed in
+        let result = client
+            .get("/api/v1/farms/clusters?zoom=3&min_lat=-20&min_lon=160&max_lat=-15&max_lon=-170")
+            .dispatch()
+            .await
+            .into_json::<ApiFarmClusters>()
+            .await
+            .expect("failed to deserialize clusters");
+        assert!(result.farms.is_empty());
+        assert_eq!(3, result.clusters.iter().map(|c| c.count).sum::<usize>());
+        let result = client
+            .get("/api/v1/farms/clusters?zoom=14&min_lat=-18&min_lon=169.5&max_lat=-17&max_lon=170.5")
+            .dispatch()
+            .await
+            .into_json::<ApiFarmClusters>()
+            .await
+            .expect("failed to deserialize clusters");
+        assert!(result.clusters.is_empty());
+        assert_eq!(1, result.farms.len());
+        let response = client
+            .get("/api/v1/farms/clusters?zoom=30&min_lat=-18&min_lon=169.5&max_lat=-17&max_lon=170.5")
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        for ext_id in ext_ids {
+            let response = client
+                .delete(format!("/api/v1/farms/{}", ext_id))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
 }
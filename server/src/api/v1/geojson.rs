@@ -0,0 +1,406 @@
+use crate::api::Result as ApiResult;
+use crate::api::v1::contact::NewApiContact;
+use crate::api::v1::error::ValidationError as ValidationApiError;
+use crate::api::v1::farms::{ApiFarmPatch, FullApiFarm};
+use crate::api::v1::ident::SysAdmin;
+use crate::api::v1::opening_hours::{NewApiOpeningHours, requested_instant};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use database::FarmDB;
+use database::farm::{FarmUpdate, NewFarm, NewFarmEntry};
+use database::location::NewGeoLocation;
+use database::user::User;
+use rocket::serde::json::Json;
+use rocket::{get, post};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![export_farms, import_farms]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFeatureCollection<T> {
+    pub r#type: String,
+    pub features: Vec<T>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiPoint {
+    pub r#type: String,
+    /// Longitude and latitude, in this order.
+    pub coordinates: [f64; 2],
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmFeature {
+    pub r#type: String,
+    pub id: String,
+    pub geometry: ApiPoint,
+    pub properties: FullApiFarm,
+}
+
+impl From<FullApiFarm> for ApiFarmFeature {
+    fn from(value: FullApiFarm) -> Self {
+        Self {
+            r#type: "Feature".to_string(),
+            id: value.id.clone(),
+            geometry: ApiPoint {
+                r#type: "Point".to_string(),
+                coordinates: [value.lon, value.lat],
+            },
+            properties: value,
+        }
+    }
+}
+
+/// Feature of an import. Farms with an `id`, in the feature or its properties, are updated, all
+/// others are created. Properties left out are not touched on update.
+#[derive(Deserialize)]
+struct ImportFeature {
+    #[serde(default)]
+    id: Option<String>,
+    geometry: Option<ApiPoint>,
+    properties: ImportProperties,
+}
+
+#[derive(Deserialize)]
+struct ImportProperties {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    shop_types: Option<Vec<String>>,
+    #[serde(default)]
+    opening_hours: Option<Vec<NewApiOpeningHours>>,
+    #[serde(default)]
+    contact: Option<NewApiContact>,
+}
+
+/// Outcome of an import. Features with errors are skipped, all others are applied.
+#[derive(Serialize, Deserialize)]
+pub struct ApiImportReport {
+    pub created: Vec<String>,
+    pub updated: Vec<String>,
+    pub errors: Vec<ApiFeatureError>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiFeatureError {
+    /// Position of the feature in the collection.
+    pub feature: usize,
+    pub id: Option<String>,
+    pub invalid_fields: HashMap<String, Vec<String>>,
+}
+
+enum ImportOutcome {
+    Created(String),
+    Updated(String),
+}
+
+/// All farms as GeoJSON `FeatureCollection` of points with the `FullApiFarm` as properties.
+#[get("/farms.geojson")]
+async fn export_farms(db: FarmDB) -> ApiResult<Json<ApiFeatureCollection<ApiFarmFeature>>> {
+    let at = requested_instant(None, None);
+    let farm_ids = database::farm::list_farms(&db, Default::default())
+        .await?
+        .into_iter()
+        .map(|farm| farm.id)
+        .collect();
+    let features = database::farm::load_full_farms(&db, farm_ids)
+        .await?
+        .into_iter()
+        .map(|full_farm| FullApiFarm::new(full_farm, at).into())
+        .collect();
+    Ok(Json(ApiFeatureCollection {
+        r#type: "FeatureCollection".to_string(),
+        features,
+    }))
+}
+
+/// Creates and updates farms from a GeoJSON `FeatureCollection` of points. Created farms are
+/// administrated by the importing sysadmin.
+#[post("/farms.geojson", data = "<collection>")]
+async fn import_farms(db: FarmDB, sysadmin: SysAdmin, collection: Json<Value>) -> ApiResult<Json<ApiImportReport>> {
+    let mut report = ApiImportReport {
+        created: vec![],
+        updated: vec![],
+        errors: vec![],
+    };
+    let features = match collection.into_inner() {
+        Value::Object(mut collection) if collection.get("type") == Some(&Value::from("FeatureCollection")) => {
+            collection.remove("features")
+        }
+        _ => None,
+    };
+    let Some(Value::Array(features)) = features else {
+        return Err(ValidationApiError::for_field("features", "Expected a FeatureCollection").into());
+    };
+    for (index, feature) in features.into_iter().enumerate() {
+        let id = feature
+            .pointer("/properties/id")
+            .or(feature.get("id"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+        match import_feature(&db, &sysadmin.0, feature).await? {
+            Ok(ImportOutcome::Created(id)) => report.created.push(id),
+            Ok(ImportOutcome::Updated(id)) => report.updated.push(id),
+            Err(invalid_fields) => report.errors.push(ApiFeatureError {
+                feature: index,
+                id,
+                invalid_fields,
+            }),
+        }
+    }
+    Ok(Json(report))
+}
+
+/// Imports a single feature. Invalid features are reported as `Err` of the inner result, database
+/// failures abort the whole import.
+async fn import_feature(
+    db: &FarmDB,
+    sysadmin: &User,
+    feature: Value,
+) -> ApiResult<Result<ImportOutcome, HashMap<String, Vec<String>>>> {
+    let feature: ImportFeature = match serde_json::from_value(feature) {
+        Ok(feature) => feature,
+        Err(err) => {
+            return Ok(Err(ValidationApiError::for_field("feature", err.to_string()).into_invalid_fields()));
+        }
+    };
+    if let Some(geometry) = &feature.geometry
+        && geometry.r#type != "Point"
+    {
+        let message = format!("Expected a Point but got {}", geometry.r#type);
+        return Ok(Err(ValidationApiError::for_field("geometry", message).into_invalid_fields()));
+    }
+    let properties = feature.properties;
+    let ext_id = properties.id.or(feature.id);
+    let farm_id = match &ext_id {
+        Some(ext_id) => {
+            let Some(farm_id) = parse_ext_id(ext_id) else {
+                return Ok(Err(
+                    ValidationApiError::for_field("id", format!("Invalid farm id `{ext_id}`")).into_invalid_fields(),
+                ));
+            };
+            let Some(farm_id) = database::farm::id_from_ext_id(db, farm_id).await? else {
+                return Ok(Err(
+                    ValidationApiError::for_field("id", format!("Unknown farm `{ext_id}`")).into_invalid_fields(),
+                ));
+            };
+            Some(farm_id)
+        }
+        None => None,
+    };
+    let patch = ApiFarmPatch {
+        name: properties.name.map(|name| name.trim().to_string()),
+        lat: feature.geometry.as_ref().map(|g| g.coordinates[1]),
+        lon: feature.geometry.as_ref().map(|g| g.coordinates[0]),
+        shop_types: properties.shop_types,
+        opening_hours: properties.opening_hours,
+    };
+    let known_shop_types = match farm_id {
+        Some(farm_id) => database::farm::assignable_shop_types(db, farm_id).await?,
+        None => database::shop_type::list_shop_types(db, false).await?,
+    };
+    let mut errors = patch
+        .validate(&known_shop_types)
+        .err()
+        .map(ValidationApiError::into_invalid_fields)
+        .unwrap_or_default();
+    let mut contact = properties.contact;
+    if let Some(contact) = &mut contact {
+        contact.sanitize();
+        if let Err(err) = contact.validate() {
+            errors.extend(
+                err.into_invalid_fields()
+                    .into_iter()
+                    .map(|(field, messages)| (format!("contact.{field}"), messages)),
+            );
+        }
+    }
+    if farm_id.is_none() {
+        if patch.name.is_none() {
+            errors.insert("name".to_string(), vec!["Required for new farms".to_string()]);
+        }
+        if patch.lat.is_none() {
+            errors.insert("geometry".to_string(), vec!["Required for new farms".to_string()]);
+        }
+    }
+    if !errors.is_empty() {
+        return Ok(Err(errors));
+    }
+
+    match farm_id {
+        Some(farm_id) => {
+            let update = FarmUpdate {
+                contact: contact.map(|contact| contact.into_new_contact(farm_id)),
+                ..patch.into_update(farm_id, &known_shop_types)
+            };
+            database::farm::update_any_farm(db, farm_id, update).await?;
+            Ok(Ok(ImportOutcome::Updated(ext_id.unwrap_or_default())))
+        }
+        None => {
+            // the farm id is assigned once the farm is created
+            let entry = NewFarmEntry {
+                farm: NewFarm {
+                    name: patch.name.clone().unwrap_or_default(),
+                },
+                location: NewGeoLocation {
+                    lat: patch.lat.unwrap_or_default(),
+                    lon: patch.lon.unwrap_or_default(),
+                },
+                details: FarmUpdate {
+                    contact: contact.map(|contact| contact.into_new_contact(0)),
+                    ..patch.into_update(0, &known_shop_types)
+                },
+            };
+            let farm = database::farm::create_farm_entry(db, sysadmin, entry).await?;
+            Ok(Ok(ImportOutcome::Created(URL_SAFE.encode(farm.ext_id))))
+        }
+    }
+}
+
+fn parse_ext_id(ext_id: &str) -> Option<Uuid> {
+    URL_SAFE
+        .decode(ext_id)
+        .ok()
+        .and_then(|bytes| Uuid::from_slice(&bytes).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::geojson::{ApiFarmFeature, ApiFeatureCollection, ApiImportReport, parse_ext_id};
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::set_sysadmin;
+    use database::{FarmDB, farm, user};
+    use rocket::http::Status;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn geojson_import_export() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        let admin = create_test_user(&client, "geojson_admin", password).await;
+        set_sysadmin(&db, admin.id, true)
+            .await
+            .expect("failed to make user a sysadmin");
+        let admin_token = login_user(&client, &admin.username, password).await;
+        let other = create_test_user(&client, "geojson_other", password).await;
+        let other_token = login_user(&client, &other.username, password).await;
+
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": [
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [3.36, -54.42] },
+                    "properties": {
+                        "name": "F geojson",
+                        "shop_types": ["store"],
+                        "contact": { "email": "farm@example.com" }
+                    }
+                },
+                {
+                    "type": "Feature",
+                    "geometry": { "type": "Point", "coordinates": [200.0, -54.42] },
+                    "properties": { "name": "F geojson invalid", "shop_types": ["unknown"] }
+                }
+            ]
+        });
+        let response = client
+            .post("/api/v1/farms.geojson")
+            .body(collection.to_string())
+            .auth(&other_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let report = client
+            .post("/api/v1/farms.geojson")
+            .body(collection.to_string())
+            .auth(&admin_token)
+            .dispatch()
+            .await
+            .into_json::<ApiImportReport>()
+            .await
+            .expect("failed to deserialize import report");
+        assert_eq!(1, report.created.len());
+        assert!(report.updated.is_empty());
+        assert_eq!(1, report.errors.len());
+        assert_eq!(1, report.errors[0].feature);
+        assert!(report.errors[0].invalid_fields.contains_key("location"));
+        assert!(report.errors[0].invalid_fields.contains_key("shop_types"));
+        let farm_id = report.created[0].clone();
+
+        let exported = client
+            .get("/api/v1/farms.geojson")
+            .dispatch()
+            .await
+            .into_json::<ApiFeatureCollection<ApiFarmFeature>>()
+            .await
+            .expect("failed to deserialize feature collection");
+        let feature = exported
+            .features
+            .iter()
+            .find(|f| f.id == farm_id)
+            .expect("imported farm is exported");
+        assert_eq!([3.36, -54.42], feature.geometry.coordinates);
+        assert_eq!("F geojson", feature.properties.name);
+        assert_eq!(vec!["store"], feature.properties.shop_types);
+        assert!(feature.properties.contact.is_some());
+
+        // features with a known id update the farm
+        let collection = json!({
+            "type": "FeatureCollection",
+            "features": [
+                { "type": "Feature", "id": farm_id, "geometry": null, "properties": { "name": "F geojson renamed" } }
+            ]
+        });
+        let report = client
+            .post("/api/v1/farms.geojson")
+            .body(collection.to_string())
+            .auth(&admin_token)
+            .dispatch()
+            .await
+            .into_json::<ApiImportReport>()
+            .await
+            .expect("failed to deserialize import report");
+        assert_eq!(vec![farm_id.clone()], report.updated);
+        assert!(report.errors.is_empty());
+        let exported = client
+            .get("/api/v1/farms.geojson")
+            .dispatch()
+            .await
+            .into_json::<ApiFeatureCollection<ApiFarmFeature>>()
+            .await
+            .expect("failed to deserialize feature collection");
+        let feature = exported
+            .features
+            .iter()
+            .find(|f| f.id == farm_id)
+            .expect("imported farm is exported");
+        assert_eq!("F geojson renamed", feature.properties.name);
+        assert_eq!(vec!["store"], feature.properties.shop_types);
+
+        // the importing sysadmin administrates the farm but is no farm owner
+        let farm_id = parse_ext_id(&farm_id).expect("valid farm id");
+        let farm_id = farm::id_from_ext_id(&db, farm_id)
+            .await
+            .expect("failed to look up farm")
+            .expect("farm exists");
+        assert!(farm::delete_farm(&db, admin.id, farm_id).await.expect("failed to delete farm"));
+        user::delete(&db, admin.id)
+            .await
+            .expect("failed to delete user");
+        user::delete(&db, other.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
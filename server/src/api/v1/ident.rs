@@ -14,6 +14,7 @@ use serde::{Deserialize, Serialize};
 
 #[cfg(not(test))]
 use std::env;
+use crate::api::v1::error::ApiError;
 use crate::api::v1::error::ApiError::WrongCredentials;
 
 #[cfg(not(test))]
@@ -77,6 +78,9 @@ pub async fn login_jwt(
     if !check_login(&db, username.clone(), credentials.0.password).await? {
         return Err(WrongCredentials);
     };
+    if database::user::by_username(&db, username.clone()).await?.is_some_and(|user| user.locked) {
+        return Err(ApiError::Forbidden);
+    }
 
     if let Ok(token) = create_jwt(username) {
         Ok(Some(token))
@@ -85,6 +89,7 @@ pub async fn login_jwt(
     }
 }
 
+/// User logged in with a valid token, unless locked.
 pub struct UserLogin(pub User);
 
 #[async_trait]
@@ -101,7 +106,9 @@ impl<'r> FromRequest<'r> for UserLogin {
             database::user::by_username(&db, username)
                 .await
                 .ok()
-                .flatten().map(UserLogin)
+                .flatten()
+                .filter(|user| !user.locked)
+                .map(UserLogin)
                 .or_forward(Status::Unauthorized)
         } else {
             Outcome::Forward(Status::Unauthorized)
@@ -125,6 +132,45 @@ impl<'r> FromRequest<'r> for FarmOwner {
     }
 }
 
+/// User allowed to administrate all users and farms.
+pub struct SysAdmin(pub User);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for SysAdmin {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let user = try_outcome!(request.guard::<UserLogin>().await).0;
+        if user.sysadmin != 0 {
+            Outcome::Success(SysAdmin(user))
+        } else {
+            Outcome::Forward(Status::Forbidden)
+        }
+    }
+}
+
+/// Farm authenticated by an API key in the `X-Api-Key` header, for devices like vending machines.
+pub struct FarmApiKey(pub i32);
+
+#[async_trait]
+impl<'r> FromRequest<'r> for FarmApiKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let db = try_outcome!(request.guard::<FarmDB>().await);
+        if let Some(key) = request.headers().get_one("X-Api-Key") {
+            database::api_key::farm_of_api_key(&db, key.to_string())
+                .await
+                .ok()
+                .flatten()
+                .map(FarmApiKey)
+                .or_forward(Status::Unauthorized)
+        } else {
+            Outcome::Forward(Status::Unauthorized)
+        }
+    }
+}
+
 fn username_from_valid_jwt_token(jwt_token: &str) -> Option<String> {
     let decoding_key = DecodingKey::from_secret(JWT_SECRET.as_bytes());
     let validation = Validation::new(Algorithm::HS512);
@@ -0,0 +1,492 @@
+//! Locations of a farm, like the shop at the farm and a vending machine down the road. The first
+//! location of a farm is its main location, moved by farm updates. Each location may have its own
+//! shop types and opening hours, otherwise it has those of the farm.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::farms::ApiFarmPatch;
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::opening_hours::{ApiOpenStatus, ApiOpeningHours, NewApiOpeningHours, requested_instant};
+use crate::api::v1::types::{ApiTimeZone, ApiTimestamp, ExtId};
+use crate::schedule::open_status;
+use crate::validation::{StringLengthCriteria, StringValidator, Validator};
+use chrono::DateTime;
+use chrono_tz::Tz;
+use database::FarmDB;
+use database::farm::{OpeningHours, OpeningHoursException};
+use database::location::{FullLocation, LocationDeletion, LocationUpdate, NewGeoLocation};
+use rocket::serde::json::Json;
+use rocket::{delete, get, patch, post};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const MAX_LABEL_LENGTH: usize = 50;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![get_locations, add_location, update_location, delete_location]
+}
+
+/// Location of a farm. Empty `shop_types` or `opening_hours` mean those of the farm apply, which
+/// `open_status` takes into account together with the opening hours exceptions of the farm.
+#[derive(Serialize, Deserialize)]
+pub struct ApiFarmLocation {
+    pub id: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    pub shop_types: Vec<String>,
+    pub opening_hours: Vec<ApiOpeningHours>,
+    pub open_status: ApiOpenStatus,
+}
+
+impl ApiFarmLocation {
+    pub fn new(
+        value: FullLocation,
+        farm_hours: &[OpeningHours],
+        exceptions: &[OpeningHoursException],
+        at: DateTime<Tz>,
+    ) -> Self {
+        let hours = if value.opening_hours.is_empty() {
+            farm_hours
+        } else {
+            &value.opening_hours
+        };
+        let open_status = open_status(hours, exceptions, at).into();
+        Self {
+            id: value.id,
+            label: value.label,
+            lat: value.lat,
+            lon: value.lon,
+            shop_types: value.shop_types.into_iter().map(|t| t.name).collect(),
+            opening_hours: value.opening_hours.into_iter().map(From::from).collect(),
+            open_status,
+        }
+    }
+}
+
+/// Additional location. Shop types and opening hours left out are those of the farm.
+#[derive(Serialize, Deserialize)]
+pub struct NewApiFarmLocation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub lat: f64,
+    pub lon: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shop_types: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opening_hours: Option<Vec<NewApiOpeningHours>>,
+}
+
+/// Partial location change for `PATCH`. Fields left out of the request body are not touched, an
+/// empty `label` removes the label. Empty `shop_types` or `opening_hours` fall back to the farm's.
+#[derive(Serialize, Deserialize, Default)]
+pub struct ApiFarmLocationPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lat: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lon: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shop_types: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub opening_hours: Option<Vec<NewApiOpeningHours>>,
+}
+
+/// Validates the label and the fields shared with farm patches, converted to a location update.
+fn validated_update(
+    farm_id: i32,
+    label: Option<Option<String>>,
+    patch: ApiFarmPatch,
+    known_shop_types: &[database::farm::ShopType],
+) -> Result<LocationUpdate, ValidationApiError> {
+    let mut errors = match patch.validate(known_shop_types) {
+        Ok(()) => HashMap::new(),
+        Err(err) => err.into_invalid_fields(),
+    };
+    if let Some(Some(label)) = &label {
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::max(MAX_LABEL_LENGTH));
+        if let Err(err) = validator.validate(label) {
+            errors.insert("label".to_string(), err.messages);
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ValidationApiError::for_fields(errors));
+    }
+    let update = patch.into_update(farm_id, known_shop_types);
+    Ok(LocationUpdate {
+        label,
+        position: update.location,
+        shop_types: update.shop_types,
+        opening_hours: update.opening_hours,
+    })
+}
+
+async fn load_location(db: &FarmDB, farm_id: i32, location_id: i32, at: DateTime<Tz>) -> ApiResult<ApiFarmLocation> {
+    let farm = database::farm::load_full_farm(db, farm_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    farm.locations
+        .into_iter()
+        .find(|l| l.id == location_id)
+        .map(|l| ApiFarmLocation::new(l, &farm.opening_hours, &farm.opening_hours_exceptions, at))
+        .ok_or(ApiError::NotFound)
+}
+
+/// All locations of the farm, the main location first. `open_status` is evaluated at `at` or now,
+/// in `tz` or UTC.
+#[get("/<farm_id>/locations?<at>&<tz>&<page..>")]
+async fn get_locations(
+    db: FarmDB,
+    farm_id: ExtId,
+    at: Option<ApiTimestamp>,
+    tz: Option<ApiTimeZone>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiFarmLocation>>> {
+    let request = page.validate(&["main"])?;
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let farm = database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let at = requested_instant(at, tz);
+    // the main location is the one added first
+    let page = ApiPage::of_sorted(&request, farm.locations, |l| l.id)?;
+    Ok(Json(page.map(|l| ApiFarmLocation::new(l, &farm.opening_hours, &farm.opening_hours_exceptions, at))))
+}
+
+#[post("/<farm_id>/locations", data = "<location>")]
+async fn add_location(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    location: Json<NewApiFarmLocation>,
+) -> ApiResult<Json<ApiFarmLocation>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let location = location.into_inner();
+    let patch = ApiFarmPatch {
+        lat: Some(location.lat),
+        lon: Some(location.lon),
+        shop_types: location.shop_types,
+        opening_hours: location.opening_hours,
+        ..Default::default()
+    };
+    let known_shop_types = database::farm::assignable_shop_types(&db, farm_id).await?;
+    let update = validated_update(farm_id, Some(location.label), patch, &known_shop_types)?;
+    let position = NewGeoLocation {
+        lat: location.lat,
+        lon: location.lon,
+    };
+    let update = LocationUpdate { position: None, ..update };
+    let location_id = database::location::add_location(&db, farm_owner.0.id, farm_id, position, update)
+        .await?
+        .ok_or(ApiError::Forbidden)?;
+    load_location(&db, farm_id, location_id, requested_instant(None, None))
+        .await
+        .map(Json)
+}
+
+#[patch("/<farm_id>/locations/<location_id>", data = "<patch>")]
+async fn update_location(
+    db: FarmDB,
+    farm_id: ExtId,
+    location_id: i32,
+    farm_owner: FarmOwner,
+    patch: Json<ApiFarmLocationPatch>,
+) -> ApiResult<Json<ApiFarmLocation>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let patch = patch.into_inner();
+    let label = patch
+        .label
+        .map(|label| if label.is_empty() { None } else { Some(label) });
+    let farm_patch = ApiFarmPatch {
+        lat: patch.lat,
+        lon: patch.lon,
+        shop_types: patch.shop_types,
+        opening_hours: patch.opening_hours,
+        ..Default::default()
+    };
+    let known_shop_types = database::farm::assignable_shop_types(&db, farm_id).await?;
+    let update = validated_update(farm_id, label, farm_patch, &known_shop_types)?;
+    if !database::location::update_location(&db, farm_owner.0.id, farm_id, location_id, update).await? {
+        return Err(ApiError::Forbidden);
+    }
+    load_location(&db, farm_id, location_id, requested_instant(None, None))
+        .await
+        .map(Json)
+}
+
+/// Deletes a location. The last location of a farm can't be deleted.
+#[delete("/<farm_id>/locations/<location_id>")]
+async fn delete_location(
+    db: FarmDB,
+    farm_id: ExtId,
+    location_id: i32,
+    farm_owner: FarmOwner,
+) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    match database::location::delete_location(&db, farm_owner.0.id, farm_id, location_id).await? {
+        LocationDeletion::Deleted => Ok(()),
+        LocationDeletion::NotAdmin => Err(ApiError::Forbidden),
+        LocationDeletion::NotFound => Err(ApiError::NotFound),
+        LocationDeletion::LastLocation => {
+            Err(ValidationApiError::for_field("location", "A farm needs at least one location").into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farms::{ApiFarm, FullApiFarm, NewApiFarm};
+    use crate::api::v1::locations::{ApiFarmLocation, ApiFarmLocationPatch, NewApiFarmLocation};
+    use crate::api::v1::opening_hours::{NewApiOpeningHours, NewApiOpeningHoursException};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use chrono::{NaiveDate, NaiveTime};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    /// Whether the farm `F farm_locations` is open at the instant `at`.
+    async fn open_at(client: &Client, at: &str) -> bool {
+        client
+            .get(format!("/api/v1/farms/find_near?lat=-62.0&lon=-54.0&radius=500&open_at={at}&tz=UTC"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items
+            .iter()
+            .any(|f| f.name == "F farm_locations")
+    }
+
+    #[tokio::test]
+    async fn farm_locations() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farm_locations", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F farm_locations".to_string(),
+            lat: -62.0,
+            lon: -58.0,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let ext_id = farm.id;
+
+        let vending_machine = NewApiFarmLocation {
+            label: Some("Vending machine".to_string()),
+            lat: -62.0,
+            lon: -50.0,
+            shop_types: Some(vec!["vending machine".to_string()]),
+            opening_hours: Some(vec![NewApiOpeningHours {
+                weekday: 0,
+                open: NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+                close: NaiveTime::from_hms_opt(0, 0, 0).unwrap(),
+                overnight: true,
+            }]),
+        };
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/locations"))
+            .body(serde_json::to_string(&vending_machine).expect("failed to serialize location"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let location = response
+            .into_json::<ApiFarmLocation>()
+            .await
+            .expect("failed to deserialize location");
+        assert_eq!(Some("Vending machine"), location.label.as_deref());
+        assert_eq!(vec!["vending machine"], location.shop_types);
+
+        let invalid = NewApiFarmLocation {
+            label: Some("x".repeat(51)),
+            lat: 91.0,
+            lon: 0.0,
+            shop_types: None,
+            opening_hours: None,
+        };
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/locations"))
+            .body(serde_json::to_string(&invalid).expect("failed to serialize location"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // either location is found
+        for lon in [-58.0, -50.0] {
+            let farms = client
+                .get(format!("/api/v1/farms/find_near?lat=-62.0&lon={lon}&radius=5"))
+                .dispatch()
+                .await
+                .into_json::<ApiPage<ApiFarm>>()
+                .await
+                .expect("failed to deserialize farms")
+                .items;
+            let names: Vec<&str> = farms.iter().map(|f| f.name.as_str()).collect();
+            assert_eq!(vec!["F farm_locations"], names);
+        }
+
+        let full_farm = client
+            .get(format!("/api/v1/farms/{ext_id}"))
+            .dispatch()
+            .await
+            .into_json::<FullApiFarm>()
+            .await
+            .expect("failed to deserialize full farm");
+        assert_eq!(-58.0, full_farm.lon);
+        assert_eq!(2, full_farm.locations.len());
+        assert_eq!(None, full_farm.locations[0].label);
+        assert_eq!(Some("Vending machine"), full_farm.locations[1].label.as_deref());
+        assert!(full_farm.locations[0].opening_hours.is_empty());
+        assert_eq!(1, full_farm.locations[1].opening_hours.len());
+
+        // open while any location is, the vending machine keeps its own hours
+        let farm_hours = vec![NewApiOpeningHours {
+            weekday: 1,
+            open: NaiveTime::from_hms_opt(8, 0, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(12, 0, 0).unwrap(),
+            overnight: false,
+        }];
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/opening_hours"))
+            .body(serde_json::to_string(&farm_hours).expect("failed to serialize opening hours"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(open_at(&client, "2025-06-02T03:00:00Z").await);
+        assert!(open_at(&client, "2025-06-03T10:00:00Z").await);
+        assert!(!open_at(&client, "2025-06-03T13:00:00Z").await);
+        // exceptions of the farm apply to all locations
+        let closure = NewApiOpeningHoursException {
+            start_date: NaiveDate::from_ymd_opt(2025, 6, 9).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(2025, 6, 9).unwrap(),
+            open: None,
+            close: None,
+            overnight: false,
+            description: None,
+        };
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/opening_hours/exceptions"))
+            .body(serde_json::to_string(&closure).expect("failed to serialize exception"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(!open_at(&client, "2025-06-09T03:00:00Z").await);
+
+        let patch = ApiFarmLocationPatch {
+            label: Some(String::new()),
+            lat: Some(-62.0),
+            lon: Some(-51.0),
+            ..Default::default()
+        };
+        let response = client
+            .patch(format!("/api/v1/farms/{ext_id}/locations/{}", location.id))
+            .body(serde_json::to_string(&patch).expect("failed to serialize location patch"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let changed = response
+            .into_json::<ApiFarmLocation>()
+            .await
+            .expect("failed to deserialize location");
+        assert_eq!(None, changed.label);
+        assert_eq!(-51.0, changed.lon);
+        assert_eq!(1, changed.opening_hours.len());
+
+        let main_id = full_farm.locations[0].id;
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/locations/{main_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let locations = client
+            .get(format!("/api/v1/farms/{ext_id}/locations"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarmLocation>>()
+            .await
+            .expect("failed to deserialize locations")
+            .items;
+        assert_eq!(1, locations.len());
+        assert_eq!(location.id, locations[0].id);
+        // no location uses the hours of the farm anymore
+        assert!(open_at(&client, "2025-06-02T03:00:00Z").await);
+        assert!(!open_at(&client, "2025-06-03T10:00:00Z").await);
+
+        // only admins of the farm learn which locations exist
+        let other = create_test_user(&client, "farm_locations_other", password).await;
+        make_farmowner(&db, other.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let other_token = login_user(&client, &other.username, password).await;
+        for location_id in [location.id, -1] {
+            let response = client
+                .delete(format!("/api/v1/farms/{ext_id}/locations/{location_id}"))
+                .auth(&other_token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Forbidden);
+        }
+        user::delete(&db, other.id)
+            .await
+            .expect("failed to delete user");
+
+        // the last location stays
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/locations/{}", location.id))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/locations/-1"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -0,0 +1,532 @@
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::types::{ApiTimeZone, ApiTimestamp, ExtId};
+use crate::schedule::{OpenStatus, open_status};
+use chrono::{DateTime, Datelike, FixedOffset, NaiveDate, NaiveTime, Timelike, Utc};
+use chrono_tz::Tz;
+use database::FarmDB;
+use database::farm::{
+    FarmUpdate, NewOpeningHours, NewOpeningHoursException, OpeningHours, OpeningHoursException,
+};
+use database::osm::OsmSchedule;
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, put};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const SECONDS_PER_DAY: u32 = 24 * 60 * 60;
+const SECONDS_PER_WEEK: u32 = 7 * SECONDS_PER_DAY;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        get_opening_hours,
+        replace_opening_hours,
+        get_open_status,
+        get_exceptions,
+        add_exception,
+        delete_exception,
+        get_osm_opening_hours,
+        replace_osm_opening_hours,
+    ]
+}
+
+/// Opening hours as returned by the API. `weekday` counts from Monday (`0`) to Sunday (`6`).
+/// Ranges with `overnight` set close on the following day.
+#[derive(Serialize, Deserialize)]
+pub struct ApiOpeningHours {
+    pub farm_id: i32,
+    pub weekday: i32,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    pub overnight: bool,
+}
+
+impl From<OpeningHours> for ApiOpeningHours {
+    fn from(value: OpeningHours) -> Self {
+        Self {
+            farm_id: value.farm_id,
+            weekday: value.weekday,
+            open: value.open,
+            close: value.close,
+            overnight: value.close <= value.open,
+        }
+    }
+}
+
+/// Exception from the weekly opening hours for every day from `start_date` to `end_date`. The farm
+/// is closed on those days if `open` and `close` are missing.
+#[derive(Serialize, Deserialize)]
+pub struct ApiOpeningHoursException {
+    pub id: i32,
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    pub open: Option<NaiveTime>,
+    pub close: Option<NaiveTime>,
+    pub overnight: bool,
+    pub description: Option<String>,
+}
+
+impl From<OpeningHoursException> for ApiOpeningHoursException {
+    fn from(value: OpeningHoursException) -> Self {
+        Self {
+            id: value.id,
+            start_date: value.start_date,
+            end_date: value.end_date,
+            open: value.open,
+            close: value.close,
+            overnight: value.open.zip(value.close).is_some_and(|(open, close)| close <= open),
+            description: value.description,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewApiOpeningHoursException {
+    pub start_date: NaiveDate,
+    pub end_date: NaiveDate,
+    #[serde(default)]
+    pub open: Option<NaiveTime>,
+    #[serde(default)]
+    pub close: Option<NaiveTime>,
+    #[serde(default)]
+    pub overnight: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+impl NewApiOpeningHoursException {
+    fn validate(&self) -> Result<(), ValidationApiError> {
+        let mut errors = HashMap::new();
+        if self.end_date < self.start_date {
+            errors.insert(
+                "end_date".to_string(),
+                vec![format!("End date has to be on or after {}", self.start_date)],
+            );
+        }
+        match (self.open, self.close) {
+            (Some(open), Some(close)) => {
+                let range = NewApiOpeningHours {
+                    weekday: 0,
+                    open,
+                    close,
+                    overnight: self.overnight,
+                };
+                let range_errors = range.validate();
+                if !range_errors.is_empty() {
+                    errors.insert("close".to_string(), range_errors);
+                }
+            }
+            (None, None) => {}
+            _ => {
+                errors.insert(
+                    "open".to_string(),
+                    vec!["`open` and `close` have to be given together".to_string()],
+                );
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationApiError::for_fields(errors))
+        }
+    }
+
+    fn into_new_exception(self, farm_id: i32) -> NewOpeningHoursException {
+        NewOpeningHoursException {
+            farm_id,
+            start_date: self.start_date,
+            end_date: self.end_date,
+            open: self.open,
+            close: self.close,
+            description: self.description,
+        }
+    }
+}
+
+/// Result of replacing the opening hours of a farm by an OSM `opening_hours` value.
+#[derive(Serialize, Deserialize)]
+pub struct ApiOsmOpeningHours {
+    pub opening_hours: Vec<ApiOpeningHours>,
+    pub opening_hours_exceptions: Vec<ApiOpeningHoursException>,
+    pub ignored_rules: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiOpenStatus {
+    pub is_open: bool,
+    pub next_opening: Option<DateTime<FixedOffset>>,
+    pub closes_at: Option<DateTime<FixedOffset>>,
+}
+
+impl From<OpenStatus> for ApiOpenStatus {
+    fn from(value: OpenStatus) -> Self {
+        Self {
+            is_open: value.is_open,
+            next_opening: value.next_opening.map(|t| t.fixed_offset()),
+            closes_at: value.closes_at.map(|t| t.fixed_offset()),
+        }
+    }
+}
+
+/// Resolves the instant and time zone of open status queries. Defaults to now in UTC.
+pub fn requested_instant(at: Option<ApiTimestamp>, tz: Option<ApiTimeZone>) -> DateTime<Tz> {
+    let tz = tz.map(|tz| tz.0).unwrap_or(Tz::UTC);
+    at.map(|at| at.0.with_timezone(&tz))
+        .unwrap_or_else(|| Utc::now().with_timezone(&tz))
+}
+
+/// A single range of a new weekly schedule. Ranges past midnight have to set `overnight`
+/// explicitly, otherwise `open` has to be before `close`. Overnight ranges with equal opening and
+/// closing time are open for 24 hours.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct NewApiOpeningHours {
+    pub weekday: i32,
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+    #[serde(default)]
+    pub overnight: bool,
+}
+
+impl NewApiOpeningHours {
+    pub fn into_new_opening_hours(self, farm_id: i32) -> NewOpeningHours {
+        NewOpeningHours {
+            farm_id,
+            weekday: self.weekday,
+            open: self.open,
+            close: self.close,
+        }
+    }
+
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        if !(0..=6).contains(&self.weekday) {
+            errors.push(format!(
+                "Weekday has to be between 0 and 6 but was {}",
+                self.weekday
+            ));
+        }
+        if self.open == self.close && !self.overnight {
+            errors.push(
+                "Opening and closing time must not be equal, set `overnight` to open for 24 hours"
+                    .to_string(),
+            );
+        } else if self.overnight && self.close > self.open {
+            errors.push(format!(
+                "Overnight range has to close before {} on the next day",
+                self.open
+            ));
+        } else if !self.overnight && self.close < self.open {
+            errors.push(format!(
+                "Opening time {} has to be before closing time {}, set `overnight` for ranges past midnight",
+                self.open, self.close
+            ));
+        }
+        errors
+    }
+
+    /// Seconds since Monday 00:00 this range covers. Ranges wrapping from Sunday into Monday are
+    /// split in two.
+    fn week_intervals(&self) -> Vec<(u32, u32)> {
+        let start = self.weekday as u32 * SECONDS_PER_DAY + self.open.num_seconds_from_midnight();
+        let mut end = self.weekday as u32 * SECONDS_PER_DAY + self.close.num_seconds_from_midnight();
+        if self.overnight {
+            end += SECONDS_PER_DAY;
+        }
+        if end > SECONDS_PER_WEEK {
+            vec![(start, SECONDS_PER_WEEK), (0, end - SECONDS_PER_WEEK)]
+        } else {
+            vec![(start, end)]
+        }
+    }
+}
+
+/// Validates a full weekly schedule. Errors are keyed by the position of the offending range, e.g.
+/// `opening_hours[2]`.
+pub fn validate_schedule(hours: &[NewApiOpeningHours]) -> HashMap<String, Vec<String>> {
+    let mut errors: HashMap<String, Vec<String>> = HashMap::new();
+    for (index, range) in hours.iter().enumerate() {
+        let range_errors = range.validate();
+        if !range_errors.is_empty() {
+            errors.insert(schedule_key(index), range_errors);
+        }
+    }
+    if !errors.is_empty() {
+        return errors;
+    }
+    let intervals: Vec<Vec<(u32, u32)>> = hours.iter().map(|h| h.week_intervals()).collect();
+    for (index, current) in intervals.iter().enumerate() {
+        for (other_index, other) in intervals.iter().enumerate().take(index) {
+            let overlaps = current.iter().any(|(start, end)| {
+                other
+                    .iter()
+                    .any(|(other_start, other_end)| start < other_end && other_start < end)
+            });
+            if overlaps {
+                errors
+                    .entry(schedule_key(index))
+                    .or_default()
+                    .push(format!("Overlaps with opening_hours[{other_index}]"));
+            }
+        }
+    }
+    errors
+}
+
+fn schedule_key(index: usize) -> String {
+    format!("opening_hours[{index}]")
+}
+
+#[get("/<farm_id>/opening_hours")]
+async fn get_opening_hours(db: FarmDB, farm_id: ExtId) -> ApiResult<Json<Vec<ApiOpeningHours>>> {
+    if let Some(farm_id) = database::farm::id_from_ext_id(&db, farm_id.0).await?
+        && let Some(full_farm) = database::farm::load_full_farm(&db, farm_id).await?
+    {
+        return Ok(Json(
+            full_farm.opening_hours.into_iter().map(From::from).collect(),
+        ));
+    }
+    Err(ApiError::NotFound)
+}
+
+#[put("/<farm_id>/opening_hours", data = "<hours>")]
+async fn replace_opening_hours(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    hours: Json<Vec<NewApiOpeningHours>>,
+) -> ApiResult<Json<Vec<ApiOpeningHours>>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let hours = hours.into_inner();
+    let errors = validate_schedule(&hours);
+    if !errors.is_empty() {
+        return Err(ValidationApiError::for_fields(errors).into());
+    }
+    let update = FarmUpdate {
+        opening_hours: Some(
+            hours
+                .into_iter()
+                .map(|h| h.into_new_opening_hours(farm_id))
+                .collect(),
+        ),
+        ..Default::default()
+    };
+    if !database::farm::update_farm(&db, farm_owner.0.id, farm_id, update).await? {
+        return Err(ApiError::Forbidden);
+    }
+    database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .map(|farm| Json(farm.opening_hours.into_iter().map(From::from).collect()))
+        .ok_or(ApiError::NotFound)
+}
+
+#[get("/<farm_id>/open_status?<at>&<tz>")]
+async fn get_open_status(
+    db: FarmDB,
+    farm_id: ExtId,
+    at: Option<ApiTimestamp>,
+    tz: Option<ApiTimeZone>,
+) -> ApiResult<Json<ApiOpenStatus>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let hours = database::farm::load_opening_hours(&db, vec![farm_id]).await?;
+    let exceptions = database::farm::load_opening_hours_exceptions(&db, vec![farm_id]).await?;
+    let status = open_status(&hours, &exceptions, requested_instant(at, tz));
+    Ok(Json(status.into()))
+}
+
+/// Exceptions of the farm, sorted by start date.
+#[get("/<farm_id>/opening_hours/exceptions?<page..>")]
+async fn get_exceptions(
+    db: FarmDB,
+    farm_id: ExtId,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiOpeningHoursException>>> {
+    let request = page.validate(&["start_date"])?;
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let exceptions = database::farm::load_opening_hours_exceptions(&db, vec![farm_id]).await?;
+    let page = ApiPage::of_sorted(&request, exceptions, |e| (e.start_date, e.id))?;
+    Ok(Json(page.map(ApiOpeningHoursException::from)))
+}
+
+#[post("/<farm_id>/opening_hours/exceptions", data = "<exception>")]
+async fn add_exception(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    exception: Json<NewApiOpeningHoursException>,
+) -> ApiResult<Json<ApiOpeningHoursException>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let exception = exception.into_inner();
+    exception.validate()?;
+    database::farm::add_opening_hours_exception(&db, farm_owner.0.id, exception.into_new_exception(farm_id))
+        .await?
+        .map(|exception| Json(exception.into()))
+        .ok_or(ApiError::Forbidden)
+}
+
+#[delete("/<farm_id>/opening_hours/exceptions/<exception_id>")]
+async fn delete_exception(
+    db: FarmDB,
+    farm_id: ExtId,
+    exception_id: i32,
+    farm_owner: FarmOwner,
+) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    if !database::farm::delete_opening_hours_exception(&db, farm_owner.0.id, farm_id, exception_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
+
+/// Validates a parsed OSM schedule with the same rules as schedules and exceptions given as JSON.
+pub fn validate_osm_schedule(schedule: &OsmSchedule) -> Result<(), ValidationApiError> {
+    let hours: Vec<NewApiOpeningHours> = schedule
+        .weekly
+        .iter()
+        .map(|r| NewApiOpeningHours {
+            weekday: r.weekday,
+            open: r.open,
+            close: r.close,
+            overnight: r.close <= r.open,
+        })
+        .collect();
+    let mut errors = validate_schedule(&hours);
+    let exception_errors: Vec<String> = schedule
+        .exceptions
+        .iter()
+        .flat_map(|e| e.hours.iter().map(move |(open, close)| (e, *open, *close)))
+        .flat_map(|(e, open, close)| {
+            NewApiOpeningHours {
+                weekday: 0,
+                open,
+                close,
+                overnight: close <= open,
+            }
+            .validate()
+            .into_iter()
+            .map(move |err| format!("{} ({})", err, e.start_date))
+        })
+        .collect();
+    if !exception_errors.is_empty() {
+        errors.insert("opening_hours_exceptions".to_string(), exception_errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+#[get("/<farm_id>/opening_hours/osm")]
+async fn get_osm_opening_hours(db: FarmDB, farm_id: ExtId) -> ApiResult<String> {
+    if let Some(farm_id) = database::farm::id_from_ext_id(&db, farm_id.0).await?
+        && let Some(full_farm) = database::farm::load_full_farm(&db, farm_id).await?
+    {
+        let schedule = OsmSchedule::from_rows(&full_farm.opening_hours, &full_farm.opening_hours_exceptions);
+        return Ok(database::osm::to_osm(&schedule));
+    }
+    Err(ApiError::NotFound)
+}
+
+/// Replaces weekly hours and all exceptions of a farm. Dates without a year are resolved against
+/// `year`, which defaults to the current year.
+#[put("/<farm_id>/opening_hours/osm?<year>", data = "<opening_hours>")]
+async fn replace_osm_opening_hours(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    year: Option<i32>,
+    opening_hours: String,
+) -> ApiResult<Json<ApiOsmOpeningHours>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let year = year.unwrap_or_else(|| Utc::now().year());
+    let schedule = database::osm::parse(&opening_hours, year)
+        .map_err(|err| ValidationApiError::for_field("opening_hours", err.to_string()))?;
+    validate_osm_schedule(&schedule)?;
+    let update = FarmUpdate {
+        opening_hours: Some(schedule.opening_hours(farm_id)),
+        opening_hours_exceptions: Some(schedule.opening_hours_exceptions(farm_id)),
+        ..Default::default()
+    };
+    if !database::farm::update_farm(&db, farm_owner.0.id, farm_id, update).await? {
+        return Err(ApiError::Forbidden);
+    }
+    let full_farm = database::farm::load_full_farm(&db, farm_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(ApiOsmOpeningHours {
+        opening_hours: full_farm.opening_hours.into_iter().map(From::from).collect(),
+        opening_hours_exceptions: full_farm
+            .opening_hours_exceptions
+            .into_iter()
+            .map(From::from)
+            .collect(),
+        ignored_rules: schedule.ignored_rules,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::opening_hours::{validate_schedule, NewApiOpeningHours};
+    use chrono::NaiveTime;
+
+    fn range(weekday: i32, open: u32, close: u32, overnight: bool) -> NewApiOpeningHours {
+        NewApiOpeningHours {
+            weekday,
+            open: NaiveTime::from_hms_opt(open, 0, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(close, 0, 0).unwrap(),
+            overnight,
+        }
+    }
+
+    #[test]
+    fn schedule_validation() {
+        let valid = vec![
+            range(0, 8, 12, false),
+            range(0, 13, 18, false),
+            range(1, 8, 18, false),
+            range(4, 20, 2, true),
+            range(5, 8, 12, false),
+            range(6, 22, 6, true),
+            range(0, 6, 8, false),
+            range(2, 0, 0, true),
+        ];
+        assert!(validate_schedule(&valid).is_empty());
+
+        let errors = validate_schedule(&[range(7, 8, 12, false), range(0, 12, 8, false)]);
+        assert_eq!(errors.len(), 2);
+        assert!(errors.contains_key("opening_hours[0]"));
+        assert!(errors.contains_key("opening_hours[1]"));
+
+        let errors = validate_schedule(&[range(2, 8, 12, true), range(2, 8, 8, false)]);
+        assert_eq!(errors.len(), 2);
+
+        let errors = validate_schedule(&[range(3, 8, 12, false), range(3, 11, 14, false)]);
+        assert_eq!(
+            errors.get("opening_hours[1]"),
+            Some(&vec!["Overlaps with opening_hours[0]".to_string()])
+        );
+
+        let errors = validate_schedule(&[range(3, 22, 4, true), range(4, 3, 10, false)]);
+        assert!(errors.contains_key("opening_hours[1]"));
+
+        let errors = validate_schedule(&[range(0, 5, 10, false), range(6, 22, 6, true)]);
+        assert!(errors.contains_key("opening_hours[1]"));
+    }
+}
@@ -0,0 +1,208 @@
+//! Paging of collection endpoints. Clients pass `limit`, `sort` and the `next_cursor` of the
+//! previous page as `cursor`, and get the items in an [ApiPage]. Cursors hold the sort key of the
+//! last item of a page, so items added or removed meanwhile don't shift later pages.
+
+use crate::api::v1::error::ValidationError as ValidationApiError;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use database::page::Page;
+use rocket::FromForm;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Items per page when no `limit` is given.
+pub const DEFAULT_LIMIT: i64 = 20;
+/// Most items per page.
+pub const MAX_LIMIT: i64 = 100;
+
+/// Query parameters of collection endpoints, taken as trailing parameter `<page..>`.
+#[derive(FromForm, Default)]
+pub struct Pagination {
+    pub limit: Option<i64>,
+    /// `next_cursor` of the previous page.
+    pub cursor: Option<String>,
+    pub sort: Option<String>,
+}
+
+/// Contents of a cursor. The sort is kept to reject cursors of pages sorted differently.
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: String,
+    after: serde_json::Value,
+}
+
+/// Validated [Pagination].
+pub struct PageRequest {
+    pub limit: i64,
+    /// One of the sorts of the endpoint.
+    pub sort: &'static str,
+    after: Option<serde_json::Value>,
+}
+
+const CURSOR_MESSAGE: &str = "Expected the `next_cursor` of a page with the same sort";
+
+impl Pagination {
+    /// Checks the parameters against the sorts the endpoint supports, the first one being the
+    /// default.
+    pub fn validate(self, sorts: &[&'static str]) -> Result<PageRequest, ValidationApiError> {
+        let mut errors = HashMap::new();
+        let limit = self.limit.unwrap_or(DEFAULT_LIMIT);
+        if !(1..=MAX_LIMIT).contains(&limit) {
+            errors.insert("limit".to_string(), vec![format!("Expected a limit between 1 and {MAX_LIMIT}")]);
+        }
+        let sort = match self.sort.as_deref() {
+            None => Some(sorts[0]),
+            Some(sort) => sorts.iter().copied().find(|s| *s == sort),
+        };
+        if sort.is_none() {
+            errors.insert("sort".to_string(), vec![format!("Expected one of {}", sorts.join(", "))]);
+        }
+        let cursor = self.cursor.map(|cursor| {
+            URL_SAFE_NO_PAD
+                .decode(cursor)
+                .ok()
+                .and_then(|json| serde_json::from_slice::<Cursor>(&json).ok())
+                .filter(|cursor| sort == Some(cursor.sort.as_str()))
+        });
+        if let Some(None) = cursor {
+            errors.insert("cursor".to_string(), vec![CURSOR_MESSAGE.to_string()]);
+        }
+        match sort {
+            Some(sort) if errors.is_empty() => Ok(PageRequest {
+                limit,
+                sort,
+                after: cursor.flatten().map(|cursor| cursor.after),
+            }),
+            _ => Err(ValidationApiError::for_fields(errors)),
+        }
+    }
+}
+
+impl PageRequest {
+    /// Rows to fetch, one more than returned to learn whether there is a next page.
+    pub fn fetch_limit(&self) -> i64 {
+        self.limit + 1
+    }
+
+    /// Sort key of the last item of the previous page, `None` for the first page.
+    pub fn after<K: DeserializeOwned>(&self) -> Result<Option<K>, ValidationApiError> {
+        self.after
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()
+            .map_err(|_| ValidationApiError::for_field("cursor", CURSOR_MESSAGE))
+    }
+
+    fn cursor<K: Serialize>(&self, key: K) -> String {
+        let cursor = Cursor {
+            sort: self.sort.to_string(),
+            after: serde_json::to_value(key).expect("sort keys serialize to json"),
+        };
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&cursor).expect("cursors serialize to json"))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiPage<T> {
+    pub items: Vec<T>,
+    /// Cursor of the next page, missing on the last page.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+    /// Number of items on all pages, which may change while paging.
+    pub total_estimate: i64,
+}
+
+impl<T> ApiPage<T> {
+    /// Page of rows fetched with [PageRequest::fetch_limit]. `key` gives the sort key a page
+    /// following a row starts after.
+    pub fn new<K: Serialize>(request: &PageRequest, page: Page<T>, key: impl Fn(&T) -> K) -> Self {
+        let mut items = page.rows;
+        let mut next_cursor = None;
+        if items.len() as i64 > request.limit {
+            items.truncate(request.limit as usize);
+            next_cursor = items.last().map(|item| request.cursor(key(item)));
+        }
+        Self {
+            items,
+            next_cursor,
+            total_estimate: page.total,
+        }
+    }
+
+    /// Page of a whole collection sorted by `key`, for collections small enough to load at once.
+    pub fn of_sorted<K>(request: &PageRequest, rows: Vec<T>, key: impl Fn(&T) -> K) -> Result<Self, ValidationApiError>
+    where
+        K: Serialize + DeserializeOwned + PartialOrd,
+    {
+        let total = rows.len() as i64;
+        let after = request.after::<K>()?;
+        let rows = rows
+            .into_iter()
+            .filter(|row| after.as_ref().is_none_or(|after| key(row) > *after))
+            .take(request.fetch_limit() as usize)
+            .collect();
+        Ok(Self::new(request, Page { rows, total }, key))
+    }
+
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> ApiPage<U> {
+        ApiPage {
+            items: self.items.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            total_estimate: self.total_estimate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::pagination::{ApiPage, Pagination};
+
+    fn pagination(limit: Option<i64>, cursor: Option<String>, sort: Option<&str>) -> Pagination {
+        Pagination { limit, cursor, sort: sort.map(str::to_string) }
+    }
+
+    #[test]
+    fn pages_of_sorted_rows() {
+        let rows = || vec![("a", 1), ("b", 2), ("b", 3), ("c", 4), ("d", 5)];
+        let key = |row: &(&str, i32)| (row.0.to_string(), row.1);
+        let request = pagination(Some(2), None, None).validate(&["name"]).expect("valid pagination");
+        let first = ApiPage::of_sorted(&request, rows(), key).expect("valid cursor");
+        assert_eq!(vec![("a", 1), ("b", 2)], first.items);
+        assert_eq!(5, first.total_estimate);
+
+        let request = pagination(Some(2), first.next_cursor, Some("name"))
+            .validate(&["name"])
+            .expect("valid pagination");
+        let second = ApiPage::of_sorted(&request, rows(), key).expect("valid cursor");
+        assert_eq!(vec![("b", 3), ("c", 4)], second.items);
+
+        let request = pagination(Some(2), second.next_cursor, None).validate(&["name"]).expect("valid pagination");
+        let last = ApiPage::of_sorted(&request, rows(), key).expect("valid cursor");
+        assert_eq!(vec![("d", 5)], last.items);
+        assert_eq!(None, last.next_cursor);
+    }
+
+    #[test]
+    fn invalid_pagination() {
+        let sorts = ["name", "newest"];
+        let request = pagination(Some(1), None, None).validate(&sorts).expect("valid pagination");
+        let cursor = ApiPage::of_sorted(&request, vec![1, 2], |row| *row)
+            .expect("valid cursor")
+            .next_cursor;
+        assert!(cursor.is_some());
+        for (limit, cursor, sort) in [
+            (Some(0), None, None),
+            (Some(101), None, None),
+            (None, None, Some("size")),
+            (None, Some("not a cursor".to_string()), None),
+            // cursor of another sort
+            (None, cursor.clone(), Some("newest")),
+        ] {
+            assert!(pagination(limit, cursor, sort).validate(&sorts).is_err());
+        }
+        // cursor with a key of another type
+        let request = pagination(None, cursor, None).validate(&sorts).expect("valid pagination");
+        assert!(ApiPage::of_sorted(&request, vec!["a"], |row| row.to_string()).is_err());
+    }
+}
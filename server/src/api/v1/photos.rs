@@ -0,0 +1,349 @@
+//! Photos of farms, uploaded by farm admins as `multipart/form-data` with the image in the `photo`
+//! field. Every photo is served in all [PhotoSize]s.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::types::ExtId;
+use crate::photos::{PhotoError, PhotoSize, PhotoStorage, StorageError, photo_key, process_photo};
+use database::FarmDB;
+use database::photo::{FarmPhoto, NewFarmPhoto};
+use rocket::form::{Form, FromForm};
+use rocket::fs::TempFile;
+use rocket::http::{ContentType, Header};
+use rocket::serde::json::Json;
+use rocket::tokio::io::AsyncReadExt;
+use rocket::tokio::task;
+use rocket::{Responder, State, delete, get, post};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list_photos, upload_photo, delete_photo]
+}
+
+pub fn file_routes() -> Vec<rocket::Route> {
+    rocket::routes![photo_file]
+}
+
+/// Photo files never change, a new upload gets a new URL.
+const PHOTO_MAX_AGE: u32 = 31_536_000;
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiPhoto {
+    pub id: i32,
+    /// Size of the original, upright image.
+    pub width: i32,
+    pub height: i32,
+    pub urls: ApiPhotoUrls,
+}
+
+/// URL of the photo in every size. The longest edge of `large` is at most 1280 pixels, of `medium`
+/// 640 and of `small` 200.
+#[derive(Serialize, Deserialize)]
+pub struct ApiPhotoUrls {
+    pub original: String,
+    pub large: String,
+    pub medium: String,
+    pub small: String,
+}
+
+impl From<FarmPhoto> for ApiPhoto {
+    fn from(value: FarmPhoto) -> Self {
+        let url = |size| format!("/api/v1/photos/{}", photo_key(value.storage_id, size, &value.format));
+        Self {
+            id: value.id,
+            width: value.width,
+            height: value.height,
+            urls: ApiPhotoUrls {
+                original: url(PhotoSize::Original),
+                large: url(PhotoSize::Large),
+                medium: url(PhotoSize::Medium),
+                small: url(PhotoSize::Small),
+            },
+        }
+    }
+}
+
+#[derive(FromForm)]
+struct PhotoUpload<'r> {
+    photo: TempFile<'r>,
+}
+
+#[derive(Responder)]
+struct PhotoFile {
+    data: (ContentType, Vec<u8>),
+    cache_control: Header<'static>,
+}
+
+/// Removes all sizes of the photo from the storage.
+pub(crate) async fn delete_photo_files(storage: &dyn PhotoStorage, photo: &FarmPhoto) -> Result<(), StorageError> {
+    for size in PhotoSize::ALL {
+        storage.delete(&photo_key(photo.storage_id, size, &photo.format)).await?;
+    }
+    Ok(())
+}
+
+/// Photos of the farm in the order they were uploaded.
+#[get("/<farm_id>/photos?<page..>")]
+async fn list_photos(db: FarmDB, farm_id: ExtId, page: Pagination) -> ApiResult<Json<ApiPage<ApiPhoto>>> {
+    let request = page.validate(&["uploaded"])?;
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let photos = database::photo::list_photos(&db, farm_id).await?;
+    Ok(Json(ApiPage::of_sorted(&request, photos, |p| p.id)?.map(From::from)))
+}
+
+/// Takes a JPEG, PNG or WebP image. Uploads larger than the `file` limit of Rocket are rejected.
+#[post("/<farm_id>/photos", data = "<upload>")]
+async fn upload_photo(
+    db: FarmDB,
+    storage: &State<Box<dyn PhotoStorage>>,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    upload: Form<PhotoUpload<'_>>,
+) -> ApiResult<Json<ApiPhoto>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    let mut data = Vec::new();
+    upload
+        .photo
+        .open()
+        .await
+        .map_err(StorageError::from)?
+        .read_to_end(&mut data)
+        .await
+        .map_err(StorageError::from)?;
+    let processed = task::spawn_blocking(move || process_photo(&data))
+        .await
+        .expect("photo processing panicked")
+        .map_err(|error| match error {
+            PhotoError::UnsupportedFormat => ValidationApiError::for_field("photo", "Expected a JPEG, PNG or WebP image"),
+            PhotoError::Image(error) => ValidationApiError::for_field("photo", format!("Cannot read the image: {error}")),
+        })?;
+
+    let storage_id = Uuid::new_v4();
+    let extension = processed.extension();
+    for (size, file) in processed.files {
+        storage.put(&photo_key(storage_id, size, extension), file).await?;
+    }
+    let photo = NewFarmPhoto {
+        farm_id,
+        storage_id,
+        format: extension.to_string(),
+        width: processed.width as i32,
+        height: processed.height as i32,
+    };
+    let photo = database::photo::create_photo(&db, farm_owner.0.id, photo).await?;
+    let Some(photo) = photo else {
+        // the user stopped being an admin of the farm in the meantime
+        for size in PhotoSize::ALL {
+            storage.delete(&photo_key(storage_id, size, extension)).await?;
+        }
+        return Err(ApiError::Forbidden);
+    };
+    Ok(Json(photo.into()))
+}
+
+#[delete("/<farm_id>/photos/<photo_id>")]
+async fn delete_photo(
+    db: FarmDB,
+    storage: &State<Box<dyn PhotoStorage>>,
+    farm_id: ExtId,
+    photo_id: i32,
+    farm_owner: FarmOwner,
+) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    let photo = database::photo::delete_photo(&db, farm_owner.0.id, farm_id, photo_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    delete_photo_files(storage.inner().as_ref(), &photo).await?;
+    Ok(())
+}
+
+/// One size of a photo, as linked in [ApiPhotoUrls].
+#[get("/<storage_id>/<file>")]
+async fn photo_file(
+    storage: &State<Box<dyn PhotoStorage>>,
+    storage_id: Uuid,
+    file: &str,
+) -> ApiResult<PhotoFile> {
+    let (size, extension) = file.split_once('.').ok_or(ApiError::NotFound)?;
+    let size = PhotoSize::from_name(size).ok_or(ApiError::NotFound)?;
+    let content_type = match extension {
+        "jpg" => ContentType::JPEG,
+        "png" => ContentType::PNG,
+        _ => return Err(ApiError::NotFound),
+    };
+    let data = storage
+        .get(&photo_key(storage_id, size, extension))
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(PhotoFile {
+        data: (content_type, data),
+        cache_control: Header::new("Cache-Control", format!("public, max-age={PHOTO_MAX_AGE}, immutable")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farms::{ApiFarm, FullApiFarm, NewApiFarm};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::photos::ApiPhoto;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use image::{DynamicImage, ImageFormat, RgbImage};
+    use rocket::http::{ContentType, Status};
+    use std::io::Cursor;
+
+    const BOUNDARY: &str = "photo-boundary";
+
+    fn multipart(content_type: &str, data: &[u8]) -> Vec<u8> {
+        let mut body = format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"photo\"; filename=\"shop\"\r\n\
+            Content-Type: {content_type}\r\n\r\n"
+        )
+        .into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(format!("\r\n--{BOUNDARY}--\r\n").as_bytes());
+        body
+    }
+
+    #[tokio::test]
+    async fn photo_upload() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "photo_upload", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F photo_upload".to_string(),
+            lat: -36.2,
+            lon: -82.4,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let ext_id = farm.id;
+        let form_type = ContentType::new("multipart", "form-data").with_params(("boundary", BOUNDARY));
+
+        let mut png = Vec::new();
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(800, 400, image::Rgb([30, 90, 30])))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .expect("failed to encode png");
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/photos"))
+            .header(form_type.clone())
+            .body(multipart("image/png", &png))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/photos"))
+            .header(form_type.clone())
+            .body(multipart("text/plain", b"not an image"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/photos"))
+            .header(form_type.clone())
+            .body(multipart("image/png", &png))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let photo = response
+            .into_json::<ApiPhoto>()
+            .await
+            .expect("failed to deserialize photo");
+        assert_eq!((800, 400), (photo.width, photo.height));
+        assert!(photo.urls.small.ends_with("/small.jpg"));
+
+        let full_farm = client
+            .get(format!("/api/v1/farms/{ext_id}"))
+            .dispatch()
+            .await
+            .into_json::<FullApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        assert_eq!(1, full_farm.photos.len());
+        assert_eq!(photo.urls.medium, full_farm.photos[0].urls.medium);
+
+        let response = client.get(&photo.urls.small).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::JPEG));
+        let small = response.into_bytes().await.expect("failed to read photo");
+        let small = image::load_from_memory(&small).expect("failed to decode photo");
+        assert_eq!((200, 100), (small.width(), small.height()));
+        let response = client.get(photo.urls.small.replace("small", "huge")).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/photos/{}", photo.id))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get(&photo.urls.original).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        let photos = client
+            .get(format!("/api/v1/farms/{ext_id}/photos"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiPhoto>>()
+            .await
+            .expect("failed to deserialize photos")
+            .items;
+        assert!(photos.is_empty());
+
+        // photos still stored are removed together with the farm
+        let photo = client
+            .post(format!("/api/v1/farms/{ext_id}/photos"))
+            .header(form_type)
+            .body(multipart("image/png", &png))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiPhoto>()
+            .await
+            .expect("failed to deserialize photo");
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client.get(&photo.urls.large).dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -0,0 +1,674 @@
+//! Products a farm sells. Categories come from a fixed catalogue and are referenced by name. A
+//! product may be limited to yearly recurring availability periods, otherwise it is available all
+//! year.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::FarmOwner;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::api::v1::types::ExtId;
+use crate::validation::{RegexValidator, StringLengthCriteria, StringValidator, Validator};
+use database::FarmDB;
+use chrono::{DateTime, Datelike, Months, NaiveDate, Utc};
+use database::product::{Availability, FullProduct, NewAvailability, NewProduct, Product, ProductCategory};
+use rocket::serde::json::Json;
+use rocket::{delete, get, post, put};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![list_products, create_product, replace_product, replace_availability, delete_product]
+}
+
+pub fn category_routes() -> Vec<rocket::Route> {
+    rocket::routes![list_categories]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiProduct {
+    pub id: i32,
+    pub name: String,
+    pub category: Option<String>,
+    /// Unit the price refers to, like `kg` or `6 eggs`.
+    pub unit: String,
+    /// In the minor unit of the currency, like cents.
+    pub price: Option<i32>,
+    /// ISO 4217 code.
+    pub currency: String,
+    pub organic: bool,
+    /// Empty if available all year.
+    pub availability: Vec<ApiAvailability>,
+    /// Quantity in stock, not set if not tracked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stock_updated: Option<DateTime<Utc>>,
+}
+
+impl ApiProduct {
+    pub fn new(product: Product, category: Option<String>, availability: Vec<Availability>) -> Self {
+        Self {
+            id: product.id,
+            category,
+            name: product.name,
+            unit: product.unit,
+            price: product.price,
+            currency: product.currency,
+            organic: product.organic,
+            availability: availability.into_iter().map(From::from).collect(),
+            stock: product.stock,
+            stock_updated: product.stock_updated,
+        }
+    }
+}
+
+impl From<FullProduct> for ApiProduct {
+    fn from(value: FullProduct) -> Self {
+        Self::new(value.product, value.category, value.availability)
+    }
+}
+
+/// Yearly recurring availability period with first and last day as `MM-DD`.
+#[derive(Serialize, Deserialize)]
+pub struct ApiAvailability {
+    pub id: i32,
+    pub start: String,
+    pub end: String,
+}
+
+impl From<Availability> for ApiAvailability {
+    fn from(value: Availability) -> Self {
+        Self {
+            id: value.id,
+            start: format!("{:02}-{:02}", value.start_month, value.start_day),
+            end: format!("{:02}-{:02}", value.end_month, value.end_day),
+        }
+    }
+}
+
+/// Day of the year as `(month, day)`.
+type MonthDay = (u32, u32);
+
+/// Yearly recurring availability period, either whole months like `{"start_month": 6,
+/// "end_month": 6}` for June, or days as `MM-DD` like `{"start": "12-01", "end": "01-15"}`. A period
+/// ending before its start spans the turn of the year.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum NewApiAvailability {
+    Months { start_month: u32, end_month: u32 },
+    Days { start: String, end: String },
+}
+
+impl NewApiAvailability {
+    /// First and last day.
+    fn days(&self) -> Result<(MonthDay, MonthDay), String> {
+        match self {
+            Self::Months { start_month, end_month } => {
+                let last_day = last_day_of_month(*end_month)
+                    .filter(|_| (1..=12).contains(start_month))
+                    .ok_or_else(|| "Expected months between 1 and 12".to_string())?;
+                Ok(((*start_month, 1), (*end_month, last_day)))
+            }
+            Self::Days { start, end } => Ok((parse_month_day(start)?, parse_month_day(end)?)),
+        }
+    }
+
+    pub fn into_new_availability(self, product_id: i32) -> Option<NewAvailability> {
+        let ((start_month, start_day), (end_month, end_day)) = self.days().ok()?;
+        Some(NewAvailability {
+            product_id,
+            start_month: start_month as i32,
+            start_day: start_day as i32,
+            end_month: end_month as i32,
+            end_day: end_day as i32,
+        })
+    }
+}
+
+/// Last day of the month in a leap year, so periods may end on February 29th.
+fn last_day_of_month(month: u32) -> Option<u32> {
+    let first = NaiveDate::from_ymd_opt(2024, month, 1)?;
+    Some((first + Months::new(1)).pred_opt()?.day())
+}
+
+fn parse_month_day(value: &str) -> Result<MonthDay, String> {
+    value
+        .split_once('-')
+        .and_then(|(month, day)| Some((month.parse().ok()?, day.parse().ok()?)))
+        .filter(|(month, day)| NaiveDate::from_ymd_opt(2024, *month, *day).is_some())
+        .ok_or_else(|| format!("Expected a day as `MM-DD` but got `{value}`"))
+}
+
+pub fn validate_availability(periods: &[NewApiAvailability]) -> Result<(), ValidationApiError> {
+    let errors: HashMap<String, Vec<String>> = periods
+        .iter()
+        .enumerate()
+        .filter_map(|(index, period)| Some((format!("availability[{index}]"), vec![period.days().err()?])))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+/// New product or replacement of all fields of an existing one.
+#[derive(Serialize, Deserialize)]
+pub struct NewApiProduct {
+    pub name: String,
+    #[serde(default)]
+    pub category: Option<String>,
+    pub unit: String,
+    #[serde(default)]
+    pub price: Option<i32>,
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    #[serde(default)]
+    pub organic: bool,
+}
+
+fn default_currency() -> String {
+    "EUR".to_string()
+}
+
+impl NewApiProduct {
+    pub fn validate(&self, categories: &[ProductCategory]) -> Result<(), ValidationApiError> {
+        let mut errors = HashMap::new();
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::new(1, 100));
+        if let Err(err) = validator.validate(self.name.trim()) {
+            errors.insert("name".to_string(), err.messages);
+        }
+        let mut validator = StringValidator::new();
+        validator.add_criteria(StringLengthCriteria::new(1, 30));
+        if let Err(err) = validator.validate(self.unit.trim()) {
+            errors.insert("unit".to_string(), err.messages);
+        }
+        if let Some(category) = &self.category
+            && !categories.iter().any(|c| &c.name == category)
+        {
+            errors.insert("category".to_string(), vec![format!("Unknown category `{category}`")]);
+        }
+        if self.price.is_some_and(|price| price < 0) {
+            errors.insert("price".to_string(), vec!["Expected a price of at least 0".to_string()]);
+        }
+        let mut validator = StringValidator::new();
+        validator.add_criteria(RegexValidator::new("^[A-Z]{3}$").expect("Cannot parse currency regex"));
+        if validator.validate(&self.currency).is_err() {
+            errors.insert(
+                "currency".to_string(),
+                vec!["Expected a three letter ISO 4217 code like `EUR`".to_string()],
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationApiError::for_fields(errors))
+        }
+    }
+
+    pub fn into_new_product(self, farm_id: i32, categories: &[ProductCategory]) -> NewProduct {
+        NewProduct {
+            farm_id,
+            category_id: self
+                .category
+                .and_then(|name| categories.iter().find(|c| c.name == name))
+                .map(|c| c.id),
+            name: self.name.trim().to_string(),
+            unit: self.unit.trim().to_string(),
+            price: self.price,
+            currency: self.currency,
+            organic: self.organic,
+        }
+    }
+}
+
+/// Validates the product including that no other product of the farm has the same name.
+async fn validate_product(
+    db: &FarmDB,
+    farm_id: i32,
+    product: &NewApiProduct,
+    product_id: Option<i32>,
+    categories: &[ProductCategory],
+) -> ApiResult<()> {
+    let mut errors = match product.validate(categories) {
+        Ok(()) => HashMap::new(),
+        Err(err) => err.into_invalid_fields(),
+    };
+    let name = product.name.trim().to_string();
+    if !errors.contains_key("name")
+        && let Some(existing) = database::product::by_name(db, farm_id, name.clone()).await?
+        && Some(existing.id) != product_id
+    {
+        errors.insert("name".to_string(), vec![format!("Product `{name}` already exists")]);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors).into())
+    }
+}
+
+/// Names of the product categories, sorted by name.
+#[get("/?<page..>")]
+async fn list_categories(db: FarmDB, page: Pagination) -> ApiResult<Json<ApiPage<String>>> {
+    let request = page.validate(&["name"])?;
+    let categories = database::product::list_categories(&db).await?;
+    let page = ApiPage::of_sorted(&request, categories, |c| c.name.clone())?;
+    Ok(Json(page.map(|c| c.name)))
+}
+
+/// Products of the farm, sorted by name.
+#[get("/<farm_id>/products?<page..>")]
+async fn list_products(db: FarmDB, farm_id: ExtId, page: Pagination) -> ApiResult<Json<ApiPage<ApiProduct>>> {
+    let request = page.validate(&["name"])?;
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let products = database::product::load_products(&db, vec![farm_id]).await?;
+    let page = ApiPage::of_sorted(&request, products, |p| (p.product.name.clone(), p.product.id))?;
+    Ok(Json(page.map(ApiProduct::from)))
+}
+
+#[post("/<farm_id>/products", data = "<product>")]
+async fn create_product(
+    db: FarmDB,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    product: Json<NewApiProduct>,
+) -> ApiResult<Json<ApiProduct>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let product = product.into_inner();
+    let categories = database::product::list_categories(&db).await?;
+    validate_product(&db, farm_id, &product, None, &categories).await?;
+    let category = product.category.clone();
+    database::product::create_product(&db, farm_owner.0.id, product.into_new_product(farm_id, &categories))
+        .await?
+        .map(|product| Json(ApiProduct::new(product, category, vec![])))
+        .ok_or(ApiError::Forbidden)
+}
+
+/// Replaces all fields of the product.
+#[put("/<farm_id>/products/<product_id>", data = "<product>")]
+async fn replace_product(
+    db: FarmDB,
+    farm_id: ExtId,
+    product_id: i32,
+    farm_owner: FarmOwner,
+    product: Json<NewApiProduct>,
+) -> ApiResult<Json<ApiProduct>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let product = product.into_inner();
+    let categories = database::product::list_categories(&db).await?;
+    validate_product(&db, farm_id, &product, Some(product_id), &categories).await?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    if database::product::update_product(&db, farm_owner.0.id, product_id, product.into_new_product(farm_id, &categories))
+        .await?
+        .is_none()
+    {
+        return Err(ApiError::NotFound);
+    }
+    load_product(&db, farm_id, product_id).await.map(Json)
+}
+
+/// Replaces the availability periods of the product. An empty list makes it available all year.
+#[put("/<farm_id>/products/<product_id>/availability", data = "<periods>")]
+async fn replace_availability(
+    db: FarmDB,
+    farm_id: ExtId,
+    product_id: i32,
+    farm_owner: FarmOwner,
+    periods: Json<Vec<NewApiAvailability>>,
+) -> ApiResult<Json<ApiProduct>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let periods = periods.into_inner();
+    validate_availability(&periods)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    let periods = periods
+        .into_iter()
+        .filter_map(|p| p.into_new_availability(product_id))
+        .collect();
+    if !database::product::replace_availability(&db, farm_owner.0.id, farm_id, product_id, periods).await? {
+        return Err(ApiError::NotFound);
+    }
+    load_product(&db, farm_id, product_id).await.map(Json)
+}
+
+async fn load_product(db: &FarmDB, farm_id: i32, product_id: i32) -> ApiResult<ApiProduct> {
+    database::product::load_products(db, vec![farm_id])
+        .await?
+        .into_iter()
+        .find(|p| p.product.id == product_id)
+        .map(ApiProduct::from)
+        .ok_or(ApiError::NotFound)
+}
+
+#[delete("/<farm_id>/products/<product_id>")]
+async fn delete_product(db: FarmDB, farm_id: ExtId, product_id: i32, farm_owner: FarmOwner) -> ApiResult<()> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    if !database::product::delete_product(&db, farm_owner.0.id, farm_id, product_id).await? {
+        return Err(ApiError::NotFound);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farms::{ApiFarm, FullApiFarm, NewApiFarm};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::products::{ApiProduct, NewApiAvailability, NewApiProduct, validate_availability};
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+
+    fn product(name: &str, category: Option<&str>, price: Option<i32>) -> NewApiProduct {
+        NewApiProduct {
+            name: name.to_string(),
+            category: category.map(str::to_string),
+            unit: "kg".to_string(),
+            price,
+            currency: "EUR".to_string(),
+            organic: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn product_api_crud() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "product_api_crud", password).await;
+        let other = create_test_user(&client, "product_api_crud_other", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        for user_id in [user.id, other.id] {
+            make_farmowner(&db, user_id)
+                .await
+                .expect("failed to make user a farm owner");
+        }
+        let token = login_user(&client, &user.username, password).await;
+        let other_token = login_user(&client, &other.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F product_api_crud".to_string(),
+            lat: -33.9,
+            lon: -78.8,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let ext_id = farm.id;
+
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/products"))
+            .body(serde_json::to_string(&product("White asparagus", Some("vegetables"), Some(1450))).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let asparagus = response
+            .into_json::<ApiProduct>()
+            .await
+            .expect("failed to deserialize product");
+        assert_eq!(Some("vegetables"), asparagus.category.as_deref());
+
+        let categories = client
+            .get("/api/v1/product_categories?limit=5")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<String>>()
+            .await
+            .expect("failed to deserialize categories");
+        assert_eq!(vec!["bakery", "beverages", "dairy", "eggs", "fruit"], categories.items);
+        let cursor = categories.next_cursor.expect("more categories");
+        let categories = client
+            .get(format!("/api/v1/product_categories?limit=5&cursor={cursor}"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<String>>()
+            .await
+            .expect("failed to deserialize categories");
+        assert_eq!(vec!["meat", "other", "vegetables"], categories.items);
+        assert_eq!(None, categories.next_cursor);
+
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/products"))
+            .body(serde_json::to_string(&product("White asparagus", Some("spaceships"), Some(-1))).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let body = response.into_string().await.expect("expected response");
+        assert!(body.contains("already exists") && body.contains("category") && body.contains("price"), "{body}");
+
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/products"))
+            .body(serde_json::to_string(&product("Eggs", None, None)).expect("failed to serialize product"))
+            .auth(&other_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Forbidden);
+
+        let mut organic = product("White asparagus", Some("vegetables"), None);
+        organic.organic = true;
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/products/{}", asparagus.id))
+            .body(serde_json::to_string(&organic).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let changed = response
+            .into_json::<ApiProduct>()
+            .await
+            .expect("failed to deserialize product");
+        assert!(changed.organic);
+        assert_eq!(None, changed.price);
+
+        let full_farm = client
+            .get(format!("/api/v1/farms/{ext_id}"))
+            .dispatch()
+            .await
+            .into_json::<FullApiFarm>()
+            .await
+            .expect("failed to deserialize full farm");
+        assert_eq!(1, full_farm.products.len());
+
+        for (query, expected) in [("?product=asparagus", 1), ("?product=ASPARAGUS_", 0)] {
+            let farms = client
+                .get(format!("/api/v1/farms{query}"))
+                .dispatch()
+                .await
+                .into_json::<ApiPage<ApiFarm>>()
+                .await
+                .expect("failed to deserialize farms")
+                .items;
+            let matching = farms.iter().filter(|f| f.name == "F product_api_crud").count();
+            assert_eq!(expected, matching, "{query}");
+        }
+        let farms = client
+            .get("/api/v1/farms/find_near?lat=-33.9&lon=-78.8&radius=5&product=aspara")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items;
+        assert_eq!(1, farms.len());
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/products/{}", asparagus.id))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let products = client
+            .get(format!("/api/v1/farms/{ext_id}/products"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiProduct>>()
+            .await
+            .expect("failed to deserialize products")
+            .items;
+        assert!(products.is_empty());
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        for user_id in [user.id, other.id] {
+            user::delete(&db, user_id)
+                .await
+                .expect("failed to delete user");
+        }
+    }
+
+    #[test]
+    fn availability_validation() {
+        let valid = [
+            NewApiAvailability::Months { start_month: 11, end_month: 2 },
+            NewApiAvailability::Days { start: "02-29".to_string(), end: "03-15".to_string() },
+        ];
+        assert!(validate_availability(&valid).is_ok());
+        assert_eq!(Some((2, 29)), valid[0].days().ok().map(|(_, end)| end));
+
+        let invalid = [
+            NewApiAvailability::Months { start_month: 0, end_month: 2 },
+            NewApiAvailability::Days { start: "02-30".to_string(), end: "03-15".to_string() },
+            NewApiAvailability::Days { start: "June".to_string(), end: "07-01".to_string() },
+        ];
+        let errors = validate_availability(&invalid)
+            .expect_err("expected invalid periods")
+            .into_invalid_fields();
+        assert_eq!(3, errors.len());
+    }
+
+    #[tokio::test]
+    async fn seasonal_products() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "seasonal_products", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F seasonal_products".to_string(),
+            lat: -34.5,
+            lon: -80.2,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let ext_id = farm.id;
+
+        let seasons = [
+            ("Asparagus", NewApiAvailability::Months { start_month: 4, end_month: 6 }),
+            ("Kale", NewApiAvailability::Days { start: "11-15".to_string(), end: "02-15".to_string() }),
+        ];
+        for (name, season) in seasons {
+            let created = client
+                .post(format!("/api/v1/farms/{ext_id}/products"))
+                .body(serde_json::to_string(&product(name, None, None)).expect("failed to serialize product"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiProduct>()
+                .await
+                .expect("failed to deserialize product");
+            let response = client
+                .put(format!("/api/v1/farms/{ext_id}/products/{}/availability", created.id))
+                .body(serde_json::to_string(&vec![season]).expect("failed to serialize availability"))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+            let changed = response
+                .into_json::<ApiProduct>()
+                .await
+                .expect("failed to deserialize product");
+            assert_eq!(1, changed.availability.len());
+        }
+        let products = client
+            .get(format!("/api/v1/farms/{ext_id}/products"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiProduct>>()
+            .await
+            .expect("failed to deserialize products")
+            .items;
+        assert_eq!("04-01", products[0].availability[0].start);
+        assert_eq!("06-30", products[0].availability[0].end);
+
+        let near = "/api/v1/farms/find_near?lat=-34.5&lon=-80.2&radius=5";
+        for (query, expected) in [
+            ("product=asparagus&available_from=2026-05-04&available_to=2026-05-10", 1),
+            ("product=asparagus&available_from=2026-06-29&available_to=2026-07-05", 1),
+            ("product=asparagus&available_from=2026-08-01", 0),
+            ("product=kale&available_from=2026-12-28&available_to=2027-01-03", 1),
+            ("product=kale&available_from=2026-02-10", 1),
+            ("product=kale&available_from=2026-07-01&available_to=2026-07-31", 0),
+            ("available_from=2026-09-01&available_to=2026-09-30", 0),
+            ("product=kale&available_from=2026-07-01&available_to=2027-06-30", 1),
+        ] {
+            let farms = client
+                .get(format!("{near}&{query}"))
+                .dispatch()
+                .await
+                .into_json::<ApiPage<ApiFarm>>()
+                .await
+                .expect("failed to deserialize farms")
+                .items;
+            assert_eq!(expected, farms.len(), "{query}");
+        }
+        let response = client
+            .get(format!("{near}&available_from=2026-05-10&available_to=2026-05-04"))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -0,0 +1,284 @@
+//! Search of farms by their name, the names of their products and their public address.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::farms::{ApiFarm, validate_position};
+use crate::api::v1::pagination::{ApiPage, PageRequest, Pagination};
+use database::FarmDB;
+use database::search::{FarmSearch, HIGHLIGHT_END, HIGHLIGHT_START, SearchHit, SearchOrder, prefix_query};
+use rocket::get;
+use rocket::serde::json::Json;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Sorts of search results, the default first.
+const SEARCH_SORTS: [&str; 2] = ["relevance", "distance"];
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![search_farms]
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ApiSearchResult {
+    #[serde(flatten)]
+    pub farm: ApiFarm,
+    pub relevance: f32,
+    pub highlights: ApiSearchHighlights,
+}
+
+/// Matched texts as HTML, with the matching words in `<mark>` elements. Products and address are
+/// only given if they contain matching words.
+#[derive(Serialize, Deserialize)]
+pub struct ApiSearchHighlights {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub products: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+impl From<SearchHit> for ApiSearchResult {
+    fn from(value: SearchHit) -> Self {
+        let matched = |text: String| text.contains(HIGHLIGHT_START).then(|| highlight_html(&text));
+        Self {
+            farm: ApiFarm {
+                distance_km: value.distance.map(|d| (d * 1000.0).round() / 1000.0),
+                ..value.farm.into()
+            },
+            relevance: value.relevance,
+            highlights: ApiSearchHighlights {
+                name: highlight_html(&value.name),
+                products: matched(value.products),
+                address: matched(value.address),
+            },
+        }
+    }
+}
+
+/// Escapes the text for HTML and turns the highlight markers into `<mark>` elements.
+fn highlight_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            HIGHLIGHT_START => html.push_str("<mark>"),
+            HIGHLIGHT_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+fn validated_search(
+    q: String,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    request: &PageRequest,
+) -> Result<FarmSearch, ValidationApiError> {
+    let mut errors = HashMap::new();
+    if prefix_query(&q).is_none() {
+        errors.insert("q".to_string(), vec!["Expected at least one word".to_string()]);
+    }
+    let near = match (lat, lon) {
+        (Some(lat), Some(lon)) => {
+            if let Some(messages) = validate_position(lat, lon) {
+                errors.insert("lat".to_string(), messages);
+            }
+            Some((lat, lon))
+        }
+        (None, None) => None,
+        _ => {
+            errors.insert("lat".to_string(), vec!["Expected both lat and lon".to_string()]);
+            None
+        }
+    };
+    let order = match request.sort {
+        "distance" if near.is_some() => SearchOrder::Distance,
+        "distance" => {
+            errors.insert("sort".to_string(), vec!["Sorting by distance needs lat and lon".to_string()]);
+            SearchOrder::Relevance
+        }
+        _ => SearchOrder::Relevance,
+    };
+    let offset = request.after::<i64>()?.unwrap_or_default();
+    if errors.is_empty() {
+        Ok(FarmSearch { text: q, near, order, limit: request.fetch_limit(), offset })
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+/// Farms with words starting with every word of `q`, or resembling `q` despite typos. Sorted by
+/// `relevance` or, with `sort=distance`, by the distance from `lat` and `lon`. Given a position, the
+/// distance to the closest location of each farm is included.
+#[get("/search?<q>&<lat>&<lon>&<page..>")]
+async fn search_farms(
+    db: FarmDB,
+    q: String,
+    lat: Option<f64>,
+    lon: Option<f64>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiSearchResult>>> {
+    let request = page.validate(&SEARCH_SORTS)?;
+    let search = validated_search(q, lat, lon, &request).map_err(ApiError::from)?;
+    // ranked hits have no stable sort key, so their cursors hold the number of hits to skip
+    let next_offset = search.offset + request.limit;
+    let hits = database::search::search_farms(&db, search).await?;
+    Ok(Json(ApiPage::new(&request, hits, |_| next_offset).map(ApiSearchResult::from)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::contact::NewApiContact;
+    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::products::NewApiProduct;
+    use crate::api::v1::search::{ApiSearchResult, highlight_html};
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+    use rocket::local::asynchronous::Client;
+
+    #[test]
+    fn highlights_are_escaped() {
+        assert_eq!(
+            "<mark>Smith</mark> &amp; Sons &lt;b&gt;",
+            highlight_html("\u{2}Smith\u{3} & Sons <b>")
+        );
+    }
+
+    async fn search(client: &Client, query: &str) -> Vec<ApiSearchResult> {
+        let response = client.get(format!("/api/v1/farms/search?{query}")).dispatch().await;
+        assert_eq!(response.status(), Status::Ok, "{query}");
+        response
+            .into_json::<ApiPage<ApiSearchResult>>()
+            .await
+            .expect("failed to deserialize search results")
+            .items
+    }
+
+    #[tokio::test]
+    async fn farm_search() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farm_search", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let mut ext_ids = Vec::new();
+        for (name, lat, lon) in [
+            ("Zyxwood Orchard", -37.0, -83.0),
+            ("Quorvale Dairy", -37.5, -83.0),
+            ("Zyxwood Creamery", -39.0, -83.0),
+        ] {
+            let new_farm = NewApiFarm { name: name.to_string(), lat, lon };
+            let farm = client
+                .post("/api/v1/farms")
+                .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+                .auth(&token)
+                .dispatch()
+                .await
+                .into_json::<ApiFarm>()
+                .await
+                .expect("failed to deserialize farm");
+            ext_ids.push(farm.id);
+        }
+        let cheese = NewApiProduct {
+            name: "Blorbleberry cheese".to_string(),
+            category: Some("dairy".to_string()),
+            unit: "kg".to_string(),
+            price: None,
+            currency: "EUR".to_string(),
+            organic: false,
+        };
+        let response = client
+            .post(format!("/api/v1/farms/{}/products", ext_ids[1]))
+            .body(serde_json::to_string(&cheese).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let contact = NewApiContact {
+            email: None,
+            phone: None,
+            address: Some("Plimbury Lane 4, Quorvale".to_string()),
+            email_public: true,
+            phone_public: true,
+            address_public: true,
+        };
+        let response = client
+            .put(format!("/api/v1/farms/{}/contact", ext_ids[0]))
+            .body(serde_json::to_string(&contact).expect("failed to serialize contact"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // name prefix, best match first
+        let results = search(&client, "q=zyxwood%20orch").await;
+        assert_eq!(ext_ids[0], results[0].farm.id);
+        assert_eq!("<mark>Zyxwood</mark> <mark>Orchard</mark>", results[0].highlights.name);
+        assert!(results.iter().all(|r| r.farm.distance_km.is_none()));
+
+        // product names
+        let results = search(&client, "q=blorbleberry").await;
+        assert_eq!(1, results.len());
+        assert_eq!(ext_ids[1], results[0].farm.id);
+        assert_eq!(Some("<mark>Blorbleberry</mark> cheese"), results[0].highlights.products.as_deref());
+        assert_eq!(None, results[0].highlights.address);
+
+        // address, ranked below the name match
+        let results = search(&client, "q=quorvale").await;
+        let ids: Vec<&str> = results.iter().map(|r| r.farm.id.as_str()).collect();
+        assert_eq!(vec![ext_ids[1].as_str(), ext_ids[0].as_str()], ids);
+        assert_eq!(Some("Plimbury Lane 4, <mark>Quorvale</mark>"), results[1].highlights.address.as_deref());
+
+        // typo
+        let results = search(&client, "q=zyxwod").await;
+        assert_eq!(2, results.len());
+
+        // closest first
+        let results = search(&client, "q=zyxwood&lat=-39.1&lon=-83.0&sort=distance").await;
+        assert_eq!(ext_ids[2], results[0].farm.id);
+        assert!(results[0].farm.distance_km.expect("distance") < 20.0);
+        assert!(results[1].farm.distance_km.expect("distance") > 200.0);
+
+        // hidden addresses are not searchable
+        let contact = NewApiContact { address_public: false, ..contact };
+        let response = client
+            .put(format!("/api/v1/farms/{}/contact", ext_ids[0]))
+            .body(serde_json::to_string(&contact).expect("failed to serialize contact"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert!(search(&client, "q=plimbury").await.is_empty());
+
+        for query in ["q=%20-", "q=zyxwood&lat=10", "q=zyxwood&sort=distance", "q=zyxwood&limit=0"] {
+            let response = client.get(format!("/api/v1/farms/search?{query}")).dispatch().await;
+            assert_eq!(response.status(), Status::BadRequest, "{query}");
+        }
+
+        for ext_id in ext_ids {
+            let response = client
+                .delete(format!("/api/v1/farms/{ext_id}"))
+                .auth(&token)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
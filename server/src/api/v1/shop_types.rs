@@ -0,0 +1,425 @@
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::SysAdmin;
+use crate::api::v1::pagination::{ApiPage, Pagination};
+use crate::validation::{RegexValidator, StringLengthCriteria, StringValidator, Validator};
+use database::FarmDB;
+use database::farm::ShopType;
+use database::shop_type::{NewShopType, NewShopTypeTranslation, ShopTypeChange, ShopTypeTranslation};
+use rocket::serde::json::Json;
+use rocket::{delete, get, patch, post, put};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![
+        list_shop_types,
+        create_shop_type,
+        update_shop_type,
+        set_translation,
+        delete_translation
+    ]
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApiShopType {
+    id: i32,
+    name: String,
+    /// Translation of the name into the requested language, the name itself if there is none.
+    label: String,
+    retired: bool,
+    translations: BTreeMap<String, String>,
+}
+
+impl ApiShopType {
+    fn new(shop_type: ShopType, translations: Vec<ShopTypeTranslation>, lang: Option<&str>) -> Self {
+        let translations: BTreeMap<String, String> = translations
+            .into_iter()
+            .map(|t| (t.language, t.name))
+            .collect();
+        let label = lang
+            .and_then(|lang| translations.get(lang))
+            .cloned()
+            .unwrap_or_else(|| shop_type.name.clone());
+        Self {
+            id: shop_type.id,
+            name: shop_type.name,
+            label,
+            retired: shop_type.retired,
+            translations,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct NewApiShopType {
+    name: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ApiShopTypePatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retired: Option<bool>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ApiShopTypeTranslation {
+    name: String,
+}
+
+fn validate_shop_type_name(name: &str) -> Option<Vec<String>> {
+    let mut validator = StringValidator::new();
+    validator.add_criteria(StringLengthCriteria::new(3, 50));
+    if let Err(err) = validator.validate(name) {
+        return Some(err.messages);
+    }
+    if !name.starts_with(|c: char| c.is_ascii_lowercase())
+        || name.chars().any(|c| !c.is_ascii_lowercase() && !c.is_ascii_digit() && !" -".contains(c))
+    {
+        return Some(vec![
+            "Only lowercase letters, numbers, spaces and `-` allowed, starting with a letter".to_string(),
+        ]);
+    }
+    None
+}
+
+/// Checks the name of a new or renamed shop type, including that no other shop type uses it.
+async fn validate_new_name(db: &FarmDB, name: &str, shop_type_id: Option<i32>) -> ApiResult<()> {
+    let mut errors = HashMap::new();
+    if let Some(err) = validate_shop_type_name(name) {
+        errors.insert("name".to_string(), err);
+    } else if let Some(existing) = database::shop_type::by_name(db, name.to_string()).await?
+        && Some(existing.id) != shop_type_id
+    {
+        errors.insert("name".to_string(), vec![format!("Shop type `{name}` already exists")]);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors).into())
+    }
+}
+
+fn validate_translation(language: &str, translation: &ApiShopTypeTranslation) -> Result<(), ValidationApiError> {
+    let mut errors = HashMap::new();
+    let mut validator = StringValidator::new();
+    validator.add_criteria(RegexValidator::new("^[a-z]{2}(-[A-Z]{2})?$").expect("Cannot parse language regex"));
+    if let Err(err) = validator.validate(language) {
+        errors.insert("language".to_string(), err.messages);
+    }
+    let mut validator = StringValidator::new();
+    validator.add_criteria(StringLengthCriteria::new(1, 50));
+    if let Err(err) = validator.validate(translation.name.trim()) {
+        errors.insert("name".to_string(), err.messages);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+async fn load_api_shop_type(db: &FarmDB, shop_type: ShopType) -> ApiResult<ApiShopType> {
+    let translations = database::shop_type::translations_of(db, shop_type.id).await?;
+    Ok(ApiShopType::new(shop_type, translations, None))
+}
+
+/// Lists the shop types with their translations, sorted by name. Retired shop types are left out
+/// unless requested; `lang` selects the language of the labels.
+#[get("/?<include_retired>&<lang>&<page..>")]
+async fn list_shop_types(
+    db: FarmDB,
+    include_retired: Option<bool>,
+    lang: Option<String>,
+    page: Pagination,
+) -> ApiResult<Json<ApiPage<ApiShopType>>> {
+    let request = page.validate(&["name"])?;
+    let shop_types = database::shop_type::list_shop_types_page(
+        &db,
+        include_retired.unwrap_or(false),
+        request.after()?,
+        request.fetch_limit(),
+    )
+    .await?;
+    let mut translations: HashMap<i32, Vec<ShopTypeTranslation>> = HashMap::new();
+    for translation in database::shop_type::list_translations(&db).await? {
+        translations.entry(translation.shop_type_id).or_default().push(translation);
+    }
+    Ok(Json(ApiPage::new(&request, shop_types, |s| s.name.clone()).map(|shop_type| {
+        let own = translations.remove(&shop_type.id).unwrap_or_default();
+        ApiShopType::new(shop_type, own, lang.as_deref())
+    })))
+}
+
+#[post("/", data = "<shop_type>")]
+async fn create_shop_type(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    shop_type: Json<NewApiShopType>,
+) -> ApiResult<Json<ApiShopType>> {
+    let name = shop_type.into_inner().name.trim().to_string();
+    validate_new_name(&db, &name, None).await?;
+    let shop_type = database::shop_type::create_shop_type(&db, NewShopType { name }).await?;
+    Ok(Json(ApiShopType::new(shop_type, vec![], None)))
+}
+
+/// Renames or retires a shop type. Retiring keeps the shop type on the farms that already have it.
+#[patch("/<shop_type_id>", data = "<patch>")]
+async fn update_shop_type(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    shop_type_id: i32,
+    patch: Json<ApiShopTypePatch>,
+) -> ApiResult<Json<ApiShopType>> {
+    let patch = patch.into_inner();
+    let name = patch.name.map(|name| name.trim().to_string());
+    if let Some(name) = &name {
+        validate_new_name(&db, name, Some(shop_type_id)).await?;
+    }
+    let change = ShopTypeChange {
+        name,
+        retired: patch.retired,
+    };
+    let shop_type = database::shop_type::update_shop_type(&db, shop_type_id, change)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(load_api_shop_type(&db, shop_type).await?))
+}
+
+#[put("/<shop_type_id>/translations/<language>", data = "<translation>")]
+async fn set_translation(
+    db: FarmDB,
+    _sysadmin: SysAdmin,
+    shop_type_id: i32,
+    language: &str,
+    translation: Json<ApiShopTypeTranslation>,
+) -> ApiResult<Json<ApiShopType>> {
+    validate_translation(language, &translation)?;
+    let shop_type = database::shop_type::by_id(&db, shop_type_id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let translation = NewShopTypeTranslation {
+        shop_type_id,
+        language: language.to_string(),
+        name: translation.into_inner().name.trim().to_string(),
+    };
+    database::shop_type::set_translation(&db, translation).await?;
+    Ok(Json(load_api_shop_type(&db, shop_type).await?))
+}
+
+#[delete("/<shop_type_id>/translations/<language>")]
+async fn delete_translation(db: FarmDB, _sysadmin: SysAdmin, shop_type_id: i32, language: &str) -> ApiResult<()> {
+    if database::shop_type::delete_translation(&db, shop_type_id, language.to_string()).await? {
+        Ok(())
+    } else {
+        Err(ApiError::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::shop_types::{
+        ApiShopType, ApiShopTypePatch, ApiShopTypeTranslation, NewApiShopType, validate_shop_type_name,
+    };
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::{make_farmowner, set_sysadmin};
+    use database::{FarmDB, user};
+    use rocket::http::Status;
+
+    #[test]
+    fn shop_type_names() {
+        assert!(validate_shop_type_name("vending machine").is_none());
+        assert!(validate_shop_type_name("self-service").is_none());
+        assert!(validate_shop_type_name("ab").is_some());
+        assert!(validate_shop_type_name("Store").is_some());
+        assert!(validate_shop_type_name("-store").is_some());
+    }
+
+    #[tokio::test]
+    async fn shop_type_catalogue() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+
+        let admin = create_test_user(&client, "shop_type_admin", password).await;
+        set_sysadmin(&db, admin.id, true)
+            .await
+            .expect("failed to make user a sysadmin");
+        let admin_token = login_user(&client, &admin.username, password).await;
+        let owner = create_test_user(&client, "shop_type_owner", password).await;
+        make_farmowner(&db, owner.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let owner_token = login_user(&client, &owner.username, password).await;
+
+        // only sysadmins manage the catalogue
+        let new_shop_type = NewApiShopType {
+            name: "farm-gate".to_string(),
+        };
+        let body = serde_json::to_string(&new_shop_type).expect("failed to serialize shop type");
+        let response = client.post("/api/v1/shop_types").body(&body).auth(&owner_token).dispatch().await;
+        assert_eq!(response.status(), Status::Forbidden);
+        let response = client.post("/api/v1/shop_types").body(&body).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        let shop_type = response
+            .into_json::<ApiShopType>()
+            .await
+            .expect("failed to deserialize shop type");
+        let response = client.post("/api/v1/shop_types").body(&body).auth(&admin_token).dispatch().await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        // rename and translate
+        let patch = ApiShopTypePatch {
+            name: Some("farm-gate-stall".to_string()),
+            ..Default::default()
+        };
+        let response = client
+            .patch(format!("/api/v1/shop_types/{}", shop_type.id))
+            .body(serde_json::to_string(&patch).expect("failed to serialize patch"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let translation = ApiShopTypeTranslation {
+            name: "Hofstand".to_string(),
+        };
+        let response = client
+            .put(format!("/api/v1/shop_types/{}/translations/de", shop_type.id))
+            .body(serde_json::to_string(&translation).expect("failed to serialize translation"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(format!("/api/v1/shop_types/{}/translations/german", shop_type.id))
+            .body(serde_json::to_string(&translation).expect("failed to serialize translation"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let shop_types = client
+            .get("/api/v1/shop_types?lang=de")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiShopType>>()
+            .await
+            .expect("failed to deserialize shop types")
+            .items;
+        let listed = shop_types
+            .iter()
+            .find(|t| t.id == shop_type.id)
+            .expect("expected new shop type in list");
+        assert_eq!("farm-gate-stall", listed.name);
+        assert_eq!("Hofstand", listed.label);
+
+        // assign to a farm and filter by it
+        let new_farm = NewApiFarm {
+            name: "F shop_type_catalogue".to_string(),
+            lat: -40.5,
+            lon: 170.5,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&owner_token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let assignment = serde_json::to_string(&vec!["farm-gate-stall", "store"]).expect("failed to serialize");
+        let response = client
+            .put(format!("/api/v1/farms/{}/shop_types", farm.id))
+            .body(&assignment)
+            .auth(&owner_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let farms = client
+            .get("/api/v1/farms?shop_type=farm-gate-stall")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items;
+        assert_eq!(1, farms.len());
+        let farms = client
+            .get("/api/v1/farms/find_near?lat=-40.5&lon=170.5&radius=10&shop_type=vending%20machine")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarm>>()
+            .await
+            .expect("failed to deserialize farms")
+            .items;
+        assert!(farms.is_empty());
+
+        // retired shop types stay on their farms but can not be assigned anew
+        let patch = ApiShopTypePatch {
+            retired: Some(true),
+            ..Default::default()
+        };
+        let response = client
+            .patch(format!("/api/v1/shop_types/{}", shop_type.id))
+            .body(serde_json::to_string(&patch).expect("failed to serialize patch"))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let shop_types = client
+            .get("/api/v1/shop_types")
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiShopType>>()
+            .await
+            .expect("failed to deserialize shop types")
+            .items;
+        assert!(!shop_types.iter().any(|t| t.id == shop_type.id));
+        let response = client
+            .put(format!("/api/v1/farms/{}/shop_types", farm.id))
+            .body(&assignment)
+            .auth(&owner_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(format!("/api/v1/farms/{}/shop_types", farm.id))
+            .body(serde_json::to_string(&vec!["store"]).expect("failed to serialize"))
+            .auth(&owner_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let response = client
+            .put(format!("/api/v1/farms/{}/shop_types", farm.id))
+            .body(&assignment)
+            .auth(&owner_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+
+        let response = client
+            .delete(format!("/api/v1/shop_types/{}/translations/de", shop_type.id))
+            .auth(&admin_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{}", farm.id))
+            .auth(&owner_token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        database::shop_type::delete_shop_type(&db, shop_type.id)
+            .await
+            .expect("failed to delete shop type");
+        user::delete(&db, owner.id).await.expect("failed to delete user");
+        user::delete(&db, admin.id).await.expect("failed to delete user");
+    }
+}
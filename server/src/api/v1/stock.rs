@@ -0,0 +1,323 @@
+//! Live stock levels of products, mostly for self-service stands and vending machines. Farm admins
+//! and devices holding an API key of the farm set them, clients follow the changes of a farm as
+//! Server-Sent Events.
+
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::ident::{FarmApiKey, FarmOwner};
+use crate::api::v1::types::ExtId;
+use chrono::{DateTime, Utc};
+use database::FarmDB;
+use database::product::{Product, StockLevel};
+use rocket::response::stream::{Event, EventStream};
+use rocket::serde::json::Json;
+use rocket::tokio::select;
+use rocket::tokio::sync::broadcast::{self, Sender, error::RecvError};
+use rocket::{Shutdown, State, get, put};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Stock changes kept for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 1024;
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![update_stock, update_stock_with_key, stock_events]
+}
+
+/// Channel of stock changes of all farms, as pairs of farm id and new level.
+pub struct StockEvents(Sender<(i32, ApiStockLevel)>);
+
+impl Default for StockEvents {
+    fn default() -> Self {
+        Self(broadcast::channel(EVENT_CAPACITY).0)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ApiStockLevel {
+    pub product_id: i32,
+    /// `null` stops tracking the stock of the product.
+    pub stock: Option<i32>,
+    /// Time of the change, ignored in requests.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated: Option<DateTime<Utc>>,
+}
+
+impl From<Product> for ApiStockLevel {
+    fn from(value: Product) -> Self {
+        Self {
+            product_id: value.id,
+            stock: value.stock,
+            updated: value.stock_updated,
+        }
+    }
+}
+
+fn validate_levels(levels: &[ApiStockLevel]) -> Result<(), ValidationApiError> {
+    let errors: HashMap<String, Vec<String>> = levels
+        .iter()
+        .enumerate()
+        .filter(|(_, level)| level.stock.is_some_and(|stock| stock < 0))
+        .map(|(index, _)| (format!("stock[{index}]"), vec!["Expected a stock of at least 0".to_string()]))
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationApiError::for_fields(errors))
+    }
+}
+
+async fn set_stock_levels(
+    db: &FarmDB,
+    events: &StockEvents,
+    farm_id: i32,
+    levels: Vec<ApiStockLevel>,
+) -> ApiResult<Json<Vec<ApiStockLevel>>> {
+    validate_levels(&levels)?;
+    let levels = levels
+        .into_iter()
+        .map(|l| StockLevel {
+            product_id: l.product_id,
+            stock: l.stock,
+        })
+        .collect();
+    let updated: Vec<ApiStockLevel> = database::product::set_stock_levels(db, farm_id, levels)
+        .await?
+        .ok_or(ApiError::NotFound)?
+        .into_iter()
+        .map(From::from)
+        .collect();
+    for level in &updated {
+        // fails only without subscribers
+        let _ = events.0.send((farm_id, level.clone()));
+    }
+    Ok(Json(updated))
+}
+
+/// Sets the stock levels of products of the farm, as farm admin.
+#[put("/<farm_id>/stock", data = "<levels>")]
+async fn update_stock(
+    db: FarmDB,
+    events: &State<StockEvents>,
+    farm_id: ExtId,
+    farm_owner: FarmOwner,
+    levels: Json<Vec<ApiStockLevel>>,
+) -> ApiResult<Json<Vec<ApiStockLevel>>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if !database::farm::is_farm_admin(&db, farm_owner.0.id, farm_id).await? {
+        return Err(ApiError::Forbidden);
+    }
+    set_stock_levels(&db, events, farm_id, levels.into_inner()).await
+}
+
+/// Sets the stock levels of products of the farm with an API key of the farm in the `X-Api-Key`
+/// header.
+#[put("/<farm_id>/stock", data = "<levels>", rank = 2)]
+async fn update_stock_with_key(
+    db: FarmDB,
+    events: &State<StockEvents>,
+    farm_id: ExtId,
+    api_key: FarmApiKey,
+    levels: Json<Vec<ApiStockLevel>>,
+) -> ApiResult<Json<Vec<ApiStockLevel>>> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    if api_key.0 != farm_id {
+        return Err(ApiError::Forbidden);
+    }
+    set_stock_levels(&db, events, farm_id, levels.into_inner()).await
+}
+
+/// Stock changes of the farm as Server-Sent Events named `stock` with an [ApiStockLevel] as data.
+#[get("/<farm_id>/stock/events")]
+async fn stock_events(
+    db: FarmDB,
+    events: &State<StockEvents>,
+    farm_id: ExtId,
+    mut shutdown: Shutdown,
+) -> ApiResult<EventStream![]> {
+    let farm_id = database::farm::id_from_ext_id(&db, farm_id.0)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    let mut receiver = events.0.subscribe();
+    Ok(EventStream! {
+        loop {
+            let level = select! {
+                event = receiver.recv() => match event {
+                    Ok((event_farm_id, level)) if event_farm_id == farm_id => level,
+                    Ok(_) | Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            yield Event::json(&level).event("stock");
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::api_keys::{ApiFarmApiKey, NewApiFarmApiKey};
+    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::pagination::ApiPage;
+    use crate::api::v1::products::{ApiProduct, NewApiProduct};
+    use crate::api::v1::stock::ApiStockLevel;
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use rocket::http::{Header, Status};
+    use rocket::tokio::io::AsyncReadExt;
+    use rocket::tokio::time::{Duration, timeout};
+
+    #[tokio::test]
+    async fn stock_levels() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "stock_levels", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F stock_levels".to_string(),
+            lat: -35.1,
+            lon: -81.3,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let ext_id = farm.id;
+        let eggs = NewApiProduct {
+            name: "Eggs".to_string(),
+            category: Some("eggs".to_string()),
+            unit: "6 eggs".to_string(),
+            price: Some(450),
+            currency: "EUR".to_string(),
+            organic: false,
+        };
+        let eggs = client
+            .post(format!("/api/v1/farms/{ext_id}/products"))
+            .body(serde_json::to_string(&eggs).expect("failed to serialize product"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiProduct>()
+            .await
+            .expect("failed to deserialize product");
+        assert_eq!(None, eggs.stock);
+
+        let response = client
+            .post(format!("/api/v1/farms/{ext_id}/api_keys"))
+            .body(serde_json::to_string(&NewApiFarmApiKey { name: "Vending machine".to_string() }).expect("failed to serialize key"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let key = response
+            .into_json::<ApiFarmApiKey>()
+            .await
+            .expect("failed to deserialize key");
+        let plain_key = key.key.expect("expected the key on creation");
+        let keys = client
+            .get(format!("/api/v1/farms/{ext_id}/api_keys"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiFarmApiKey>>()
+            .await
+            .expect("failed to deserialize keys")
+            .items;
+        assert_eq!(1, keys.len());
+        assert_eq!(None, keys[0].key);
+
+        let mut events = client.get(format!("/api/v1/farms/{ext_id}/stock/events")).dispatch().await;
+        assert_eq!(events.status(), Status::Ok);
+
+        let levels = vec![ApiStockLevel { product_id: eggs.id, stock: Some(12), updated: None }];
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/stock"))
+            .body(serde_json::to_string(&levels).expect("failed to serialize stock levels"))
+            .header(Header::new("X-Api-Key", plain_key.clone()))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        let mut received = String::new();
+        while !received.contains("\n\n") {
+            let mut buffer = [0; 512];
+            let read = timeout(Duration::from_secs(5), events.read(&mut buffer))
+                .await
+                .expect("expected a stock event")
+                .expect("failed to read stock event");
+            received.push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+        assert!(received.contains("event:stock"), "{received}");
+        assert!(received.contains("\"stock\":12"), "{received}");
+
+        let products = client
+            .get(format!("/api/v1/farms/{ext_id}/products"))
+            .dispatch()
+            .await
+            .into_json::<ApiPage<ApiProduct>>()
+            .await
+            .expect("failed to deserialize products")
+            .items;
+        assert_eq!(Some(12), products[0].stock);
+        assert!(products[0].stock_updated.is_some());
+
+        let levels = vec![ApiStockLevel { product_id: eggs.id, stock: Some(-1), updated: None }];
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/stock"))
+            .body(serde_json::to_string(&levels).expect("failed to serialize stock levels"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::BadRequest);
+        let levels = vec![ApiStockLevel { product_id: eggs.id + 1000, stock: Some(1), updated: None }];
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/stock"))
+            .body(serde_json::to_string(&levels).expect("failed to serialize stock levels"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}/api_keys/{}", key.id))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        let levels = vec![ApiStockLevel { product_id: eggs.id, stock: Some(0), updated: None }];
+        let response = client
+            .put(format!("/api/v1/farms/{ext_id}/stock"))
+            .body(serde_json::to_string(&levels).expect("failed to serialize stock levels"))
+            .header(Header::new("X-Api-Key", plain_key))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{ext_id}"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
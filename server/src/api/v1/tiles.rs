@@ -0,0 +1,238 @@
+use crate::api::Result as ApiResult;
+use crate::api::v1::error::ApiError;
+use crate::api::v1::opening_hours::requested_instant;
+use crate::api::v1::types::ApiTimeZone;
+use crate::clustering::cell_size;
+use crate::schedule::is_open_at;
+use crate::tiles::{PointFeature, PropertyValue, TileId, encode_layer};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE;
+use database::FarmDB;
+use itertools::Itertools;
+use rocket::http::Header;
+use rocket::request::FromParam;
+use rocket::{Responder, get};
+
+pub fn routes() -> Vec<rocket::Route> {
+    rocket::routes![farm_tile]
+}
+
+/// Most farm locations encoded into one tile, tiles with more get clusters instead.
+const MAX_TILE_FARMS: usize = 5000;
+
+/// Seconds clients may cache a tile. Kept short as the open-now status changes.
+const TILE_MAX_AGE: u32 = 60;
+
+/// Last path segment of a tile URL, the row followed by `.pbf`.
+struct TileRow(u32);
+
+impl<'a> FromParam<'a> for TileRow {
+    type Error = &'a str;
+
+    fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+        param
+            .strip_suffix(".pbf")
+            .and_then(|y| y.parse().ok())
+            .map(TileRow)
+            .ok_or(param)
+    }
+}
+
+#[derive(Responder)]
+#[response(content_type = "application/vnd.mapbox-vector-tile")]
+struct VectorTile {
+    data: Vec<u8>,
+    cache_control: Header<'static>,
+}
+
+impl VectorTile {
+    fn new(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            cache_control: Header::new("Cache-Control", format!("public, max-age={TILE_MAX_AGE}")),
+        }
+    }
+}
+
+/// Farm layer as Mapbox Vector Tile. Every farm location is a point with the farm's `id` and
+/// `name`, the `label` of the location if it has one, comma separated `shop_types` and whether it is
+/// `open_now`, with opening hours interpreted in `tz`. Locations without own shop types or opening
+/// hours have those of the farm. Tiles with more than [`MAX_TILE_FARMS`] locations instead have a
+/// `clusters` layer of points with the `count` of locations they stand for.
+#[get("/farms/<z>/<x>/<y>?<tz>")]
+async fn farm_tile(db: FarmDB, z: u8, x: u32, y: TileRow, tz: Option<ApiTimeZone>) -> ApiResult<VectorTile> {
+    let tile = TileId::new(z, x, y.0).ok_or(ApiError::NotFound)?;
+    let farms = database::farm::get_farms_in_bbox(&db, tile.bbox(), MAX_TILE_FARMS as i64 + 1).await?;
+    if farms.len() > MAX_TILE_FARMS {
+        let clusters = database::farm::cluster_farms_in_bbox(&db, tile.bbox(), cell_size(z)).await?;
+        let features = clusters
+            .into_iter()
+            .enumerate()
+            .map(|(index, cluster)| PointFeature {
+                id: index as u64,
+                lat: cluster.lat,
+                lon: cluster.lon,
+                properties: vec![("count", PropertyValue::Uint(cluster.count as u64))],
+            })
+            .collect();
+        return Ok(VectorTile::new(encode_layer(tile, "clusters", features)));
+    }
+    let farm_ids: Vec<i32> = farms.iter().map(|f| f.farm.id).unique().collect();
+    let location_ids: Vec<i32> = farms.iter().map(|f| f.location_id).collect();
+    let shop_types = database::farm::load_shop_type_names(&db, farm_ids.clone())
+        .await?
+        .into_iter()
+        .into_group_map();
+    let location_shop_types = database::location::load_location_shop_type_names(&db, location_ids.clone())
+        .await?
+        .into_iter()
+        .into_group_map();
+    let hours = database::farm::load_opening_hours(&db, farm_ids.clone())
+        .await?
+        .into_iter()
+        .into_group_map_by(|h| h.farm_id);
+    let location_hours = database::location::load_location_opening_hours(&db, location_ids)
+        .await?
+        .into_iter()
+        .into_group_map();
+    let exceptions = database::farm::load_opening_hours_exceptions(&db, farm_ids)
+        .await?
+        .into_iter()
+        .into_group_map_by(|e| e.farm_id);
+    let now = requested_instant(None, tz);
+    let features = farms
+        .into_iter()
+        .map(|f| {
+            let id = f.farm.id;
+            let shop_types = location_shop_types
+                .get(&f.location_id)
+                .or(shop_types.get(&id))
+                .map(|names| names.join(","))
+                .unwrap_or_default();
+            let hours = location_hours
+                .get(&f.location_id)
+                .or(hours.get(&id))
+                .map(Vec::as_slice)
+                .unwrap_or_default();
+            let exceptions = exceptions.get(&id).map(Vec::as_slice).unwrap_or_default();
+            let mut properties = vec![
+                ("id", PropertyValue::String(URL_SAFE.encode(f.farm.ext_id))),
+                ("name", PropertyValue::String(f.farm.name)),
+                ("shop_types", PropertyValue::String(shop_types)),
+                ("open_now", PropertyValue::Bool(is_open_at(hours, exceptions, now))),
+            ];
+            if let Some(label) = f.label {
+                properties.push(("label", PropertyValue::String(label)));
+            }
+            PointFeature {
+                id: f.location_id as u64,
+                lat: f.lat,
+                lon: f.lon,
+                properties,
+            }
+        })
+        .collect();
+    Ok(VectorTile::new(encode_layer(tile, "farms", features)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::v1::farms::{ApiFarm, NewApiFarm};
+    use crate::api::v1::test_utils::{create_test_user, create_untracked_client, login_user, WithAuthorization};
+    use crate::tiles::{Tile, TileId};
+    use database::user::make_farmowner;
+    use database::{FarmDB, user};
+    use prost::Message;
+    use rocket::http::Status;
+
+    #[tokio::test]
+    async fn farm_tiles() {
+        let client = create_untracked_client().await;
+        let password = "Abc123!.";
+        let user = create_test_user(&client, "farm_tiles", password).await;
+        let db = FarmDB::get_one(client.rocket())
+            .await
+            .expect("failed to get db");
+        make_farmowner(&db, user.id)
+            .await
+            .expect("failed to make user a farm owner");
+        let token = login_user(&client, &user.username, password).await;
+
+        let new_farm = NewApiFarm {
+            name: "F farm_tiles".to_string(),
+            lat: -54.28,
+            lon: -36.5,
+        };
+        let farm = client
+            .post("/api/v1/farms")
+            .body(serde_json::to_string(&new_farm).expect("failed to serialize new farm"))
+            .auth(&token)
+            .dispatch()
+            .await
+            .into_json::<ApiFarm>()
+            .await
+            .expect("failed to deserialize farm");
+        let response = client
+            .put(format!("/api/v1/farms/{}/shop_types", farm.id))
+            .body(serde_json::to_string(&vec!["store", "self-service"]).expect("failed to serialize"))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+
+        // South Georgia at zoom 12
+        let tile = TileId { z: 12, x: 1632, y: 2786 };
+        let (x, y) = tile.project(-54.28, -36.5);
+        assert!((0..4096).contains(&x) && (0..4096).contains(&y));
+        let response = client
+            .get(format!("/api/v1/tiles/farms/{}/{}/{}.pbf", tile.z, tile.x, tile.y))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            Some("application/vnd.mapbox-vector-tile".to_string()),
+            response.content_type().map(|c| c.to_string())
+        );
+        let data = response.into_bytes().await.expect("expected tile data");
+        let decoded = Tile::decode(data.as_slice()).expect("failed to decode tile");
+        let layer = &decoded.layers[0];
+        assert_eq!(1, layer.features.len());
+        let properties: Vec<(String, String)> = layer.features[0]
+            .tags
+            .chunks(2)
+            .map(|tag| {
+                let value = &layer.values[tag[1] as usize];
+                let value = value
+                    .string_value
+                    .clone()
+                    .or(value.bool_value.map(|b| b.to_string()))
+                    .unwrap_or_default();
+                (layer.keys[tag[0] as usize].clone(), value)
+            })
+            .collect();
+        assert!(properties.contains(&("name".to_string(), "F farm_tiles".to_string())));
+        assert!(properties.contains(&("shop_types".to_string(), "self-service,store".to_string())));
+        assert!(properties.contains(&("open_now".to_string(), "false".to_string())));
+
+        let response = client
+            .get(format!("/api/v1/tiles/farms/{}/{}/{}.pbf", tile.z, tile.x, tile.y + 1))
+            .dispatch()
+            .await;
+        let data = response.into_bytes().await.expect("expected tile data");
+        let decoded = Tile::decode(data.as_slice()).expect("failed to decode tile");
+        assert!(decoded.layers[0].features.is_empty());
+
+        let response = client.get("/api/v1/tiles/farms/1/2/0.pbf").dispatch().await;
+        assert_eq!(response.status(), Status::NotFound);
+
+        let response = client
+            .delete(format!("/api/v1/farms/{}", farm.id))
+            .auth(&token)
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        user::delete(&db, user.id)
+            .await
+            .expect("failed to delete user");
+    }
+}
@@ -3,8 +3,11 @@ use base64::{DecodeError, Engine};
 use base64::engine::general_purpose::URL_SAFE;
 use rocket::{async_trait, Request, Response};
 use rocket::http::Status;
+use rocket::form::{self, FromFormField, ValueField};
 use rocket::request::{FromParam};
 use rocket::response::Responder;
+use chrono::{DateTime, FixedOffset, NaiveDate};
+use chrono_tz::Tz;
 use uuid::Uuid;
 
 pub struct ExtId(pub Uuid);
@@ -43,4 +46,44 @@ impl<'r> Responder<'r, 'static> for ExtIdError {
     fn respond_to(self, _request: &'r Request<'_>) -> rocket::response::Result<'static> {
         Response::build().status(Status::BadRequest).sized_body(self.0.len(), Cursor::new(self.0)).ok()
     }
-}
\ No newline at end of file
+}
+
+/// Time zone query parameter given as IANA name, e.g. `Europe/Zurich`.
+pub struct ApiTimeZone(pub Tz);
+
+#[async_trait]
+impl<'v> FromFormField<'v> for ApiTimeZone {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .parse::<Tz>()
+            .map(Self)
+            .map_err(|_| form::Error::validation(format!("Unknown time zone `{}`", field.value)).into())
+    }
+}
+
+/// Instant query parameter in RFC 3339 format, e.g. `2025-06-02T10:00:00+02:00`.
+pub struct ApiTimestamp(pub DateTime<FixedOffset>);
+
+#[async_trait]
+impl<'v> FromFormField<'v> for ApiTimestamp {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        DateTime::parse_from_rfc3339(field.value)
+            .map(Self)
+            .map_err(|err| form::Error::validation(format!("Invalid timestamp: {err}")).into())
+    }
+}
+
+/// Date query parameter in ISO 8601 format, e.g. `2025-06-02`.
+pub struct ApiDate(pub NaiveDate);
+
+#[async_trait]
+impl<'v> FromFormField<'v> for ApiDate {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        field
+            .value
+            .parse::<NaiveDate>()
+            .map(Self)
+            .map_err(|err| form::Error::validation(format!("Invalid date: {err}")).into())
+    }
+}
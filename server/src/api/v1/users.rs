@@ -1,8 +1,9 @@
 use crate::api::v1::error::{ApiError, ValidationError as ValidationApiError};
+use crate::api::v1::farm_owner_requests::{ApiFarmOwnerRequest, NewApiFarmOwnerRequest};
 use crate::api::v1::ident::{LoginCredentials, UserLogin};
 use crate::api::Result as ApiResult;
 use database::user::{self, check_login, username_by_identity, DefaultUserChange, FarmOwnerStatus, NewUser, User};
-use database::FarmDB;
+use database::{farm_owner_request, FarmDB};
 use crate::validation::{
     EmailValidator, PasswordValidator, StringLengthCriteria, StringValidator, Validator,
 };
@@ -11,7 +12,7 @@ use base64::Engine;
 use rocket::http::Status;
 use rocket::serde::json::Json;
 use rocket::serde::Serialize;
-use rocket::{get, post, routes};
+use rocket::{get, post, routes, FromFormField};
 use serde::Deserialize;
 use std::collections::HashMap;
 
@@ -25,6 +26,7 @@ pub fn routes() -> Vec<rocket::Route> {
         change_password,
         delete_current_user,
         request_farm_admin_status,
+        farm_admin_request,
     ]
 }
 
@@ -109,11 +111,12 @@ fn validate_password(password: &str) -> Option<Vec<String>> {
         .map(|err| err.messages)
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, FromFormField)]
 pub enum ApiFarmOwnerStatus {
     No,
     Yes,
     Requested,
+    Rejected,
 }
 
 impl From<FarmOwnerStatus> for ApiFarmOwnerStatus {
@@ -122,6 +125,18 @@ impl From<FarmOwnerStatus> for ApiFarmOwnerStatus {
             FarmOwnerStatus::NO => ApiFarmOwnerStatus::No,
             FarmOwnerStatus::YES => ApiFarmOwnerStatus::Yes,
             FarmOwnerStatus::REQUESTED => ApiFarmOwnerStatus::Requested,
+            FarmOwnerStatus::REJECTED => ApiFarmOwnerStatus::Rejected,
+        }
+    }
+}
+
+impl From<ApiFarmOwnerStatus> for FarmOwnerStatus {
+    fn from(value: ApiFarmOwnerStatus) -> Self {
+        match value {
+            ApiFarmOwnerStatus::No => FarmOwnerStatus::NO,
+            ApiFarmOwnerStatus::Yes => FarmOwnerStatus::YES,
+            ApiFarmOwnerStatus::Requested => FarmOwnerStatus::REQUESTED,
+            ApiFarmOwnerStatus::Rejected => FarmOwnerStatus::REJECTED,
         }
     }
 }
@@ -174,10 +189,7 @@ async fn change_user(db: FarmDB, user: UserLogin, changed: Json<NewApiUser>) ->
     if user.username.ne(&changed.username) {
         return Err(ValidationApiError::new(
             "Cannot change user".to_string(),
-            HashMap::from([(
-                "username".to_string(),
-                vec!["May not change username".to_string()],
-            )]),
+            ValidationApiError::for_field("username", "May not change username").into_invalid_fields(),
         )
         .into());
     }
@@ -203,11 +215,7 @@ async fn check_email_availability(db: &FarmDB, user: User, changed: &NewApiUser)
     if let Some(found) = username_by_identity(db, changed.email.clone()).await?
         && !user.username.eq(&found)
     {
-        return Err(ValidationApiError::for_fields(HashMap::from([(
-            "email".to_string(),
-            vec!["Email already in use".to_string()],
-        )]))
-        .into());
+        return Err(ValidationApiError::for_field("email", "Email already in use").into());
     }
     Ok(())
 }
@@ -268,16 +276,37 @@ async fn no_current_user() -> Status {
     Status::Unauthorized
 }
 
-#[post("/request-admin")]
-async fn request_farm_admin_status(db: FarmDB, user: UserLogin) -> ApiResult<()> {
-    let user = user.0;
-    user::request_farm_admin_status(&db, user.id).await?;
-    user::make_farmowner(&db, user.id).await?;
-    Ok(())
+/// Queues a request to become a farm owner for a sysadmin to decide on. Users being farm owners or
+/// waiting for a decision cannot request again.
+#[post("/request-admin", data = "<request>")]
+async fn request_farm_admin_status(
+    db: FarmDB,
+    user: UserLogin,
+    request: Json<NewApiFarmOwnerRequest>,
+) -> ApiResult<Json<ApiFarmOwnerRequest>> {
+    let mut request = request.into_inner();
+    request.sanitize();
+    request.validate()?;
+    let request = farm_owner_request::submit(&db, user.0.id, request.motivation, request.evidence)
+        .await?
+        .ok_or_else(|| {
+            ValidationApiError::for_field("farmowner", "Farm owner status already granted or requested")
+        })?;
+    Ok(Json(request.into()))
+}
+
+/// Latest request of the user to become a farm owner, with the decision once taken.
+#[get("/request-admin")]
+async fn farm_admin_request(db: FarmDB, user: UserLogin) -> ApiResult<Json<ApiFarmOwnerRequest>> {
+    let request = farm_owner_request::latest_of_user(&db, user.0.id)
+        .await?
+        .ok_or(ApiError::NotFound)?;
+    Ok(Json(request.into()))
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::api::v1::farm_owner_requests::NewApiFarmOwnerRequest;
     use crate::api::v1::test_utils::{create_untracked_client, get_current_user, login_user, WithAuthorization};
     use crate::api::v1::users::{ApiFarmOwnerStatus, NewApiUser};
     use crate::api::v1::users::PasswordChangeRequest;
@@ -393,15 +422,20 @@ mod tests {
             .await
             .expect("failed to check user login");
         assert!(password_check);
-        // request farm admin status
+        // request farm admin status, which waits for a sysadmin
+        let request = NewApiFarmOwnerRequest {
+            motivation: "I sell eggs at my farm gate.".to_string(),
+            evidence: None,
+        };
         let req = client.post("/api/v1/users/request-admin");
         let response = req
+            .body(serde_json::to_string(&request).expect("failed to serialize request"))
             .auth(&token)
             .dispatch()
             .await;
         assert_eq!(response.status(), Status::Ok);
         let current_user = get_current_user(&client, token.clone()).await;
-        assert_eq!(current_user.farmowner, ApiFarmOwnerStatus::Yes);
+        assert_eq!(current_user.farmowner, ApiFarmOwnerStatus::Requested);
         // delete created user
         let req = client.post("/api/v1/users/delete-current");
         let response = req
@@ -0,0 +1,29 @@
+/// Zoom level from which farms are shown individually instead of in clusters.
+pub const CLUSTER_MAX_ZOOM: u8 = 13;
+
+/// Edge length of a grid cell in screen pixels of 256 pixel map tiles.
+const CELL_SIZE_PX: f64 = 64.0;
+
+/// Width of the world in Web Mercator metres.
+const WORLD_SIZE_M: f64 = 2.0 * std::f64::consts::PI * 6_378_137.0;
+
+/// Edge length in Web Mercator metres of the grid cells clusters are aggregated in, chosen so that
+/// cells cover the same screen area at every zoom level.
+pub fn cell_size(zoom: u8) -> f64 {
+    let cells_per_axis = 256.0 * (1u64 << zoom) as f64 / CELL_SIZE_PX;
+    WORLD_SIZE_M / cells_per_axis
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clustering::cell_size;
+
+    #[test]
+    fn cell_sizes() {
+        // four cells per axis for the whole world
+        assert!((cell_size(0) - 10_018_754.17).abs() < 0.01);
+        // cells get smaller with every zoom level
+        assert!((cell_size(1) * 2.0 - cell_size(0)).abs() < 1e-6);
+        assert!((cell_size(14) - 611.5).abs() < 0.1);
+    }
+}
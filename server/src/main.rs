@@ -1,4 +1,8 @@
 mod api;
+mod clustering;
+mod photos;
+mod schedule;
+mod tiles;
 mod validation;
 
 use crate::api::v1::ident;
@@ -30,7 +34,7 @@ fn make_cors() -> Cors {
         .allowed_headers(AllowedHeaders::all())
         .expose_headers(["Authorization"].iter().map(ToString::to_string).collect())
         .allowed_methods(
-            vec![Method::Get, Method::Post, Method::Delete]
+            vec![Method::Get, Method::Post, Method::Put, Method::Patch, Method::Delete]
                 .into_iter()
                 .map(From::from)
                 .collect()
@@ -0,0 +1,365 @@
+//! Processing and storage of farm photos. Uploads are decoded and encoded again in several sizes,
+//! which drops all metadata of the original file, including EXIF GPS positions.
+
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageError, ImageFormat, ImageReader};
+use object_store::aws::{AmazonS3, AmazonS3Builder};
+use object_store::path::Path as StorePath;
+use object_store::{ObjectStore, PutPayload};
+use rocket::async_trait;
+use rocket::tokio::fs;
+use std::env;
+use std::io::{self, Cursor};
+use std::path::PathBuf;
+use uuid::Uuid;
+
+/// Quality of stored JPEG files.
+const JPEG_QUALITY: u8 = 85;
+
+/// Sizes every photo is stored in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PhotoSize {
+    Original,
+    Large,
+    Medium,
+    Small,
+}
+
+impl PhotoSize {
+    pub const ALL: [PhotoSize; 4] = [PhotoSize::Original, PhotoSize::Large, PhotoSize::Medium, PhotoSize::Small];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhotoSize::Original => "original",
+            PhotoSize::Large => "large",
+            PhotoSize::Medium => "medium",
+            PhotoSize::Small => "small",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|size| size.name() == name)
+    }
+
+    /// Longest edge in pixels, `None` to keep the size of the upload.
+    fn max_edge(&self) -> Option<u32> {
+        match self {
+            PhotoSize::Original => None,
+            PhotoSize::Large => Some(1280),
+            PhotoSize::Medium => Some(640),
+            PhotoSize::Small => Some(200),
+        }
+    }
+}
+
+/// Key of one size of a photo in the storage, `extension` being that of its [ImageFormat].
+pub fn photo_key(storage_id: Uuid, size: PhotoSize, extension: &str) -> String {
+    format!("{storage_id}/{}.{extension}", size.name())
+}
+
+#[derive(Debug)]
+pub enum PhotoError {
+    /// The upload is no JPEG, PNG or WebP image.
+    UnsupportedFormat,
+    Image(ImageError),
+}
+
+impl From<ImageError> for PhotoError {
+    fn from(value: ImageError) -> Self {
+        Self::Image(value)
+    }
+}
+
+pub struct ProcessedPhoto {
+    /// JPEG, or PNG for images with transparency.
+    pub format: ImageFormat,
+    pub width: u32,
+    pub height: u32,
+    pub files: Vec<(PhotoSize, Vec<u8>)>,
+}
+
+impl ProcessedPhoto {
+    pub fn extension(&self) -> &'static str {
+        self.format.extensions_str()[0]
+    }
+}
+
+/// Decodes an uploaded JPEG, PNG or WebP image, turns it upright according to its EXIF orientation
+/// and encodes it in all [PhotoSize]s.
+pub fn process_photo(data: &[u8]) -> Result<ProcessedPhoto, PhotoError> {
+    let format = image::guess_format(data).map_err(|_| PhotoError::UnsupportedFormat)?;
+    if !matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP) {
+        return Err(PhotoError::UnsupportedFormat);
+    }
+    let mut decoder = ImageReader::with_format(Cursor::new(data), format).into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let format = if image.color().has_alpha() { ImageFormat::Png } else { ImageFormat::Jpeg };
+    let files = PhotoSize::ALL
+        .into_iter()
+        .map(|size| {
+            let resized = match size.max_edge() {
+                Some(edge) if image.width() > edge || image.height() > edge => {
+                    image.resize(edge, edge, FilterType::Triangle)
+                }
+                _ => image.clone(),
+            };
+            Ok((size, encode(&resized, format)?))
+        })
+        .collect::<Result<_, ImageError>>()?;
+    Ok(ProcessedPhoto {
+        format,
+        width: image.width(),
+        height: image.height(),
+        files,
+    })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>, ImageError> {
+    let mut data = Vec::new();
+    if format == ImageFormat::Png {
+        DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(PngEncoder::new(&mut data))?;
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+    }
+    Ok(data)
+}
+
+#[derive(Debug)]
+pub struct StorageError(pub String);
+
+impl From<io::Error> for StorageError {
+    fn from(value: io::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl From<object_store::Error> for StorageError {
+    fn from(value: object_store::Error) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Place the photo files are kept in, addressed by keys like `<uuid>/small.jpg`.
+#[async_trait]
+pub trait PhotoStorage: Send + Sync {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), StorageError>;
+
+    /// `None` if nothing is stored under the key.
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError>;
+
+    /// Deleting a key that doesn't exist is no error.
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+}
+
+/// Storage selected by `PHOTO_STORAGE`: `s3` for [S3Storage], anything else for [LocalStorage].
+pub fn storage_from_env() -> Box<dyn PhotoStorage> {
+    if env::var("PHOTO_STORAGE").is_ok_and(|storage| storage == "s3") {
+        Box::new(S3Storage::from_env().expect("S3 photo storage must be configured"))
+    } else {
+        Box::new(LocalStorage::from_env())
+    }
+}
+
+/// Files in a directory of the local file system.
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// Directory from `PHOTO_PATH`, `photos` in the working directory if not set.
+    pub fn from_env() -> Self {
+        let root = env::var("PHOTO_PATH").map(PathBuf::from).unwrap_or_else(|_| {
+            #[cfg(not(test))]
+            let default_path = PathBuf::from("photos");
+            #[cfg(test)]
+            let default_path = env::temp_dir().join("farmers-photos");
+            default_path
+        });
+        Self::new(root)
+    }
+}
+
+#[async_trait]
+impl PhotoStorage for LocalStorage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), StorageError> {
+        let path = self.root.join(key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(path, data).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match fs::read(self.root.join(key)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.root.join(key);
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+        if let Some(parent) = path.parent().filter(|parent| *parent != self.root) {
+            // only succeeds once the last size of the photo is gone
+            let _ = fs::remove_dir(parent).await;
+        }
+        Ok(())
+    }
+}
+
+/// Objects in a bucket of S3 or a compatible service like MinIO.
+pub struct S3Storage {
+    store: AmazonS3,
+}
+
+impl S3Storage {
+    /// Configured by the usual `AWS_*` variables, like `AWS_BUCKET`, `AWS_ACCESS_KEY_ID`,
+    /// `AWS_SECRET_ACCESS_KEY` and `AWS_REGION`. For MinIO, `AWS_ENDPOINT` points to the server and
+    /// `AWS_ALLOW_HTTP=true` allows it to be reached without TLS.
+    pub fn from_env() -> Result<Self, StorageError> {
+        Ok(Self {
+            store: AmazonS3Builder::from_env().build()?,
+        })
+    }
+}
+
+#[async_trait]
+impl PhotoStorage for S3Storage {
+    async fn put(&self, key: &str, data: Vec<u8>) -> Result<(), StorageError> {
+        self.store.put(&StorePath::from(key), PutPayload::from(data)).await?;
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, StorageError> {
+        match self.store.get(&StorePath::from(key)).await {
+            Ok(result) => Ok(Some(result.bytes().await?.to_vec())),
+            Err(object_store::Error::NotFound { .. }) => Ok(None),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        match self.store.delete(&StorePath::from(key)).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::photos::{
+        LocalStorage, PhotoError, PhotoSize, PhotoStorage, S3Storage, photo_key, process_photo,
+    };
+    use image::codecs::jpeg::JpegEncoder;
+    use image::codecs::webp::WebPEncoder;
+    use image::{DynamicImage, ImageEncoder, ImageFormat, RgbImage, RgbaImage};
+    use std::env;
+    use uuid::Uuid;
+
+    /// EXIF data in TIFF layout with an orientation of 6, rotating the image by 90° clockwise.
+    const EXIF_ROTATED: &[u8] = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0\x06\0\0\0\0\0\0\0";
+
+    fn jpeg_with_exif(width: u32, height: u32) -> Vec<u8> {
+        let image = RgbImage::from_pixel(width, height, image::Rgb([200, 120, 40]));
+        let mut data = Vec::new();
+        let mut encoder = JpegEncoder::new(&mut data);
+        encoder.set_exif_metadata(EXIF_ROTATED.to_vec()).expect("exif supported");
+        encoder
+            .write_image(image.as_raw(), width, height, image::ExtendedColorType::Rgb8)
+            .expect("failed to encode jpeg");
+        data
+    }
+
+    #[test]
+    fn jpeg_is_rotated_and_stripped() {
+        let upload = jpeg_with_exif(1600, 900);
+        assert!(upload.windows(4).any(|w| w == b"Exif"));
+
+        let photo = process_photo(&upload).expect("failed to process photo");
+        assert_eq!(ImageFormat::Jpeg, photo.format);
+        assert_eq!("jpg", photo.extension());
+        assert_eq!((900, 1600), (photo.width, photo.height));
+        assert_eq!(4, photo.files.len());
+        for (size, data) in &photo.files {
+            assert!(!data.windows(4).any(|w| w == b"Exif"), "{size:?} keeps EXIF data");
+            let image = image::load_from_memory(data).expect("failed to decode stored photo");
+            let expected = match size {
+                PhotoSize::Original => (900, 1600),
+                PhotoSize::Large => (720, 1280),
+                PhotoSize::Medium => (360, 640),
+                PhotoSize::Small => (113, 200),
+            };
+            assert_eq!(expected, (image.width(), image.height()), "{size:?}");
+        }
+    }
+
+    #[test]
+    fn transparency_is_kept_as_png() {
+        let image = RgbaImage::from_pixel(50, 40, image::Rgba([10, 200, 10, 128]));
+        let mut upload = Vec::new();
+        DynamicImage::ImageRgba8(image)
+            .write_with_encoder(WebPEncoder::new_lossless(&mut upload))
+            .expect("failed to encode webp");
+
+        let photo = process_photo(&upload).expect("failed to process photo");
+        assert_eq!(ImageFormat::Png, photo.format);
+        // smaller than all sizes, so never scaled up
+        for (_, data) in &photo.files {
+            let image = image::load_from_memory(data).expect("failed to decode stored photo");
+            assert_eq!((50, 40), (image.width(), image.height()));
+            assert!(image.color().has_alpha());
+        }
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        assert!(matches!(process_photo(b"GIF89a\x01\0\x01\0"), Err(PhotoError::UnsupportedFormat)));
+        assert!(matches!(process_photo(b"no image at all"), Err(PhotoError::UnsupportedFormat)));
+        assert!(matches!(process_photo(b"\x89PNG\r\n\x1a\n broken"), Err(PhotoError::Image(_))));
+    }
+
+    async fn storage_roundtrip(storage: &dyn PhotoStorage) {
+        let key = photo_key(Uuid::new_v4(), PhotoSize::Small, "jpg");
+        assert!(key.ends_with("/small.jpg"));
+        assert_eq!(None, storage.get(&key).await.expect("failed to get photo"));
+        storage.put(&key, vec![1, 2, 3]).await.expect("failed to put photo");
+        assert_eq!(Some(vec![1, 2, 3]), storage.get(&key).await.expect("failed to get photo"));
+        storage.delete(&key).await.expect("failed to delete photo");
+        assert_eq!(None, storage.get(&key).await.expect("failed to get photo"));
+        storage.delete(&key).await.expect("failed to delete missing photo");
+    }
+
+    #[tokio::test]
+    async fn local_storage() {
+        let root = env::temp_dir().join(format!("farmers-photos-{}", Uuid::new_v4()));
+        storage_roundtrip(&LocalStorage::new(root.clone())).await;
+        assert_eq!(0, std::fs::read_dir(&root).expect("root exists").count());
+        std::fs::remove_dir(root).expect("failed to remove root");
+    }
+
+    /// Run against a local MinIO, for example with `AWS_ENDPOINT=http://localhost:9000`,
+    /// `AWS_ALLOW_HTTP=true`, `AWS_BUCKET`, `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` set.
+    #[tokio::test]
+    #[ignore = "needs an S3 compatible server configured by AWS_* variables"]
+    async fn s3_storage() {
+        let storage = S3Storage::from_env().expect("S3 storage not configured");
+        storage_roundtrip(&storage).await;
+    }
+}
@@ -0,0 +1,221 @@
+use chrono::{DateTime, Datelike, Days, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone};
+use chrono_tz::Tz;
+use database::farm::{OpeningHours, OpeningHoursException};
+
+/// Number of days after the requested instant, or after the end of a longer closure, that are
+/// searched for the next opening.
+const LOOKAHEAD_DAYS: u64 = 8;
+
+/// Whether a farm is open at a given instant and when this changes next.
+#[derive(Debug, PartialEq)]
+pub struct OpenStatus {
+    pub is_open: bool,
+    /// Start of the next opening period that does not contain the requested instant.
+    pub next_opening: Option<DateTime<Tz>>,
+    /// End of the current opening period. `None` if closed or open without end in sight.
+    pub closes_at: Option<DateTime<Tz>>,
+}
+
+/// Computes the open status of a weekly schedule at `at`. Opening hours are interpreted as local
+/// times in the time zone of `at`. Ranges closing before they open continue on the following day
+/// and adjacent ranges are treated as one continuous opening period. Days covered by an exception
+/// ignore the weekly schedule and are only open during the hours of their exceptions.
+pub fn open_status(
+    hours: &[OpeningHours],
+    exceptions: &[OpeningHoursException],
+    at: DateTime<Tz>,
+) -> OpenStatus {
+    let local = at.naive_local();
+    let last_day = last_day(exceptions, local.date());
+    let periods = opening_periods(hours, exceptions, local.date(), last_day);
+    let window_end = (last_day + Days::new(1)).and_time(NaiveTime::MIN);
+    let tz = at.timezone();
+    let current = periods.iter().find(|(start, end)| *start <= local && local < *end);
+    let next = periods.iter().find(|(start, _)| *start > local);
+    OpenStatus {
+        is_open: current.is_some(),
+        next_opening: next.map(|(start, _)| to_instant(&tz, *start)),
+        closes_at: current
+            .filter(|(_, end)| *end < window_end)
+            .map(|(_, end)| to_instant(&tz, *end)),
+    }
+}
+
+pub fn is_open_at(
+    hours: &[OpeningHours],
+    exceptions: &[OpeningHoursException],
+    at: DateTime<Tz>,
+) -> bool {
+    open_status(hours, exceptions, at).is_open
+}
+
+/// Last day to search for opening periods. Exceptions within the lookahead extend it.
+fn last_day(exceptions: &[OpeningHoursException], from: NaiveDate) -> NaiveDate {
+    let mut last_day = from + Days::new(LOOKAHEAD_DAYS);
+    let mut relevant: Vec<&OpeningHoursException> =
+        exceptions.iter().filter(|e| e.end_date >= from).collect();
+    relevant.sort_by_key(|e| e.start_date);
+    for exception in relevant {
+        if exception.start_date <= last_day {
+            last_day = last_day.max(exception.end_date + Days::new(LOOKAHEAD_DAYS));
+        }
+    }
+    last_day
+}
+
+/// Concrete, merged opening periods from the day before `from` until `last_day`.
+fn opening_periods(
+    hours: &[OpeningHours],
+    exceptions: &[OpeningHoursException],
+    from: NaiveDate,
+    last_day: NaiveDate,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut periods: Vec<(NaiveDateTime, NaiveDateTime)> = (from - Days::new(1))
+        .iter_days()
+        .take_while(|day| *day <= last_day)
+        .flat_map(|day| day_periods(hours, exceptions, day))
+        .collect();
+    periods.sort();
+    let mut merged: Vec<(NaiveDateTime, NaiveDateTime)> = Vec::with_capacity(periods.len());
+    for (start, end) in periods {
+        match merged.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn day_periods(
+    hours: &[OpeningHours],
+    exceptions: &[OpeningHoursException],
+    day: NaiveDate,
+) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let day_exceptions: Vec<&OpeningHoursException> = exceptions
+        .iter()
+        .filter(|e| e.start_date <= day && day <= e.end_date)
+        .collect();
+    if day_exceptions.is_empty() {
+        let weekday = day.weekday().num_days_from_monday() as i32;
+        hours
+            .iter()
+            .filter(|h| h.weekday == weekday)
+            .map(|h| period(day, h.open, h.close))
+            .collect()
+    } else {
+        day_exceptions
+            .into_iter()
+            .filter_map(|e| e.open.zip(e.close))
+            .map(|(open, close)| period(day, open, close))
+            .collect()
+    }
+}
+
+fn period(day: NaiveDate, open: NaiveTime, close: NaiveTime) -> (NaiveDateTime, NaiveDateTime) {
+    let close_day = if close <= open { day + Days::new(1) } else { day };
+    (day.and_time(open), close_day.and_time(close))
+}
+
+/// Maps a local time back to an instant. Times skipped by a daylight saving transition are moved
+/// to the first valid instant after the gap.
+fn to_instant(tz: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+    let mut candidate = local;
+    loop {
+        if let Some(instant) = tz.from_local_datetime(&candidate).earliest() {
+            return instant;
+        }
+        candidate += TimeDelta::minutes(15);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::schedule::open_status;
+    use chrono::{NaiveDate, NaiveTime, TimeZone};
+    use chrono_tz::Europe::Zurich;
+    use database::farm::{OpeningHours, OpeningHoursException};
+
+    fn hours(weekday: i32, open: u32, close: u32) -> OpeningHours {
+        OpeningHours {
+            id: 0,
+            farm_id: 0,
+            weekday,
+            open: NaiveTime::from_hms_opt(open, 0, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(close, 0, 0).unwrap(),
+        }
+    }
+
+    #[test]
+    fn open_and_closed() {
+        // 2025-06-02 is a Monday
+        let schedule = vec![hours(0, 8, 12), hours(0, 13, 18), hours(4, 20, 2), hours(5, 2, 4)];
+
+        let status = open_status(&schedule, &[], Zurich.with_ymd_and_hms(2025, 6, 2, 10, 0, 0).unwrap());
+        assert!(status.is_open);
+        assert_eq!(status.closes_at, Some(Zurich.with_ymd_and_hms(2025, 6, 2, 12, 0, 0).unwrap()));
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 2, 13, 0, 0).unwrap()));
+
+        let status = open_status(&schedule, &[], Zurich.with_ymd_and_hms(2025, 6, 2, 12, 0, 0).unwrap());
+        assert!(!status.is_open);
+        assert_eq!(status.closes_at, None);
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 2, 13, 0, 0).unwrap()));
+
+        let status = open_status(&schedule, &[], Zurich.with_ymd_and_hms(2025, 6, 2, 19, 0, 0).unwrap());
+        assert!(!status.is_open);
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 6, 20, 0, 0).unwrap()));
+
+        // overnight range continues seamlessly into the following morning
+        let status = open_status(&schedule, &[], Zurich.with_ymd_and_hms(2025, 6, 7, 1, 0, 0).unwrap());
+        assert!(status.is_open);
+        assert_eq!(status.closes_at, Some(Zurich.with_ymd_and_hms(2025, 6, 7, 4, 0, 0).unwrap()));
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 9, 8, 0, 0).unwrap()));
+    }
+
+    #[test]
+    fn always_open_and_never_open() {
+        let schedule: Vec<OpeningHours> = (0..7).map(|d| hours(d, 0, 0)).collect();
+        let status = open_status(&schedule, &[], Zurich.with_ymd_and_hms(2025, 6, 2, 10, 0, 0).unwrap());
+        assert!(status.is_open);
+        assert_eq!(status.closes_at, None);
+        assert_eq!(status.next_opening, None);
+
+        let status = open_status(&[], &[], Zurich.with_ymd_and_hms(2025, 6, 2, 10, 0, 0).unwrap());
+        assert!(!status.is_open);
+        assert_eq!(status.next_opening, None);
+    }
+
+    fn exception(start: (i32, u32, u32), end: (i32, u32, u32), hours: Option<(u32, u32)>) -> OpeningHoursException {
+        OpeningHoursException {
+            id: 0,
+            farm_id: 0,
+            start_date: NaiveDate::from_ymd_opt(start.0, start.1, start.2).unwrap(),
+            end_date: NaiveDate::from_ymd_opt(end.0, end.1, end.2).unwrap(),
+            open: hours.map(|(open, _)| NaiveTime::from_hms_opt(open, 0, 0).unwrap()),
+            close: hours.map(|(_, close)| NaiveTime::from_hms_opt(close, 0, 0).unwrap()),
+            description: None,
+        }
+    }
+
+    #[test]
+    fn exceptions_override_weekly_hours() {
+        let schedule: Vec<OpeningHours> = (0..5).map(|d| hours(d, 8, 18)).collect();
+        let exceptions = vec![
+            // closed over the whole week of 2025-06-09
+            exception((2025, 6, 9), (2025, 6, 13), None),
+            // shorter hours on 2025-06-03
+            exception((2025, 6, 3), (2025, 6, 3), Some((10, 12))),
+        ];
+
+        let status = open_status(&schedule, &exceptions, Zurich.with_ymd_and_hms(2025, 6, 3, 9, 0, 0).unwrap());
+        assert!(!status.is_open);
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 3, 10, 0, 0).unwrap()));
+
+        let status = open_status(&schedule, &exceptions, Zurich.with_ymd_and_hms(2025, 6, 3, 11, 0, 0).unwrap());
+        assert!(status.is_open);
+        assert_eq!(status.closes_at, Some(Zurich.with_ymd_and_hms(2025, 6, 3, 12, 0, 0).unwrap()));
+
+        let status = open_status(&schedule, &exceptions, Zurich.with_ymd_and_hms(2025, 6, 6, 19, 0, 0).unwrap());
+        assert!(!status.is_open);
+        assert_eq!(status.next_opening, Some(Zurich.with_ymd_and_hms(2025, 6, 16, 8, 0, 0).unwrap()));
+    }
+}
@@ -0,0 +1,280 @@
+use database::location::BoundingBox;
+use prost::Message;
+use std::f64::consts::PI;
+
+/// Size of a tile in its own coordinate space.
+pub const EXTENT: u32 = 4096;
+
+/// Distance in tile coordinates outside the tile edges within which features are still encoded,
+/// so symbols crossing an edge are not cut off.
+const BUFFER: u32 = 64;
+
+/// Highest zoom level served.
+pub const MAX_ZOOM: u8 = 22;
+
+/// Latitude limit of the Web Mercator projection.
+const MAX_LATITUDE: f64 = 85.051_128_78;
+
+/// Tile address in the XYZ scheme used by MapLibre.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TileId {
+    pub z: u8,
+    pub x: u32,
+    pub y: u32,
+}
+
+impl TileId {
+    /// Returns `None` for tiles outside the tile pyramid.
+    pub fn new(z: u8, x: u32, y: u32) -> Option<Self> {
+        let tiles = 1u64 << z.min(MAX_ZOOM);
+        (z <= MAX_ZOOM && (x as u64) < tiles && (y as u64) < tiles).then_some(Self { z, x, y })
+    }
+
+    fn tiles(&self) -> f64 {
+        (1u64 << self.z) as f64
+    }
+
+    /// Area covered by the tile including its buffer.
+    pub fn bbox(&self) -> BoundingBox {
+        let buffer = BUFFER as f64 / EXTENT as f64;
+        let tiles = self.tiles();
+        let lon = |x: f64| (x / tiles * 360.0 - 180.0).clamp(-180.0, 180.0);
+        let lat = |y: f64| {
+            let y = (y / tiles).clamp(0.0, 1.0);
+            (PI * (1.0 - 2.0 * y)).sinh().atan().to_degrees()
+        };
+        BoundingBox {
+            min_lat: lat(self.y as f64 + 1.0 + buffer),
+            min_lon: lon(self.x as f64 - buffer),
+            max_lat: lat(self.y as f64 - buffer),
+            max_lon: lon(self.x as f64 + 1.0 + buffer),
+        }
+    }
+
+    /// Position in tile coordinates, `(0, 0)` being the top left corner.
+    pub fn project(&self, lat: f64, lon: f64) -> (i32, i32) {
+        let tiles = self.tiles();
+        let (x, y) = mercator(lat, lon);
+        (
+            ((x * tiles - self.x as f64) * EXTENT as f64).round() as i32,
+            ((y * tiles - self.y as f64) * EXTENT as f64).round() as i32,
+        )
+    }
+}
+
+/// Web Mercator position scaled to the unit square, `(0, 0)` being the top left corner of the
+/// world.
+pub fn mercator(lat: f64, lon: f64) -> (f64, f64) {
+    let lat = lat.clamp(-MAX_LATITUDE, MAX_LATITUDE).to_radians();
+    let x = (lon + 180.0) / 360.0;
+    let y = (1.0 - (lat.tan() + 1.0 / lat.cos()).ln() / PI) / 2.0;
+    (x, y)
+}
+
+/// Property value of a feature.
+pub enum PropertyValue {
+    String(String),
+    Bool(bool),
+    Uint(u64),
+}
+
+/// Point feature to encode into a layer.
+pub struct PointFeature {
+    pub id: u64,
+    pub lat: f64,
+    pub lon: f64,
+    pub properties: Vec<(&'static str, PropertyValue)>,
+}
+
+/// Encodes the features as a single layer Mapbox Vector Tile (specification version 2.1).
+/// Features outside the tile and its buffer are left out.
+pub fn encode_layer(tile: TileId, name: &str, features: Vec<PointFeature>) -> Vec<u8> {
+    let mut layer = Layer {
+        version: 2,
+        name: name.to_string(),
+        extent: Some(EXTENT),
+        ..Default::default()
+    };
+    let range = -(BUFFER as i32)..=(EXTENT + BUFFER) as i32;
+    for feature in features {
+        let (x, y) = tile.project(feature.lat, feature.lon);
+        if !range.contains(&x) || !range.contains(&y) {
+            continue;
+        }
+        let tags = feature
+            .properties
+            .into_iter()
+            .flat_map(|(key, value)| [layer.key_index(key), layer.value_index(value)])
+            .collect();
+        layer.features.push(Feature {
+            id: Some(feature.id),
+            tags,
+            r#type: Some(GeomType::Point as i32),
+            geometry: vec![command(MOVE_TO, 1), zigzag(x), zigzag(y)],
+        });
+    }
+    Tile { layers: vec![layer] }.encode_to_vec()
+}
+
+const MOVE_TO: u32 = 1;
+
+fn command(id: u32, count: u32) -> u32 {
+    (id & 0x7) | (count << 3)
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+impl Layer {
+    fn key_index(&mut self, key: &str) -> u32 {
+        match self.keys.iter().position(|k| k == key) {
+            Some(index) => index as u32,
+            None => {
+                self.keys.push(key.to_string());
+                self.keys.len() as u32 - 1
+            }
+        }
+    }
+
+    fn value_index(&mut self, value: PropertyValue) -> u32 {
+        let value = match value {
+            PropertyValue::String(s) => Value {
+                string_value: Some(s),
+                ..Default::default()
+            },
+            PropertyValue::Bool(b) => Value {
+                bool_value: Some(b),
+                ..Default::default()
+            },
+            PropertyValue::Uint(u) => Value {
+                uint_value: Some(u),
+                ..Default::default()
+            },
+        };
+        match self.values.iter().position(|v| *v == value) {
+            Some(index) => index as u32,
+            None => {
+                self.values.push(value);
+                self.values.len() as u32 - 1
+            }
+        }
+    }
+}
+
+// Messages of the vector tile protobuf schema, https://github.com/mapbox/vector-tile-spec
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Tile {
+    #[prost(message, repeated, tag = "3")]
+    pub layers: Vec<Layer>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Layer {
+    #[prost(uint32, required, tag = "15")]
+    pub version: u32,
+    #[prost(string, required, tag = "1")]
+    pub name: String,
+    #[prost(message, repeated, tag = "2")]
+    pub features: Vec<Feature>,
+    #[prost(string, repeated, tag = "3")]
+    pub keys: Vec<String>,
+    #[prost(message, repeated, tag = "4")]
+    pub values: Vec<Value>,
+    #[prost(uint32, optional, tag = "5")]
+    pub extent: Option<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Feature {
+    #[prost(uint64, optional, tag = "1")]
+    pub id: Option<u64>,
+    #[prost(uint32, repeated, packed = "true", tag = "2")]
+    pub tags: Vec<u32>,
+    #[prost(enumeration = "GeomType", optional, tag = "3")]
+    pub r#type: Option<i32>,
+    #[prost(uint32, repeated, packed = "true", tag = "4")]
+    pub geometry: Vec<u32>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Value {
+    #[prost(string, optional, tag = "1")]
+    pub string_value: Option<String>,
+    #[prost(float, optional, tag = "2")]
+    pub float_value: Option<f32>,
+    #[prost(double, optional, tag = "3")]
+    pub double_value: Option<f64>,
+    #[prost(int64, optional, tag = "4")]
+    pub int_value: Option<i64>,
+    #[prost(uint64, optional, tag = "5")]
+    pub uint_value: Option<u64>,
+    #[prost(sint64, optional, tag = "6")]
+    pub sint_value: Option<i64>,
+    #[prost(bool, optional, tag = "7")]
+    pub bool_value: Option<bool>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, prost::Enumeration)]
+#[repr(i32)]
+pub enum GeomType {
+    Unknown = 0,
+    Point = 1,
+    Linestring = 2,
+    Polygon = 3,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tiles::{PointFeature, PropertyValue, Tile, TileId, encode_layer};
+    use prost::Message;
+
+    #[test]
+    fn tile_coordinates() {
+        assert_eq!(None, TileId::new(1, 2, 0));
+        let world = TileId::new(0, 0, 0).unwrap();
+        assert_eq!((2048, 2048), world.project(0.0, 0.0));
+        assert_eq!((0, 0), world.project(85.051_128_78, -180.0));
+
+        // Zurich
+        let tile = TileId::new(10, 536, 358).unwrap();
+        let bbox = tile.bbox();
+        assert!(bbox.min_lon < 8.54 && 8.54 < bbox.max_lon);
+        assert!(bbox.min_lat < 47.37 && 47.37 < bbox.max_lat);
+        let (x, y) = tile.project(47.37, 8.54);
+        assert!((0..4096).contains(&x) && (0..4096).contains(&y));
+    }
+
+    #[test]
+    fn encode_points() {
+        let tile = TileId::new(0, 0, 0).unwrap();
+        let features = vec![
+            PointFeature {
+                id: 1,
+                lat: 0.0,
+                lon: 0.0,
+                properties: vec![
+                    ("name", PropertyValue::String("Farm".to_string())),
+                    ("open_now", PropertyValue::Bool(true)),
+                ],
+            },
+            PointFeature {
+                id: 2,
+                lat: 10.0,
+                lon: 10.0,
+                properties: vec![("open_now", PropertyValue::Bool(true)), ("count", PropertyValue::Uint(3))],
+            },
+        ];
+        let tile = Tile::decode(encode_layer(tile, "farms", features).as_slice()).expect("valid tile");
+        let layer = &tile.layers[0];
+        assert_eq!("farms", layer.name);
+        assert_eq!(vec!["name", "open_now", "count"], layer.keys);
+        assert_eq!(3, layer.values.len());
+        assert_eq!(Some(3), layer.values[2].uint_value);
+        assert_eq!(vec![0, 0, 1, 1], layer.features[0].tags);
+        assert_eq!(vec![1, 1, 2, 2], layer.features[1].tags);
+        // MoveTo(1), zigzag encoded 2048, 2048
+        assert_eq!(vec![9, 4096, 4096], layer.features[0].geometry);
+    }
+}
@@ -20,7 +20,6 @@ pub struct StringLengthCriteria {
 }
 
 impl StringLengthCriteria {
-    #[allow(dead_code)]
     pub fn new(min: usize, max: usize) -> Self {
         Self {
             min: Some(min),
@@ -35,7 +34,6 @@ impl StringLengthCriteria {
         }
     }
 
-    #[allow(dead_code)]
     pub fn max(max: usize) -> Self {
         Self {
             min: None,
@@ -155,7 +153,7 @@ pub struct EmailValidator;
 
 impl Validator<&str> for EmailValidator {
     fn validate(&self, value: &str) -> Result<(), ValidationError> {
-        RegexValidator::new(include_str!("email_regex.txt"))
+        RegexValidator::new(database::contact::EMAIL_REGEX)
             .expect("Cannot parse email regex")
             .validate(value)
             .map_err(|msg| ValidationError {
@@ -180,6 +178,12 @@ impl Validator<&str> for PasswordValidator {
     }
 }
 
+/// Normalizes an international phone number to E.164, see
+/// [`database::contact::normalize_phone_number`].
+pub fn normalize_phone_number(value: &str) -> Result<String, ValidationError> {
+    database::contact::normalize_phone_number(value).map_err(|messages| ValidationError { messages })
+}
+
 #[cfg(test)]
 mod tests {
     mod strings {
@@ -251,4 +255,19 @@ mod tests {
             assert_eq!(validator.validate(".").unwrap_err().messages.len(), 4);
         }
     }
+
+    mod phone {
+        use crate::validation::normalize_phone_number;
+
+        #[test]
+        fn phone_normalization() {
+            assert_eq!(normalize_phone_number("+41 (0)79 123 45 67").unwrap(), "+41791234567");
+            assert_eq!(normalize_phone_number("0049 30/123-456").unwrap(), "+4930123456");
+            assert_eq!(normalize_phone_number("+1 (555) 010.0199").unwrap(), "+15550100199");
+            assert!(normalize_phone_number("079 123 45 67").is_err());
+            assert!(normalize_phone_number("+41 79 CALL NOW").is_err());
+            assert!(normalize_phone_number("+0 123 456 789").is_err());
+            assert!(normalize_phone_number("+41 79 123 45 67 89 01 23").is_err());
+        }
+    }
 }
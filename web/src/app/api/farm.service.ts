@@ -1,9 +1,9 @@
 import { Injectable } from '@angular/core';
-import {HttpClient, HttpHeaders} from "@angular/common/http";
-import {Farm, FullFarm, NewFarm, NewUser, User} from "./models";
-import {Observable} from "rxjs";
+import {HttpClient, HttpHeaders, HttpParams} from "@angular/common/http";
+import {Farm, FullFarm, NewFarm, NewUser, Page, User} from "./models";
+import {EMPTY, Observable} from "rxjs";
 import {Api} from "./api";
-import {map} from "rxjs/operators";
+import {expand, map, reduce} from "rxjs/operators";
 
 @Injectable({
   providedIn: 'root'
@@ -14,14 +14,20 @@ export class FarmService {
       private http: HttpClient,
   ) { }
 
+  /** Loads all farms by following the cursors of the pages. */
   public getAll(): Observable<Farm[]> {
+    return this.getPage().pipe(
+        expand(page => page.next_cursor ? this.getPage(page.next_cursor) : EMPTY),
+        reduce((farms: Farm[], page) => farms.concat(page.items), []));
+  }
+
+  public getPage(cursor?: string): Observable<Page<Farm>> {
     const h = new HttpHeaders({ 'Content-Type': 'application/json' });
-    return this.http.get<Farm[]>(Api.API_BASE_URL + '/farms', {headers: h})
-        .pipe(map(response => {
-              const data: any = response;
-              return data;
-            }
-        ));
+    let params = new HttpParams().set('limit', 100);
+    if (cursor) {
+      params = params.set('cursor', cursor);
+    }
+    return this.http.get<Page<Farm>>(Api.API_BASE_URL + '/farms', {headers: h, params: params});
   }
 
   public getFull(id: number): Observable<FullFarm> {
@@ -2,6 +2,10 @@ export class Farm {
     constructor(public id: number, public name: string) {}
 }
 
+export class Page<T> {
+    constructor(public items: T[], public total_estimate: number, public next_cursor?: string) {}
+}
+
 export class ShopType {
     constructor(public id: number, public name: string) {}
 }
@@ -34,6 +38,26 @@ export enum FarmOwnerStatus {
     YES = 'Yes',
     NO = 'No',
     REQUESTED = 'Requested',
+    REJECTED = 'Rejected',
+}
+
+export class NewFarmOwnerRequest {
+    constructor(
+        public motivation: string,
+        public evidence?: string,
+    ) {}
+}
+
+export class FarmOwnerRequest {
+    constructor(
+        public id: number,
+        public motivation: string,
+        public status: FarmOwnerStatus,
+        public created: string,
+        public evidence?: string,
+        public decided?: string,
+        public reason?: string,
+    ) {}
 }
 
 export class User {
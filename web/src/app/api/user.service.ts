@@ -1,7 +1,7 @@
 import { Injectable } from '@angular/core';
 import {HttpClient, HttpHeaders} from "@angular/common/http";
 import {Observable} from "rxjs";
-import {NewUser, User} from "./models";
+import {FarmOwnerRequest, NewFarmOwnerRequest, NewUser, User} from "./models";
 import {Api} from "./api";
 import {map} from "rxjs/operators";
 
@@ -29,9 +29,14 @@ export class UserService {
     return this.http.post(Api.API_BASE_URL + '/users/change-password', change, {headers: h}).pipe(map(_res => true));
   }
 
-  public requestAdmin(): Observable<boolean> {
+  public requestAdmin(request: NewFarmOwnerRequest): Observable<FarmOwnerRequest> {
     const h = new HttpHeaders({ 'Content-Type': 'application/json' });
-    return this.http.post(Api.API_BASE_URL + '/users/request-admin', '', {headers: h}).pipe(map(_res => true));
+    return this.http.post<FarmOwnerRequest>(Api.API_BASE_URL + '/users/request-admin', request, {headers: h});
+  }
+
+  public getAdminRequest(): Observable<FarmOwnerRequest> {
+    const h = new HttpHeaders({ 'Content-Type': 'application/json' });
+    return this.http.get<FarmOwnerRequest>(Api.API_BASE_URL + '/users/request-admin', {headers: h});
   }
 
   public deleteCurrentUser(password: string): Observable<boolean> {
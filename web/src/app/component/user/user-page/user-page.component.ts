@@ -1,7 +1,7 @@
 import {Component, OnInit} from '@angular/core';
 import {AuthService} from "../../../auth.service";
 import {Router, RouterLink} from "@angular/router";
-import {FarmOwnerStatus, User} from "../../../api/models";
+import {FarmOwnerRequest, FarmOwnerStatus, NewFarmOwnerRequest, User} from "../../../api/models";
 import {UserService} from "../../../api/user.service";
 import {FormsModule, ReactiveFormsModule} from "@angular/forms";
 
@@ -19,6 +19,9 @@ export class UserPageComponent implements OnInit {
     user: User = new User('', '', '', '');
     submitting = false;
     password = '';
+    motivation = '';
+    evidence = '';
+    adminRequest?: FarmOwnerRequest;
 
     constructor(
         private authService: AuthService,
@@ -33,19 +36,31 @@ export class UserPageComponent implements OnInit {
             this.userService.getCurrentUser().subscribe(user => {
                 console.log(user);
                 this.user = user;
+                if (user.farmowner !== FarmOwnerStatus.NO) {
+                    this.userService.getAdminRequest().subscribe(request => this.adminRequest = request);
+                }
             });
         }
     }
 
+    validate_admin_request(): boolean {
+        return this.motivation.trim().length >= 10;
+    }
+
     request_admin_action(): void {
-        this.userService.requestAdmin().subscribe({
-            next: res => {
-                if (res) {
-                    this.userService.getCurrentUser().subscribe(user => {
-                        this.user = user;
-                        console.log(user);
-                    })
-                }
+        if (!this.validate_admin_request()) {
+            return;
+        }
+        const evidence = this.evidence.trim() || undefined;
+        this.userService.requestAdmin(new NewFarmOwnerRequest(this.motivation.trim(), evidence)).subscribe({
+            next: request => {
+                this.adminRequest = request;
+                this.motivation = '';
+                this.evidence = '';
+                this.userService.getCurrentUser().subscribe(user => {
+                    this.user = user;
+                    console.log(user);
+                })
             }
         })
     }